@@ -3,15 +3,34 @@ mod translate;
 mod uptime;
 mod wargaming;
 
-use kon_libs::{
-  KonError,
-  KonResult,
-  PoiseCtx
+use {
+  kon_libs::{
+    BINARY_PROPERTIES,
+    KonError,
+    KonResult,
+    PoiseCtx
+  },
+  poise::serenity_prelude::{
+    CreateEmbed,
+    CreateMessage,
+    GenericChannelId,
+    Http
+  }
+};
+
+pub use ilo::{
+  close_ilo_sessions,
+  close_ilo_subscriptions,
+  spawn_ilo_listener,
+  spawn_ilo_monitor
 };
 
 use {
   ilo::ilo,
-  translate::translate,
+  translate::{
+    translate,
+    translate_message
+  },
   uptime::uptime,
   wargaming::wargaming
 };
@@ -22,7 +41,20 @@ macro_rules! commands {
   }
 }
 
-pub fn register_cmds() -> Vec<poise::Command<(), KonError>> { commands!(deploy, ping, ilo, wargaming, translate, uptime) }
+pub fn register_cmds() -> Vec<poise::Command<(), KonError>> { commands!(deploy, ping, ilo, wargaming, translate, translate_message, uptime) }
+
+/// Post an embed to the logs channel, failures only end up in the console
+async fn post_kon_logs(
+  http: &Http,
+  embed: CreateEmbed<'_>
+) {
+  if let Err(e) = GenericChannelId::new(BINARY_PROPERTIES.kon_logs)
+    .send_message(http, CreateMessage::new().add_embed(embed))
+    .await
+  {
+    eprintln!("Kon[Logs] Couldn't post to the logs channel: {e}");
+  }
+}
 
 /// Deploy the commands globally or in a guild
 #[poise::command(prefix_command, owners_only, guild_only)]
@@ -1,49 +1,121 @@
+mod actions;
+mod backend;
+mod bios;
+mod boot;
+mod chart;
+mod discovery;
+mod history;
+mod hosts;
+mod inventory;
+mod ipmi;
+mod logs;
+mod manager;
+mod monitor;
+mod paginate;
+mod powercap;
+mod session;
+mod storage;
+mod subscriptions;
+#[cfg(test)]
+mod tests;
+mod tls;
+pub use {
+  monitor::spawn_ilo_monitor,
+  session::close_ilo_sessions,
+  subscriptions::{
+    close_ilo_subscriptions,
+    spawn_ilo_listener
+  }
+};
+
 use {
+  super::post_kon_logs,
+  actions::power_action,
+  backend::{
+    Bmc,
+    backend
+  },
+  bios::{
+    bios,
+    bios_set
+  },
+  boot::{
+    boot,
+    media
+  },
   dashmap::DashMap,
+  discovery::{
+    ResourceIds,
+    resource_ids
+  },
+  history::history,
+  hosts::{
+    BmcHost,
+    autocomplete_host,
+    find_host
+  },
+  inventory::inventory,
   kon_libs::{
     BINARY_PROPERTIES,
+    BmcProtocol,
     KonResult
   },
-  kon_tokens::token_path,
   lazy_static::lazy_static,
+  logs::{
+    clear_log,
+    events,
+    logs
+  },
+  manager::{
+    manager,
+    manager_reset
+  },
+  paginate::paginate,
   poise::{
     CreateReply,
     serenity_prelude::{
       CreateEmbed,
-      Timestamp
+      Timestamp,
+      User
     }
   },
+  powercap::powercap,
   reqwest::{
-    Client,
-    ClientBuilder,
-    Error as ReqError
+    Error as ReqError,
+    Method,
+    Response,
+    StatusCode
   },
   serde::{
     Deserialize,
+    Deserializer,
     Serialize,
     de::DeserializeOwned
   },
-  tokio::time::Duration
+  session::{
+    AUTH_TOKEN_HEADER,
+    session_token
+  },
+  std::{
+    error::Error,
+    fmt
+  },
+  storage::storage,
+  tls::{
+    client_for,
+    recheck_pin
+  },
+  tokio_native_tls::native_tls
 };
 
-const ILO_HOSTNAME: &str = "POMNI";
+/// Discord's limits for a single field's value, a whole embed and its field count
+const FIELD_LIMIT: usize = 1024;
+const EMBED_LIMIT: usize = 6000;
+const MAX_FIELDS: usize = 25;
+/// Kept out of [`EMBED_LIMIT`] for the title, description and footer that go along with the fields
+const EMBED_MARGIN: usize = 1000;
 
 lazy_static! {
-  static ref REQWEST_CLIENT: Client = ClientBuilder::new()
-    .danger_accept_invalid_certs(true)
-    .timeout(Duration::from_secs(15))
-    .pool_max_idle_per_host(6)
-    .pool_idle_timeout(Some(Duration::from_secs(30)))
-    .tcp_keepalive(Duration::from_secs(600))
-    .build()
-    .unwrap();
-  static ref SENSOR_NAMES: DashMap<&'static str, &'static str> = {
-    let m = DashMap::new();
-    m.insert("01-Inlet Ambient", "Inlet Ambient");
-    m.insert("04-P1 DIMM 1-6", "P1 DIMM 1-6");
-    m.insert("14-Chipset Zone", "Chipset Zone");
-    m
-  };
   static ref POST_STATES: DashMap<&'static str, &'static str> = {
     let m = DashMap::new();
     m.insert("FinishedPost", "Finished POST");
@@ -56,144 +128,624 @@ lazy_static! {
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Chassis {
+  #[serde(default)]
   fans:         Vec<Fan>,
+  #[serde(default)]
   temperatures: Vec<Temperature>
 }
 
+/// HP's iLO 4 uses `FanName`/`CurrentReading` while DMTF and everyone else
+/// uses `Name`/`Reading`, some vendors send both so they can't be aliased
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Fan {
-  current_reading: i32,
-  fan_name:        String,
-  status:          Status
+  #[serde(default, deserialize_with = "lenient_int")]
+  current_reading: Option<i32>,
+  fan_name:        Option<String>,
+  name:            Option<String>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  reading:         Option<i32>,
+  reading_units:   Option<String>,
+  #[serde(default)]
+  status:          Status,
+  units:           Option<String>
+}
+
+impl Fan {
+  fn label(&self) -> &str { self.fan_name.as_deref().or(self.name.as_deref()).unwrap_or("Unknown fan") }
+
+  fn value(&self) -> Option<i32> { self.current_reading.or(self.reading) }
+
+  fn unit(&self) -> &'static str {
+    match self.reading_units.as_deref().or(self.units.as_deref()) {
+      Some("Percent") | None => "%",
+      Some(_) => " RPM"
+    }
+  }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct Temperature {
-  current_reading:          i32,
+  #[serde(default)]
   name:                     String,
-  reading_celsius:          i32,
+  #[serde(default, deserialize_with = "lenient_int")]
+  reading_celsius:          Option<i32>,
+  #[serde(default)]
   status:                   Status,
-  units:                    String,
-  upper_threshold_critical: i32,
-  upper_threshold_fatal:    i32
+  #[serde(default, deserialize_with = "lenient_int")]
+  upper_threshold_critical: Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  upper_threshold_fatal:    Option<i32>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 struct Status {
   health: Option<String>,
-  state:  String
+  state:  Option<String>
+}
+
+impl Status {
+  /// Health if the BMC knows it, otherwise whatever state the component is in
+  fn summary(&self) -> &str { self.health.as_deref().or(self.state.as_deref()).unwrap_or("Unknown") }
+
+  /// Empty slots are still listed as members by most BMCs
+  fn is_absent(&self) -> bool { self.state.as_deref() == Some("Absent") }
 }
 
+/// iLO 4 puts the readings at the top level, DMTF keeps them in `PowerControl`
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 struct Power {
-  power_capacity_watts: i32,
-  power_consumed_watts: i32,
-  power_metrics:        PowerMetrics
+  #[serde(default, deserialize_with = "lenient_int")]
+  power_capacity_watts: Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  power_consumed_watts: Option<i32>,
+  #[serde(default)]
+  power_control:        Vec<PowerControl>,
+  power_metrics:        Option<PowerMetrics>,
+  #[serde(default)]
+  power_supplies:       Vec<PowerSupply>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct PowerControl {
+  #[serde(default, deserialize_with = "lenient_int")]
+  power_capacity_watts: Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  power_consumed_watts: Option<i32>,
+  power_limit:          Option<PowerLimit>,
+  power_metrics:        Option<PowerMetrics>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct PowerLimit {
+  limit_exception: Option<String>,
+  /// `null` when the server isn't capped
+  #[serde(default, deserialize_with = "lenient_int")]
+  limit_in_watts:  Option<i32>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 struct PowerMetrics {
-  average_consumed_watts: i32,
-  max_consumed_watts:     i32,
-  min_consumed_watts:     i32
+  #[serde(default, deserialize_with = "lenient_int")]
+  average_consumed_watts: Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  max_consumed_watts:     Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  min_consumed_watts:     Option<i32>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
+struct PowerSupply {
+  firmware_version:        Option<String>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  last_power_output_watts: Option<i32>,
+  model:                   Option<String>,
+  name:                    Option<String>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  power_capacity_watts:    Option<i32>,
+  serial_number:           Option<String>,
+  #[serde(default)]
+  status:                  Status
+}
+
+impl Power {
+  fn control(&self) -> Option<&PowerControl> { self.power_control.first() }
+
+  fn capacity(&self) -> Option<i32> { self.control().and_then(|c| c.power_capacity_watts).or(self.power_capacity_watts) }
+
+  fn consumed(&self) -> Option<i32> { self.control().and_then(|c| c.power_consumed_watts).or(self.power_consumed_watts) }
+
+  fn metrics(&self) -> Option<&PowerMetrics> { self.control().and_then(|c| c.power_metrics.as_ref()).or(self.power_metrics.as_ref()) }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
 struct System {
-  memory:            Memory,
-  model:             String,
-  oem:               Oem,
-  power_state:       String,
-  processor_summary: ProcessorSummary
+  bios_version:      Option<String>,
+  manufacturer:      Option<String>,
+  /// iLO 4 only
+  memory:            Option<Memory>,
+  memory_summary:    Option<Memory>,
+  model:             Option<String>,
+  oem:               Option<Oem>,
+  power_state:       Option<String>,
+  #[serde(default)]
+  processor_summary: ProcessorSummary,
+  serial_number:     Option<String>,
+  #[serde(rename = "SKU")]
+  sku:               Option<String>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Memory {
-  #[serde(rename = "TotalSystemMemoryGB")]
-  total_system_memory_gb: i32
+  #[serde(rename = "Status", default)]
+  status:                  Status,
+  #[serde(rename = "TotalSystemMemoryGB", default, deserialize_with = "lenient_int")]
+  total_system_memory_gb:  Option<i32>,
+  #[serde(rename = "TotalSystemMemoryGiB", default, deserialize_with = "lenient_int")]
+  total_system_memory_gib: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Memory {
+  fn total_gb(&self) -> Option<i32> { self.total_system_memory_gib.or(self.total_system_memory_gb) }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "PascalCase")]
 struct ProcessorSummary {
-  count: i32,
-  model: String
+  #[serde(default, deserialize_with = "lenient_int")]
+  count:                   Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  logical_processor_count: Option<i32>,
+  model:                   Option<String>,
+  #[serde(default)]
+  status:                  Status
 }
 
+/// Vendor specific extras, only the ones we know how to present are parsed
 #[derive(Serialize, Deserialize, Debug)]
 struct Oem {
   #[serde(rename = "Hp")]
-  hp: Hp
+  hp:         Option<HpeOem>,
+  #[serde(rename = "Hpe")]
+  hpe:        Option<HpeOem>,
+  #[serde(rename = "Dell")]
+  dell:       Option<DellOem>,
+  #[serde(rename = "Supermicro")]
+  supermicro: Option<SupermicroOem>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Hp {
+struct HpeOem {
   #[serde(rename = "PostState")]
-  post_state: String
+  post_state: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
+struct DellOem {
+  #[serde(rename = "DellSystem")]
+  dell_system: Option<DellSystem>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DellSystem {
+  current_rollup_status: Option<String>,
+  system_generation:     Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SupermicroOem {
+  #[serde(rename = "BoardID")]
+  board_id: Option<String>
+}
+
+/// Which vendor extras a [`System`] came with
+enum Vendor<'a> {
+  Hpe(&'a HpeOem),
+  Dell(&'a DellSystem),
+  Supermicro(&'a SupermicroOem),
+  Generic
+}
+
+impl System {
+  fn total_memory_gb(&self) -> Option<i32> {
+    self
+      .memory_summary
+      .as_ref()
+      .and_then(Memory::total_gb)
+      .or(self.memory.as_ref().and_then(Memory::total_gb))
+  }
+
+  fn vendor(&self) -> Vendor<'_> {
+    let Some(oem) = &self.oem else { return Vendor::Generic };
+
+    if let Some(hpe) = oem.hpe.as_ref().or(oem.hp.as_ref()) {
+      Vendor::Hpe(hpe)
+    } else if let Some(dell) = oem.dell.as_ref().and_then(|d| d.dell_system.as_ref()) {
+      Vendor::Dell(dell)
+    } else if let Some(smc) = &oem.supermicro {
+      Vendor::Supermicro(smc)
+    } else {
+      Vendor::Generic
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Default)]
 struct Event {
-  #[serde(rename = "Status")]
+  #[serde(rename = "Status", default)]
   status: Status
 }
 
 #[derive(Serialize, Deserialize)]
-/// HP calls this Integrated Management Log
+/// HP calls this Integrated Management Log, iLO 4 lists
+/// the entries in `Items` while DMTF expands them in `Members`
 struct Iml {
   #[serde(rename = "Items")]
-  items: Vec<ImlEntry>
+  items:   Option<Vec<ImlEntry>>,
+  #[serde(rename = "Members", default)]
+  members: Vec<ImlEntry>
+}
+
+impl Iml {
+  fn entries(&self) -> &[ImlEntry] { self.items.as_deref().unwrap_or(&self.members) }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct ImlEntry {
+  #[serde(default)]
   created:  String,
+  #[serde(default)]
   message:  String,
+  #[serde(default)]
   severity: String
 }
 
+/// Accept whole and fractional numbers alike since vendors can't agree on either,
+/// placeholders like `"N/A"` are treated as a missing reading
+fn lenient_int<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+  let value = serde_json::Value::deserialize(deserializer)?;
+  Ok(value.as_i64().map(|i| i as i32).or_else(|| value.as_f64().map(|f| f.round() as i32)))
+}
+
 enum RedfishEndpoint {
   Thermal,
   Power,
   System,
   EventService,
-  LogServices
+  LogServices,
+  EventLog,
+  ClearSystemLog,
+  ClearEventLog,
+  SystemReset,
+  Manager,
+  ManagerReset,
+  Bios
 }
 
 impl RedfishEndpoint {
-  fn url(&self) -> String {
-    match self {
-      RedfishEndpoint::Thermal => "Chassis/1/Thermal".to_string(),
-      RedfishEndpoint::Power => "Chassis/1/Power".to_string(),
-      RedfishEndpoint::System => "Systems/1".to_string(),
+  fn url(
+    &self,
+    ids: &ResourceIds
+  ) -> Result<String, IloError> {
+    let event_log = || ids.event_log.as_deref().ok_or(IloError::Missing("an event log of its own"));
+
+    Ok(match self {
+      RedfishEndpoint::Thermal => format!("Chassis/{}/Thermal", ids.chassis),
+      RedfishEndpoint::Power => format!("Chassis/{}/Power", ids.chassis),
+      RedfishEndpoint::System => format!("Systems/{}", ids.system),
       RedfishEndpoint::EventService => "EventService".to_string(),
-      RedfishEndpoint::LogServices => "Systems/1/LogServices/IML/Entries".to_string()
+      RedfishEndpoint::LogServices => format!("Systems/{}/LogServices/{}/Entries", ids.system, ids.log_service),
+      RedfishEndpoint::EventLog => format!("Managers/{}/LogServices/{}/Entries", ids.manager, event_log()?),
+      RedfishEndpoint::ClearSystemLog => format!("Systems/{}/LogServices/{}/Actions/LogService.ClearLog", ids.system, ids.log_service),
+      RedfishEndpoint::ClearEventLog => format!("Managers/{}/LogServices/{}/Actions/LogService.ClearLog", ids.manager, event_log()?),
+      RedfishEndpoint::SystemReset => format!("Systems/{}/Actions/ComputerSystem.Reset", ids.system),
+      RedfishEndpoint::Manager => format!("Managers/{}", ids.manager),
+      RedfishEndpoint::ManagerReset => format!("Managers/{}/Actions/Manager.Reset", ids.manager),
+      RedfishEndpoint::Bios => format!("Systems/{}/Bios", ids.system)
+    })
+  }
+}
+
+/// Everything a BMC call can fail with, the details are for the logs and never reach users as is
+enum IloError {
+  /// Nothing answered, covers DNS, refused connections and dropped sockets
+  Unreachable(String),
+  /// BMC was reached but didn't answer in time
+  Timeout(String),
+  /// BMC turned down the configured credentials
+  AuthFailed(String),
+  /// BMC presented a certificate other than the pinned or configured one
+  PinMismatch {
+    expected:  String,
+    presented: String
+  },
+  /// TLS handshake failed against the configured CA bundle
+  TlsMismatch(String),
+  Tls(String),
+  /// BMC answered with something that doesn't fit what was asked for
+  UnexpectedSchema(String),
+  /// BMC answered with an error status
+  Status(StatusCode, String),
+  /// IPMI command refused by the BMC
+  Ipmi(String),
+  /// Redfish-only feature asked of a host that's only spoken to over IPMI
+  Unsupported,
+  /// BMC doesn't offer the resource at all
+  Missing(&'static str)
+}
+
+impl From<ReqError> for IloError {
+  fn from(e: ReqError) -> Self {
+    // reqwest's own message stops at the URL, the cause is further down its sources
+    let mut details = e.to_string();
+    let mut handshake = false;
+    let mut source = e.source();
+    while let Some(err) = source {
+      handshake |= err.is::<native_tls::Error>();
+      details.push_str(&format!(": {err}"));
+      source = err.source();
+    }
+
+    if e.is_timeout() {
+      IloError::Timeout(details)
+    } else if e.is_builder() {
+      IloError::Tls(details)
+    } else if handshake {
+      IloError::TlsMismatch(details)
+    } else if e.is_decode() {
+      IloError::UnexpectedSchema(details)
+    } else if let Some(status) = e.status() {
+      match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => IloError::AuthFailed(details),
+        status => IloError::Status(status, details)
+      }
+    } else {
+      IloError::Unreachable(details)
+    }
+  }
+}
+
+impl IloError {
+  /// What users get to see, free of addresses, URLs and anything else the BMC said
+  fn user_message(&self) -> String {
+    match self {
+      IloError::Unreachable(_) => "Couldn't reach the BMC, it might be offline".to_string(),
+      IloError::Timeout(_) => "The BMC took too long to answer".to_string(),
+      IloError::AuthFailed(_) => "The BMC turned down the configured credentials".to_string(),
+      IloError::PinMismatch { .. } => "The BMC's certificate doesn't match its pin, the owner has been notified".to_string(),
+      IloError::TlsMismatch(_) => "The BMC's certificate isn't trusted".to_string(),
+      IloError::Tls(_) => "Couldn't set up a secure connection to the BMC".to_string(),
+      IloError::UnexpectedSchema(_) => "The BMC answered with something that couldn't be understood".to_string(),
+      IloError::Status(status, _) => format!("The BMC answered with HTTP {}", status.as_u16()),
+      IloError::Ipmi(_) => "The BMC refused an IPMI command".to_string(),
+      IloError::Unsupported | IloError::Missing(_) => self.to_string()
+    }
+  }
+}
+
+impl fmt::Display for IloError {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    match self {
+      IloError::Unreachable(e) => write!(f, "BMC unreachable: {e}"),
+      IloError::Timeout(e) => write!(f, "BMC timed out: {e}"),
+      IloError::AuthFailed(e) => write!(f, "Authentication failed: {e}"),
+      IloError::PinMismatch { expected, presented } => write!(
+        f,
+        "BMC's certificate doesn't match its pin (expected {expected}, got {presented}), if it was replaced on purpose then update the fingerprint \
+         or remove it from {}",
+        tls::pin_store_path()
+      ),
+      IloError::TlsMismatch(e) => write!(f, "TLS handshake failed: {e}"),
+      IloError::Tls(e) => write!(f, "TLS setup failed: {e}"),
+      IloError::UnexpectedSchema(e) => write!(f, "Unexpected response: {e}"),
+      IloError::Status(_, e) => write!(f, "{e}"),
+      IloError::Ipmi(e) => write!(f, "IPMI request failed: {e}"),
+      IloError::Unsupported => write!(f, "This needs Redfish, the BMC is only reachable over IPMI"),
+      IloError::Missing(what) => write!(f, "Not supported, the BMC doesn't have {what}")
     }
   }
 }
 
-async fn ilo_data<T: DeserializeOwned>(endpoint: RedfishEndpoint) -> Result<T, ReqError> {
-  let client = &*REQWEST_CLIENT;
-  let token = token_path().await;
-  let redfish_url = format!("https://{}/redfish/v1/{}", token.ilo_ip, endpoint.url());
+/// Send a request with the host's session token, falls back to basic auth if the BMC has no sessions
+async fn redfish_send<B: Serialize>(
+  host: &BmcHost,
+  method: Method,
+  path: &str,
+  body: Option<&B>
+) -> Result<Response, IloError> {
+  if host.protocol == BmcProtocol::Ipmi {
+    return Err(IloError::Unsupported);
+  }
+
+  let redfish_url = format!("https://{}/redfish/v1/{path}", host.address);
+  let client = client_for(host).await?;
 
-  let res = client.get(redfish_url).basic_auth(token.ilo_user, Some(token.ilo_pw)).send().await?;
+  let request = |token: Option<&str>| {
+    let req = client.request(method.clone(), &redfish_url);
+    let req = match token {
+      Some(t) => req.header(AUTH_TOKEN_HEADER, t),
+      None => req.basic_auth(&host.username, Some(&host.password))
+    };
+
+    match body {
+      Some(b) => req.json(b),
+      None => req
+    }
+  };
 
-  res.json::<T>().await
+  let token = session_token(host, None).await?;
+  let res = match request(token.as_deref()).send().await {
+    Ok(res) => res,
+    // A handshake failure could mean the certificate changed under us
+    Err(e) if e.is_connect() => return Err(recheck_pin(host).await.unwrap_or(e.into())),
+    Err(e) => return Err(e.into())
+  };
+
+  // Session got dropped (timed out, BMC reset, etc), log in again once
+  if res.status() == StatusCode::UNAUTHORIZED
+    && let Some(stale) = token
+  {
+    let token = session_token(host, Some(&stale)).await?;
+    return Ok(request(token.as_deref()).send().await?);
+  }
+
+  Ok(res)
+}
+
+/// GET a resource by its path under the service root
+async fn redfish_get<T: DeserializeOwned>(
+  host: &BmcHost,
+  path: &str
+) -> Result<T, IloError> {
+  Ok(
+    redfish_send::<()>(host, Method::GET, path, None)
+      .await?
+      .error_for_status()?
+      .json::<T>()
+      .await?
+  )
+}
+
+async fn ilo_data<T: DeserializeOwned>(
+  host: &BmcHost,
+  endpoint: RedfishEndpoint
+) -> Result<T, IloError> {
+  let ids = resource_ids(host).await?;
+  redfish_get(host, &endpoint.url(&ids)?).await
+}
+
+/// POST a Redfish action by its target path, iLO answers with an extended info body that we don't care about
+async fn redfish_post<B: Serialize>(
+  host: &BmcHost,
+  path: &str,
+  body: &B
+) -> Result<(), IloError> {
+  redfish_send(host, Method::POST, path, Some(body)).await?.error_for_status()?;
+
+  Ok(())
+}
+
+async fn ilo_action<B: Serialize>(
+  host: &BmcHost,
+  endpoint: RedfishEndpoint,
+  body: &B
+) -> Result<(), IloError> {
+  let ids = resource_ids(host).await?;
+  redfish_post(host, &endpoint.url(&ids)?, body).await
+}
+
+/// PATCH the writable properties of a resource by its path under the service root
+async fn redfish_patch<B: Serialize>(
+  host: &BmcHost,
+  path: &str,
+  body: &B
+) -> Result<(), IloError> {
+  redfish_send(host, Method::PATCH, path, Some(body)).await?.error_for_status()?;
+
+  Ok(())
+}
+
+async fn ilo_patch<B: Serialize>(
+  host: &BmcHost,
+  endpoint: RedfishEndpoint,
+  body: &B
+) -> Result<(), IloError> {
+  let ids = resource_ids(host).await?;
+  redfish_patch(host, &endpoint.url(&ids)?, body).await
+}
+
+/// Everything `/ilo system` shows, only the power state is known over IPMI
+async fn system_data(host: &BmcHost) -> Result<(System, Event), IloError> {
+  if host.protocol == BmcProtocol::Ipmi {
+    let system = System {
+      power_state: Some(backend(host).power_state().await?),
+      ..Default::default()
+    };
+    return Ok((system, Event::default()));
+  }
+
+  let (system, event) = tokio::join!(
+    ilo_data::<System>(host, RedfishEndpoint::System),
+    ilo_data::<Event>(host, RedfishEndpoint::EventService)
+  );
+  Ok((system?, event?))
+}
+
+/// Resolve the host argument, replies to the user if it doesn't exist
+async fn resolve_host(
+  ctx: &super::PoiseCtx<'_>,
+  host: Option<String>
+) -> KonResult<Option<BmcHost>> {
+  match find_host(host.as_deref()).await {
+    Some(h) => Ok(Some(h)),
+    None => {
+      ctx.reply("Unknown host or it isn't configured properly!").await?;
+      Ok(None)
+    }
+  }
+}
+
+/// Short random ID that ties what the user was told to the logged details
+fn correlation_id() -> String {
+  let mut id = [0u8; 4];
+  getrandom::fill(&mut id).expect("the OS should be able to provide random bytes");
+  hex::encode_upper(id)
+}
+
+/// Log the full error to the console under a new correlation ID and hand back the ID
+fn log_error(
+  host: &BmcHost,
+  author: &User,
+  err: &IloError
+) -> String {
+  let id = correlation_id();
+  eprintln!("iLO[Error] {id} on {} for {} ({}): {err}", host.name, author.name, author.id);
+  id
+}
+
+/// Outcome line of an owner-only action that failed, the details only end up in the console
+fn failed(
+  ctx: super::PoiseCtx<'_>,
+  host: &BmcHost,
+  err: &IloError
+) -> String {
+  let id = log_error(host, ctx.author(), err);
+  format!("Failed: {} (ref `{id}`)", err.user_message())
 }
 
 async fn ilo_err(
   ctx: &super::PoiseCtx<'_>,
-  err: ReqError
+  host: &BmcHost,
+  err: IloError
 ) {
-  let msg = format!("Command failed due to an error;-```{err}```");
+  let id = log_error(host, ctx.author(), &err);
+
+  // Anyone can run these, so only what the owners have to act on goes to the logs channel
+  let title = match err {
+    IloError::PinMismatch { .. } => Some("Certificate pin mismatch"),
+    IloError::TlsMismatch(_) => Some("Untrusted certificate"),
+    IloError::AuthFailed(_) => Some("Authentication failed"),
+    _ => None
+  };
+  if let Some(title) = title {
+    let details = format!("{err}\n-# Triggered by <@{}> • `{id}`", ctx.author().id);
+    post_kon_logs(ctx.http(), embed_builder(host, title, Some(details), None)).await;
+  }
+
+  let msg = format!("Command failed: {}\n-# Reference: `{id}`", err.user_message());
 
   if let Err(e) = ctx.reply(&msg).await {
     eprintln!("Couldn't reply to command ({e}), dumped here instead: {msg}")
@@ -201,14 +753,15 @@ async fn ilo_err(
 }
 
 fn embed_builder(
+  host: &BmcHost,
   title: &str,
   description: Option<String>,
   fields: Option<Vec<(String, String, bool)>>
-) -> CreateEmbed {
+) -> CreateEmbed<'static> {
   let mut embed = CreateEmbed::new()
     .color(BINARY_PROPERTIES.embed_color)
     .timestamp(Timestamp::now())
-    .title(format!("{ILO_HOSTNAME} - {title}"));
+    .title(format!("{} - {title}", host.name));
 
   if let Some(d) = description {
     embed = embed.description(d);
@@ -223,6 +776,64 @@ fn embed_builder(
   embed
 }
 
+/// Split the lines into as many fields as it takes to stay under [`FIELD_LIMIT`]
+fn pack_fields(
+  name: &str,
+  lines: &[String]
+) -> Vec<(String, String, bool)> {
+  let mut values = Vec::new();
+  let mut current = String::new();
+
+  for line in lines {
+    if !current.is_empty() && current.len() + line.len() + 1 > FIELD_LIMIT {
+      values.push(std::mem::take(&mut current));
+    }
+    current.push_str(line);
+    current.push('\n');
+  }
+  if !current.is_empty() {
+    values.push(current);
+  }
+
+  let total = values.len();
+  values
+    .into_iter()
+    .enumerate()
+    .map(|(i, value)| {
+      let name = if total > 1 {
+        format!("{name} ({}/{total})", i + 1)
+      } else {
+        name.to_string()
+      };
+      (name, value, false)
+    })
+    .collect()
+}
+
+/// Group the fields into pages that each fit in one embed
+fn pack_pages(fields: Vec<(String, String, bool)>) -> Vec<Vec<(String, String, bool)>> {
+  let mut pages = Vec::new();
+  let mut current: Vec<(String, String, bool)> = Vec::new();
+  let mut size = 0;
+
+  for field in fields {
+    let field_size = field.0.len() + field.1.len();
+    if !current.is_empty() && (current.len() >= MAX_FIELDS || size + field_size > EMBED_LIMIT - EMBED_MARGIN) {
+      pages.push(std::mem::take(&mut current));
+      size = 0;
+    }
+    size += field_size;
+    current.push(field);
+  }
+  if !current.is_empty() {
+    pages.push(current);
+  }
+
+  pages
+}
+
+fn fmt_watts(watts: Option<i32>) -> String { watts.map(|w| format!("{w}w")).unwrap_or_else(|| "N/A".to_string()) }
+
 fn fmt_dt(input: &str) -> Option<String> {
   let parts: Vec<&str> = input.split('T').collect();
   if parts.len() != 2 {
@@ -240,53 +851,199 @@ fn fmt_dt(input: &str) -> Option<String> {
   Some(format!("{date} {time}"))
 }
 
+/// Lines for the temperature and fan fields, `all` lists every sensor along with its thresholds
+fn sensor_lines(
+  data: &Chassis,
+  sensors: &[(&str, &str)],
+  all: bool
+) -> (Vec<String>, Vec<String>) {
+  let mut templines = Vec::new();
+  let mut fanlines = Vec::new();
+
+  for temp in &data.temperatures {
+    let reading = temp.reading_celsius.unwrap_or(0);
+    if reading == 0 {
+      continue;
+    }
+
+    if all {
+      let threshold = |t: Option<i32>| t.filter(|t| *t > 0).map(|t| format!("{t}°C")).unwrap_or_else(|| "-".to_string());
+      templines.push(format!(
+        "**{}:** `{reading}°C` (crit `{}`, fatal `{}`) - {}",
+        temp.name,
+        threshold(temp.upper_threshold_critical),
+        threshold(temp.upper_threshold_fatal),
+        temp.status.health.as_deref().unwrap_or("Unknown")
+      ));
+      continue;
+    }
+
+    let name = if sensors.is_empty() {
+      temp.name.as_str()
+    } else {
+      match sensors.iter().find(|(raw, _)| *raw == temp.name) {
+        Some((_, friendly)) => *friendly,
+        None => continue
+      }
+    };
+
+    templines.push(format!("**{name}:** `{reading}°C`"));
+  }
+  for fan in &data.fans {
+    let reading = fan.value().unwrap_or(0);
+    if reading == 0 {
+      continue;
+    }
+
+    fanlines.push(format!("**{}:** `{reading}{}`", fan.label(), fan.unit()));
+  }
+
+  (templines, fanlines)
+}
+
+fn power_summary(data: &Power) -> String {
+  let metrics = data.metrics();
+  format!(
+    "**Power Capacity:** `{}`\n**Power Consumed:** `{}`\n**Average Power:** `{}`\n**Max Consumed:** `{}`\n**Min Consumed:** `{}`",
+    fmt_watts(data.capacity()),
+    fmt_watts(data.consumed()),
+    fmt_watts(metrics.and_then(|m| m.average_consumed_watts)),
+    fmt_watts(metrics.and_then(|m| m.max_consumed_watts)),
+    fmt_watts(metrics.and_then(|m| m.min_consumed_watts))
+  )
+}
+
+/// Description and the CPU/RAM fields for `/ilo system`
+fn system_summary(
+  system: &System,
+  event: &Event
+) -> (String, Vec<(String, String, bool)>) {
+  let mut data = String::new();
+
+  data.push_str(&format!("**Health:** `{}`\n", event.status.health.as_deref().unwrap_or("Unknown")));
+
+  match system.vendor() {
+    Vendor::Hpe(hpe) => {
+      let raw_state = hpe.post_state.as_deref().unwrap_or_default();
+      let post_state = POST_STATES.get(raw_state).map(|s| *s).unwrap_or("Unknown POST state");
+
+      if raw_state != "FinishedPost" {
+        println!("iLO:PostState = {raw_state}");
+      }
+
+      data.push_str(&format!("**POST:** `{post_state}`\n"));
+    },
+    Vendor::Dell(dell) => {
+      if let Some(generation) = &dell.system_generation {
+        data.push_str(&format!("**Generation:** `{generation}`\n"));
+      }
+      if let Some(rollup) = &dell.current_rollup_status {
+        data.push_str(&format!("**Rollup Status:** `{rollup}`\n"));
+      }
+    },
+    Vendor::Supermicro(smc) => {
+      if let Some(board) = &smc.board_id {
+        data.push_str(&format!("**Board:** `{board}`\n"));
+      }
+    },
+    Vendor::Generic => ()
+  }
+
+  data.push_str(&format!("**Power:** `{}`\n", system.power_state.as_deref().unwrap_or("Unknown")));
+  data.push_str(&format!(
+    "**Model:** `{}{}`",
+    system.manufacturer.as_deref().map(|m| format!("{m} ")).unwrap_or_default(),
+    system.model.as_deref().unwrap_or("Unknown")
+  ));
+
+  let fields = vec![
+    (
+      format!("CPU ({}x)", system.processor_summary.count.unwrap_or(0)),
+      system.processor_summary.model.as_deref().unwrap_or("Unknown").trim().to_string(),
+      true
+    ),
+    (
+      "RAM".to_string(),
+      system
+        .total_memory_gb()
+        .map(|gb| format!("{gb} GB"))
+        .unwrap_or_else(|| "Unknown".to_string()),
+      true
+    ),
+  ];
+
+  (data, fields)
+}
+
 /// Retrieve data from the HP iLO interface
 #[poise::command(
   slash_command,
   install_context = "Guild|User",
   interaction_context = "Guild|BotDm|PrivateChannel",
-  subcommands("temperature", "power", "system", "logs")
+  subcommands(
+    "temperature",
+    "power",
+    "powercap",
+    "system",
+    "storage",
+    "inventory",
+    "bios",
+    "manager",
+    "logs",
+    "events",
+    "history",
+    "power_action",
+    "manager_reset",
+    "bios_set",
+    "boot",
+    "media",
+    "clear_log"
+  )
 )]
 pub async fn ilo(_: super::PoiseCtx<'_>) -> KonResult<()> { Ok(()) }
 
 /// Retrieve the server's temperature data
 #[poise::command(slash_command)]
-async fn temperature(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
+async fn temperature(
+  ctx: super::PoiseCtx<'_>,
+  #[description = "List every sensor along with its thresholds"] all: Option<bool>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
   ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
 
-  match ilo_data::<Chassis>(RedfishEndpoint::Thermal).await {
+  match backend(&host).sensors().await {
     Ok(data) => {
-      let mut tempdata = String::new();
-      let mut fandata = String::new();
-
-      let allowed_sensors = ["01-Inlet Ambient", "04-P1 DIMM 1-6", "14-Chipset Zone"];
+      let all = all.unwrap_or(false);
+      let (templines, fanlines) = sensor_lines(&data, host.sensors, all);
 
-      for temp in &data.temperatures {
-        if temp.reading_celsius == 0 || !allowed_sensors.contains(&temp.name.as_str()) {
-          continue;
-        }
+      if all {
+        let mut fields = pack_fields("Temperatures", &templines);
+        fields.extend(pack_fields("Fans", &fanlines));
 
-        let name = SENSOR_NAMES.get(temp.name.as_str()).map(|s| *s).unwrap_or("Unknown sensor");
+        let pages = pack_pages(fields)
+          .into_iter()
+          .map(|page| embed_builder(&host, "Sensors", None, Some(page)))
+          .collect();
 
-        tempdata.push_str(&format!("**{name}:** `{}°C`\n", temp.reading_celsius));
+        paginate(ctx, pages, &format!("{} sensors", templines.len() + fanlines.len())).await?;
+      } else {
+        ctx
+          .send(CreateReply::default().embed(embed_builder(
+            &host,
+            "Temperatures",
+            None,
+            Some(vec![
+              ("Temperatures".to_string(), templines.join("\n"), false),
+              ("Fans".to_string(), fanlines.join("\n"), false),
+            ])
+          )))
+          .await?;
       }
-      for fan in &data.fans {
-        if fan.current_reading == 0 {
-          continue;
-        }
-
-        fandata.push_str(&format!("**{}:** `{}%`\n", fan.fan_name, fan.current_reading));
-      }
-
-      ctx
-        .send(CreateReply::default().embed(embed_builder(
-          "Temperatures",
-          None,
-          Some(vec![("Temperatures".to_string(), tempdata, false), ("Fans".to_string(), fandata, false)])
-        )))
-        .await?;
     },
-    Err(e) => ilo_err(&ctx, e).await
+    Err(e) => ilo_err(&ctx, &host, e).await
   }
 
   Ok(())
@@ -294,25 +1051,22 @@ async fn temperature(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
 
 /// Retrieve the server's power data
 #[poise::command(slash_command)]
-async fn power(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
+async fn power(
+  ctx: super::PoiseCtx<'_>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
   ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
 
-  match ilo_data::<Power>(RedfishEndpoint::Power).await {
+  match ilo_data::<Power>(&host, RedfishEndpoint::Power).await {
     Ok(data) => {
-      let powerdata = format!(
-        "**Power Capacity:** `{}w`\n**Power Consumed:** `{}w`\n**Average Power:** `{}w`\n**Max Consumed:** `{}w`\n**Min Consumed:** `{}w`",
-        data.power_capacity_watts,
-        data.power_consumed_watts,
-        data.power_metrics.average_consumed_watts,
-        data.power_metrics.max_consumed_watts,
-        data.power_metrics.min_consumed_watts
-      );
-
       ctx
-        .send(CreateReply::default().embed(embed_builder("Power", Some(powerdata), None)))
+        .send(CreateReply::default().embed(embed_builder(&host, "Power", Some(power_summary(&data)), None)))
         .await?;
     },
-    Err(e) => ilo_err(&ctx, e).await
+    Err(e) => ilo_err(&ctx, &host, e).await
   }
 
   Ok(())
@@ -320,75 +1074,24 @@ async fn power(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
 
 /// Retrieve the server's system data
 #[poise::command(slash_command)]
-async fn system(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
-  ctx.defer().await?;
-
-  let (ilo_sys, ilo_event) = tokio::join!(
-    ilo_data::<System>(RedfishEndpoint::System),
-    ilo_data::<Event>(RedfishEndpoint::EventService)
-  );
-
-  match (ilo_sys, ilo_event) {
-    (Ok(ilo_sys), Ok(ilo_event)) => {
-      let mut data = String::new();
-
-      let post_state = POST_STATES
-        .get(ilo_sys.oem.hp.post_state.as_str())
-        .map(|s| *s)
-        .unwrap_or("Unknown POST state");
-
-      if ilo_sys.oem.hp.post_state != "FinishedPost" {
-        println!("iLO:PostState = {}", ilo_sys.oem.hp.post_state);
-      }
-
-      data.push_str(&format!(
-        "**Health:** `{}`\n",
-        ilo_event.status.health.as_ref().unwrap_or(&"Unknown".to_string())
-      ));
-      data.push_str(&format!("**POST:** `{post_state}`\n"));
-      data.push_str(&format!("**Power:** `{}`\n", &ilo_sys.power_state));
-      data.push_str(&format!("**Model:** `{}`", &ilo_sys.model));
-
-      ctx
-        .send(CreateReply::default().embed(embed_builder(
-          "System",
-          Some(data),
-          Some(vec![
-            (
-              format!("CPU ({}x)", ilo_sys.processor_summary.count),
-              ilo_sys.processor_summary.model.trim().to_string(),
-              true
-            ),
-            ("RAM".to_string(), format!("{} GB", ilo_sys.memory.total_system_memory_gb), true),
-          ])
-        )))
-        .await?;
-    },
-    (Err(e), _) | (_, Err(e)) => ilo_err(&ctx, e).await
-  }
-
-  Ok(())
-}
-
-/// Retrieve the server's IML data
-#[poise::command(slash_command)]
-async fn logs(ctx: super::PoiseCtx<'_>) -> KonResult<()> {
+async fn system(
+  ctx: super::PoiseCtx<'_>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
   ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
 
-  match ilo_data::<Iml>(RedfishEndpoint::LogServices).await {
-    Ok(data) => {
-      let mut log_entries = String::new();
-
-      for entry in data.items.iter().rev().take(5) {
-        let dt = fmt_dt(&entry.created).unwrap_or_else(|| "Unknown".to_string());
-        log_entries.push_str(&format!("**[{}:{dt}]:** {}\n", entry.severity, entry.message));
-      }
+  match system_data(&host).await {
+    Ok((ilo_sys, ilo_event)) => {
+      let (data, fields) = system_summary(&ilo_sys, &ilo_event);
 
       ctx
-        .send(CreateReply::default().embed(embed_builder("IML", Some(log_entries), None)))
+        .send(CreateReply::default().embed(embed_builder(&host, "System", Some(data), Some(fields))))
         .await?;
     },
-    Err(e) => ilo_err(&ctx, e).await
+    Err(e) => ilo_err(&ctx, &host, e).await
   }
 
   Ok(())
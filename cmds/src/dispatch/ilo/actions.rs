@@ -0,0 +1,169 @@
+use {
+  super::{
+    backend::{
+      Bmc,
+      backend
+    },
+    embed_builder,
+    failed,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    post_kon_logs,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx,
+    is_developer
+  },
+  poise::{
+    ChoiceParameter,
+    CreateReply,
+    serenity_prelude::{
+      ButtonStyle,
+      ComponentInteractionCollector,
+      CreateActionRow,
+      CreateButton,
+      CreateInteractionResponse
+    }
+  },
+  tokio::time::Duration
+};
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum ResetType {
+  #[name = "Power on"]
+  On,
+  #[name = "Graceful shutdown"]
+  GracefulShutdown,
+  #[name = "Force off"]
+  ForceOff,
+  #[name = "Force restart"]
+  ForceRestart,
+  #[name = "Press power button"]
+  PushPowerButton
+}
+
+impl ResetType {
+  pub fn as_redfish(&self) -> &'static str {
+    match self {
+      ResetType::On => "On",
+      ResetType::GracefulShutdown => "GracefulShutdown",
+      ResetType::ForceOff => "ForceOff",
+      ResetType::ForceRestart => "ForceRestart",
+      ResetType::PushPowerButton => "PushPowerButton"
+    }
+  }
+}
+
+/// Replies to non-developers and returns whether the invoker may continue
+pub async fn require_developer(ctx: PoiseCtx<'_>) -> KonResult<bool> {
+  if is_developer(ctx) {
+    return Ok(true);
+  }
+
+  ctx
+    .send(CreateReply::new().content("Only developers can do that!").ephemeral(true))
+    .await?;
+  Ok(false)
+}
+
+/// Ask the invoker to confirm through buttons, returns whether they did
+pub async fn confirm(
+  ctx: PoiseCtx<'_>,
+  prompt: String
+) -> KonResult<bool> {
+  let confirm_id = format!("{}-confirm", ctx.id());
+  let cancel_id = format!("{}-cancel", ctx.id());
+
+  let buttons = CreateActionRow::Buttons(
+    vec![
+      CreateButton::new(confirm_id.clone()).style(ButtonStyle::Danger).label("Confirm"),
+      CreateButton::new(cancel_id.clone()).style(ButtonStyle::Secondary).label("Cancel"),
+    ]
+    .into()
+  );
+
+  let reply = ctx
+    .send(CreateReply::new().content(prompt).components(vec![buttons]).ephemeral(true))
+    .await?;
+
+  let ids = [confirm_id.clone(), cancel_id];
+  let press = ComponentInteractionCollector::new(ctx.serenity_context().shard.clone())
+    .author_id(ctx.author().id)
+    .filter(move |i| ids.iter().any(|id| i.data.custom_id.as_str() == id))
+    .timeout(CONFIRM_TIMEOUT)
+    .await;
+
+  let (confirmed, outcome) = match press {
+    Some(press) => {
+      press.create_response(ctx.http(), CreateInteractionResponse::Acknowledge).await?;
+      if press.data.custom_id.as_str() == confirm_id {
+        (true, "Confirmed, working on it...")
+      } else {
+        (false, "Cancelled.")
+      }
+    },
+    None => (false, "Timed out, nothing was done.")
+  };
+
+  reply.edit(ctx, CreateReply::new().content(outcome).components(vec![])).await?;
+
+  Ok(confirmed)
+}
+
+/// Record the action in the console and the logs channel
+pub async fn log_action(
+  ctx: PoiseCtx<'_>,
+  host: &BmcHost,
+  action: &str,
+  outcome: &str
+) {
+  let author = ctx.author();
+  println!("iLO[Action] {} ran \"{action}\" on {}: {outcome}", author.name, host.name);
+
+  let embed = embed_builder(
+    host,
+    "Action",
+    Some(format!("**User:** <@{}>\n**Action:** `{action}`\n**Outcome:** {outcome}", author.id)),
+    None
+  );
+
+  post_kon_logs(ctx.http(), embed).await;
+}
+
+/// Change the server's power state
+#[poise::command(slash_command, rename = "power-action")]
+pub async fn power_action(
+  ctx: PoiseCtx<'_>,
+  #[description = "What to do with the server"] action: ResetType,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  if !confirm(ctx, format!("Are you sure you want to **{}** `{}`?", action.name(), host.name)).await? {
+    return Ok(());
+  }
+
+  let outcome = match backend(&host).power_control(action).await {
+    Ok(()) => "Sent".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  log_action(ctx, &host, action.as_redfish(), &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**{}** on `{}`: {outcome}", action.name(), host.name)))
+    .await?;
+
+  Ok(())
+}
@@ -0,0 +1,147 @@
+use {
+  super::{
+    Chassis,
+    IloError,
+    Iml,
+    RedfishEndpoint,
+    System,
+    actions::ResetType,
+    hosts::BmcHost,
+    ilo_action,
+    ilo_data,
+    ipmi
+  },
+  kon_libs::BmcProtocol,
+  serde::Serialize
+};
+
+/// What `/ilo` needs from a BMC no matter how it's spoken to,
+/// everything else stays Redfish-only
+pub trait Bmc {
+  /// Name of the log [`Bmc::event_log`] reads
+  fn log_name(&self) -> &'static str;
+
+  /// Temperatures and fans, in the shape of Redfish's Thermal resource
+  async fn sensors(&self) -> Result<Chassis, IloError>;
+
+  /// The server's hardware event log, oldest entry first
+  async fn event_log(&self) -> Result<Iml, IloError>;
+
+  /// `On` or `Off` like Redfish's `PowerState`, anything else the BMC reports is passed through
+  async fn power_state(&self) -> Result<String, IloError>;
+
+  async fn power_control(
+    &self,
+    action: ResetType
+  ) -> Result<(), IloError>;
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResetRequest {
+  reset_type: &'static str
+}
+
+pub struct Redfish<'a>(&'a BmcHost);
+
+impl Bmc for Redfish<'_> {
+  fn log_name(&self) -> &'static str { "IML" }
+
+  async fn sensors(&self) -> Result<Chassis, IloError> { ilo_data(self.0, RedfishEndpoint::Thermal).await }
+
+  async fn event_log(&self) -> Result<Iml, IloError> { ilo_data(self.0, RedfishEndpoint::LogServices).await }
+
+  async fn power_state(&self) -> Result<String, IloError> {
+    let system = ilo_data::<System>(self.0, RedfishEndpoint::System).await?;
+    Ok(system.power_state.unwrap_or_else(|| "Unknown".to_string()))
+  }
+
+  async fn power_control(
+    &self,
+    action: ResetType
+  ) -> Result<(), IloError> {
+    let reset = ResetRequest {
+      reset_type: action.as_redfish()
+    };
+
+    ilo_action(self.0, RedfishEndpoint::SystemReset, &reset).await
+  }
+}
+
+/// IPMI 2.0 over LAN, a new session is set up for every call
+pub struct Ipmi<'a>(&'a BmcHost);
+
+impl Bmc for Ipmi<'_> {
+  fn log_name(&self) -> &'static str { "SEL" }
+
+  async fn sensors(&self) -> Result<Chassis, IloError> { ipmi::sensors(self.0).await }
+
+  async fn event_log(&self) -> Result<Iml, IloError> {
+    Ok(Iml {
+      items:   None,
+      members: ipmi::sel(self.0).await?
+    })
+  }
+
+  async fn power_state(&self) -> Result<String, IloError> { ipmi::power_state(self.0).await }
+
+  async fn power_control(
+    &self,
+    action: ResetType
+  ) -> Result<(), IloError> {
+    ipmi::power_control(self.0, action).await
+  }
+}
+
+/// The host's backend, picked by its configured protocol
+pub enum Backend<'a> {
+  Redfish(Redfish<'a>),
+  Ipmi(Ipmi<'a>)
+}
+
+pub fn backend(host: &BmcHost) -> Backend<'_> {
+  match host.protocol {
+    BmcProtocol::Redfish => Backend::Redfish(Redfish(host)),
+    BmcProtocol::Ipmi => Backend::Ipmi(Ipmi(host))
+  }
+}
+
+impl Bmc for Backend<'_> {
+  fn log_name(&self) -> &'static str {
+    match self {
+      Backend::Redfish(b) => b.log_name(),
+      Backend::Ipmi(b) => b.log_name()
+    }
+  }
+
+  async fn sensors(&self) -> Result<Chassis, IloError> {
+    match self {
+      Backend::Redfish(b) => b.sensors().await,
+      Backend::Ipmi(b) => b.sensors().await
+    }
+  }
+
+  async fn event_log(&self) -> Result<Iml, IloError> {
+    match self {
+      Backend::Redfish(b) => b.event_log().await,
+      Backend::Ipmi(b) => b.event_log().await
+    }
+  }
+
+  async fn power_state(&self) -> Result<String, IloError> {
+    match self {
+      Backend::Redfish(b) => b.power_state().await,
+      Backend::Ipmi(b) => b.power_state().await
+    }
+  }
+
+  async fn power_control(
+    &self,
+    action: ResetType
+  ) -> Result<(), IloError> {
+    match self {
+      Backend::Redfish(b) => b.power_control(action).await,
+      Backend::Ipmi(b) => b.power_control(action).await
+    }
+  }
+}
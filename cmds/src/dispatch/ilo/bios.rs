@@ -0,0 +1,296 @@
+use {
+  super::{
+    IloError,
+    RedfishEndpoint,
+    actions::{
+      confirm,
+      log_action,
+      require_developer
+    },
+    discovery::{
+      Link,
+      resource_ids
+    },
+    embed_builder,
+    failed,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_data,
+    ilo_err,
+    pack_fields,
+    pack_pages,
+    paginate::paginate,
+    redfish_get,
+    redfish_patch,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::CreateReply,
+  reqwest::StatusCode,
+  serde::Deserialize,
+  serde_json::{
+    Value,
+    json
+  },
+  std::collections::BTreeMap
+};
+
+/// Properties that iLO 4 mixes in with its attributes since it doesn't wrap them
+const METADATA_KEYS: [&str; 8] = [
+  "AttributeRegistry",
+  "Description",
+  "Id",
+  "links",
+  "Modified",
+  "Name",
+  "SettingsResult",
+  "Type"
+];
+
+#[derive(Deserialize)]
+pub struct Bios {
+  #[serde(rename = "Attributes")]
+  attributes: Option<BTreeMap<String, Value>>,
+  #[serde(rename = "@Redfish.Settings")]
+  settings:   Option<SettingsLink>,
+  /// Everything else, which is where iLO 4 keeps the attributes
+  #[serde(flatten)]
+  rest:       BTreeMap<String, Value>
+}
+
+#[derive(Deserialize)]
+struct SettingsLink {
+  #[serde(rename = "SettingsObject")]
+  settings_object: Option<Link>
+}
+
+impl Bios {
+  pub fn attributes(&self) -> BTreeMap<&str, &Value> {
+    match &self.attributes {
+      Some(attributes) => attributes.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+      None => self
+        .rest
+        .iter()
+        .filter(|(k, v)| !k.starts_with('@') && !k.starts_with("Oem") && !METADATA_KEYS.contains(&k.as_str()) && !v.is_object())
+        .map(|(k, v)| (k.as_str(), v))
+        .collect()
+    }
+  }
+
+  /// Wrap a change the way this BMC expects it
+  pub fn settings_body(
+    &self,
+    name: &str,
+    value: Value
+  ) -> Value {
+    if self.attributes.is_some() {
+      json!({ "Attributes": { name: value } })
+    } else {
+      json!({ name: value })
+    }
+  }
+}
+
+fn fmt_value(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    other => other.to_string()
+  }
+}
+
+/// Attributes whose staged value differs from the current one
+pub fn pending_changes<'a>(
+  current: &'a Bios,
+  staged: &'a Bios
+) -> Vec<(&'a str, Option<&'a Value>, &'a Value)> {
+  let current = current.attributes();
+  staged
+    .attributes()
+    .into_iter()
+    .filter(|(name, value)| current.get(name) != Some(value))
+    .map(|(name, value)| (name, current.get(name).copied(), value))
+    .collect()
+}
+
+/// Pending changes and the attributes matching the search, pending ones are marked in both
+pub fn bios_lines(
+  current: &Bios,
+  staged: Option<&Bios>,
+  search: Option<&str>
+) -> (Vec<String>, Vec<String>) {
+  let pending = staged.map(|s| pending_changes(current, s)).unwrap_or_default();
+  let search = search.map(str::to_lowercase);
+  let matches = |name: &str| search.as_deref().is_none_or(|s| name.to_lowercase().contains(s));
+
+  let pending_lines = pending
+    .iter()
+    .filter(|(name, ..)| matches(name))
+    .map(|(name, old, new)| {
+      format!(
+        "🟡 **{name}:** `{}` → `{}`",
+        old.map(fmt_value).unwrap_or_else(|| "-".to_string()),
+        fmt_value(new)
+      )
+    })
+    .collect();
+
+  let attribute_lines = current
+    .attributes()
+    .into_iter()
+    .filter(|(name, _)| matches(name))
+    .map(|(name, value)| {
+      let marker = if pending.iter().any(|(p, ..)| *p == name) { "🟡 " } else { "" };
+      format!("{marker}**{name}:** `{}`", fmt_value(value))
+    })
+    .collect();
+
+  (pending_lines, attribute_lines)
+}
+
+/// Turn the input into the same JSON type as the attribute's current value
+pub fn coerce_value(
+  current: &Value,
+  input: &str
+) -> Option<Value> {
+  match current {
+    Value::Bool(_) => input.parse::<bool>().ok().map(Value::Bool),
+    Value::Number(_) => input.parse::<i64>().ok().map(Value::from),
+    Value::String(_) => Some(Value::String(input.to_string())),
+    _ => None
+  }
+}
+
+/// Fetch the current attributes, the staged ones and where to send changes
+pub async fn bios_data(host: &BmcHost) -> Result<(Bios, Option<Bios>, String), IloError> {
+  let current = ilo_data::<Bios>(host, RedfishEndpoint::Bios).await?;
+  let settings_path = match current.settings.as_ref().and_then(|s| s.settings_object.as_ref()).and_then(Link::path) {
+    Some(path) => path.to_string(),
+    None => format!("Systems/{}/Bios/Settings", resource_ids(host).await?.system)
+  };
+
+  // Only a missing settings object means nothing's staged, anything else would hide pending changes
+  let staged = match redfish_get::<Bios>(host, &settings_path).await {
+    Ok(staged) => Some(staged),
+    Err(IloError::Status(StatusCode::NOT_FOUND, _)) => None,
+    Err(e) => return Err(e)
+  };
+  Ok((current, staged, settings_path))
+}
+
+/// Browse the server's BIOS settings and any changes waiting on a reboot
+#[poise::command(slash_command)]
+pub async fn bios(
+  ctx: PoiseCtx<'_>,
+  #[description = "Only show attributes containing this"] search: Option<String>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  // The attributes include the BMC's addresses, gateways and boot URLs, same as the network details /ilo manager keeps private
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let (current, staged, _) = match bios_data(&host).await {
+    Ok(data) => data,
+    Err(e) => {
+      ilo_err(&ctx, &host, e).await;
+      return Ok(());
+    }
+  };
+
+  let (pending, attributes) = bios_lines(&current, staged.as_ref(), search.as_deref());
+  let description = match pending.len() {
+    0 => None,
+    n => Some(format!("🟡 **{n}** change(s) will apply on the next reboot"))
+  };
+
+  let mut fields = pack_fields("Pending changes", &pending);
+  fields.extend(pack_fields("Attributes", &attributes));
+
+  let pages = pack_pages(fields)
+    .into_iter()
+    .map(|page| embed_builder(&host, "BIOS", description.clone(), Some(page)))
+    .collect::<Vec<_>>();
+
+  if pages.is_empty() {
+    ctx
+      .send(CreateReply::new().embed(embed_builder(&host, "BIOS", Some("No attributes matched the search.".to_string()), None)))
+      .await?;
+    return Ok(());
+  }
+
+  paginate(ctx, pages, &format!("{} attributes", attributes.len())).await
+}
+
+/// Stage a BIOS attribute change, it applies on the next reboot
+#[poise::command(slash_command, rename = "bios-set")]
+pub async fn bios_set(
+  ctx: PoiseCtx<'_>,
+  #[description = "Attribute name, as shown by /ilo bios"] attribute: String,
+  #[description = "New value"] value: String,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+  // Looking the attribute up can take longer than Discord waits for the confirmation prompt
+  ctx.defer_ephemeral().await?;
+
+  let (current, _, settings_path) = match bios_data(&host).await {
+    Ok(data) => data,
+    Err(e) => {
+      ilo_err(&ctx, &host, e).await;
+      return Ok(());
+    }
+  };
+
+  let attributes = current.attributes();
+  let Some(old) = attributes.get(attribute.as_str()) else {
+    ctx.reply(format!("`{attribute}` isn't a BIOS attribute on `{}`!", host.name)).await?;
+    return Ok(());
+  };
+  let Some(new) = coerce_value(old, &value) else {
+    ctx.reply(format!("`{value}` isn't a valid value for `{attribute}`!")).await?;
+    return Ok(());
+  };
+
+  if !confirm(
+    ctx,
+    format!(
+      "Are you sure you want to stage **{attribute}** `{}` → `{}` on `{}`? It applies on the next reboot.",
+      fmt_value(old),
+      fmt_value(&new),
+      host.name
+    )
+  )
+  .await?
+  {
+    return Ok(());
+  }
+
+  let action = format!("Bios.{attribute}={}", fmt_value(&new));
+  let outcome = match redfish_patch(&host, &settings_path, &current.settings_body(&attribute, new)).await {
+    Ok(()) => "Staged".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  log_action(ctx, &host, &action, &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**{attribute}** on `{}`: {outcome}", host.name)))
+    .await?;
+
+  Ok(())
+}
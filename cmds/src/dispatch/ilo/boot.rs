@@ -0,0 +1,310 @@
+use {
+  super::{
+    IloError,
+    RedfishEndpoint,
+    actions::{
+      confirm,
+      log_action,
+      require_developer
+    },
+    discovery::{
+      expand,
+      redfish_path,
+      resource_ids
+    },
+    failed,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_patch,
+    redfish_patch,
+    redfish_post,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    ChoiceParameter,
+    CreateReply
+  },
+  serde::{
+    Deserialize,
+    Serialize
+  },
+  serde_json::{
+    Value,
+    json
+  }
+};
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum BootTarget {
+  #[name = "Network (PXE)"]
+  Pxe,
+  #[name = "CD/DVD (virtual media)"]
+  Cd,
+  #[name = "USB"]
+  Usb,
+  #[name = "BIOS setup"]
+  BiosSetup,
+  #[name = "Clear the override"]
+  None
+}
+
+impl BootTarget {
+  fn as_redfish(&self) -> &'static str {
+    match self {
+      BootTarget::Pxe => "Pxe",
+      BootTarget::Cd => "Cd",
+      BootTarget::Usb => "Usb",
+      BootTarget::BiosSetup => "BiosSetup",
+      BootTarget::None => "None"
+    }
+  }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BootOverrideRequest {
+  boot: BootOverride
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct BootOverride {
+  boot_source_override_enabled: &'static str,
+  boot_source_override_target:  &'static str
+}
+
+impl BootOverrideRequest {
+  pub fn new(target: BootTarget) -> Self {
+    let enabled = match target {
+      BootTarget::None => "Disabled",
+      _ => "Once"
+    };
+
+    Self {
+      boot: BootOverride {
+        boot_source_override_enabled: enabled,
+        boot_source_override_target:  target.as_redfish()
+      }
+    }
+  }
+}
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum MediaAction {
+  Insert,
+  Eject
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct VirtualMedia {
+  #[serde(rename = "@odata.id", default)]
+  odata_id:    String,
+  actions:     Option<MediaActions>,
+  image:       Option<String>,
+  #[serde(default)]
+  media_types: Vec<String>
+}
+
+#[derive(Deserialize)]
+struct MediaActions {
+  #[serde(rename = "#VirtualMedia.InsertMedia")]
+  insert: Option<ActionTarget>,
+  #[serde(rename = "#VirtualMedia.EjectMedia")]
+  eject:  Option<ActionTarget>
+}
+
+#[derive(Deserialize)]
+struct ActionTarget {
+  target: String
+}
+
+impl VirtualMedia {
+  fn path(&self) -> &str { redfish_path(&self.odata_id).unwrap_or_default() }
+
+  /// Whatever's inserted right now
+  pub fn image(&self) -> Option<&str> { self.image.as_deref().filter(|i| !i.is_empty()) }
+
+  fn is_optical(&self) -> bool { self.media_types.iter().any(|t| matches!(t.as_str(), "CD" | "DVD")) }
+
+  fn action(
+    &self,
+    action: MediaAction
+  ) -> Option<&str> {
+    let actions = self.actions.as_ref()?;
+    let target = match action {
+      MediaAction::Insert => actions.insert.as_ref(),
+      MediaAction::Eject => actions.eject.as_ref()
+    };
+
+    redfish_path(&target?.target)
+  }
+}
+
+/// How a slot gets its image changed
+#[derive(Debug, PartialEq)]
+pub enum MediaRequest {
+  /// POST to the slot's `InsertMedia`/`EjectMedia` action
+  Action(String, Value),
+  /// iLO 4 has no actions and takes a PATCH to `Image` instead
+  Patch(String, Value)
+}
+
+pub fn media_request(
+  slot: &VirtualMedia,
+  action: MediaAction,
+  url: Option<&str>
+) -> MediaRequest {
+  match slot.action(action) {
+    Some(target) => MediaRequest::Action(
+      target.to_string(),
+      match action {
+        MediaAction::Insert => json!({ "Image": url, "Inserted": true, "WriteProtected": true }),
+        MediaAction::Eject => json!({})
+      }
+    ),
+    None => {
+      let image = match action {
+        MediaAction::Insert => url,
+        MediaAction::Eject => None
+      };
+      MediaRequest::Patch(slot.path().to_string(), json!({ "Image": image }))
+    }
+  }
+}
+
+/// The CD/DVD slot, BMCs that list them under the system instead of the manager are checked after.
+/// Errors only come back when neither collection could be read
+pub async fn optical_slot(host: &BmcHost) -> Result<Option<VirtualMedia>, IloError> {
+  let ids = resource_ids(host).await?;
+  let mut read_any = false;
+  let mut last_err = None;
+
+  for path in [
+    format!("Managers/{}/VirtualMedia", ids.manager),
+    format!("Systems/{}/VirtualMedia", ids.system)
+  ] {
+    match expand::<VirtualMedia>(host, &path).await {
+      Ok(slots) => {
+        read_any = true;
+        if let Some(slot) = slots.into_iter().find(|s| s.is_optical() && !s.path().is_empty()) {
+          return Ok(Some(slot));
+        }
+      },
+      Err(e) => last_err = Some(e)
+    }
+  }
+
+  match last_err {
+    Some(e) if !read_any => Err(e),
+    _ => Ok(None)
+  }
+}
+
+pub async fn apply_media(
+  host: &BmcHost,
+  slot: &VirtualMedia,
+  action: MediaAction,
+  url: Option<&str>
+) -> Result<(), IloError> {
+  match media_request(slot, action, url) {
+    MediaRequest::Action(path, body) => redfish_post(host, &path, &body).await,
+    MediaRequest::Patch(path, body) => redfish_patch(host, &path, &body).await
+  }
+}
+
+/// Boot the server from another device once
+#[poise::command(slash_command)]
+pub async fn boot(
+  ctx: PoiseCtx<'_>,
+  #[description = "Device to boot from on the next boot"] target: BootTarget,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let prompt = match target {
+    BootTarget::None => format!("Are you sure you want to **clear the boot override** of `{}`?", host.name),
+    _ => format!("Are you sure you want `{}` to boot from **{}** next time?", host.name, target.name())
+  };
+  if !confirm(ctx, prompt).await? {
+    return Ok(());
+  }
+
+  let outcome = match ilo_patch(&host, RedfishEndpoint::System, &BootOverrideRequest::new(target)).await {
+    Ok(()) => "Set".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  log_action(ctx, &host, &format!("BootSourceOverrideTarget={}", target.as_redfish()), &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**Boot override** on `{}`: {outcome}", host.name)))
+    .await?;
+
+  Ok(())
+}
+
+/// Insert or eject an ISO on the server's virtual CD/DVD drive
+#[poise::command(slash_command)]
+pub async fn media(
+  ctx: PoiseCtx<'_>,
+  #[description = "What to do with the drive"] action: MediaAction,
+  #[description = "HTTP(S) URL of the ISO to insert"] url: Option<String>,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let prompt = match (action, url.as_deref()) {
+    (MediaAction::Insert, Some(url)) if url.starts_with("http://") || url.starts_with("https://") => {
+      format!("Are you sure you want to insert `{url}` into `{}`?", host.name)
+    },
+    (MediaAction::Insert, _) => {
+      ctx.reply("Inserting needs an HTTP(S) URL to the ISO!").await?;
+      return Ok(());
+    },
+    (MediaAction::Eject, _) => format!("Are you sure you want to eject the virtual media of `{}`?", host.name)
+  };
+  if !confirm(ctx, prompt).await? {
+    return Ok(());
+  }
+
+  let outcome = match optical_slot(&host).await {
+    Ok(Some(slot)) => match apply_media(&host, &slot, action, url.as_deref()).await {
+      Ok(()) => match (action, slot.image()) {
+        (MediaAction::Eject, Some(previous)) => format!("Ejected `{previous}`"),
+        _ => "Done".to_string()
+      },
+      Err(e) => failed(ctx, &host, &e)
+    },
+    Ok(None) => "Failed: there's no CD/DVD virtual media slot".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  let log = match action {
+    MediaAction::Insert => format!("VirtualMedia.InsertMedia={}", url.as_deref().unwrap_or_default()),
+    MediaAction::Eject => "VirtualMedia.EjectMedia".to_string()
+  };
+  log_action(ctx, &host, &log, &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**{}** on `{}`: {outcome}", action.name(), host.name)))
+    .await?;
+
+  Ok(())
+}
@@ -0,0 +1,257 @@
+use {
+  flate2::{
+    Compression,
+    write::ZlibEncoder
+  },
+  std::io::{
+    self,
+    Write
+  }
+};
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 400;
+const MARGIN_LEFT: usize = 60;
+const MARGIN_RIGHT: usize = 15;
+const MARGIN_TOP: usize = 15;
+const MARGIN_BOTTOM: usize = 20;
+const GRID_LINES: usize = 5;
+
+const BACKGROUND: [u8; 3] = [0x2B, 0x2D, 0x31];
+const GRID: [u8; 3] = [0x40, 0x42, 0x49];
+const LABEL: [u8; 3] = [0xB5, 0xBA, 0xC1];
+
+/// Matches the square emojis used for the legend
+pub const PALETTE: [([u8; 3], &str); 8] = [
+  ([0xDD, 0x2E, 0x44], "🟥"),
+  ([0x55, 0xAC, 0xEE], "🟦"),
+  ([0x78, 0xB1, 0x59], "🟩"),
+  ([0xFD, 0xCB, 0x58], "🟨"),
+  ([0xAA, 0x8E, 0xD6], "🟪"),
+  ([0xF4, 0x90, 0x0C], "🟧"),
+  ([0xC1, 0x69, 0x4F], "🟫"),
+  ([0xE6, 0xE7, 0xE8], "⬜")
+];
+
+/// 3x5 glyphs, enough for the axis labels
+fn glyph(c: char) -> Option<[u8; 5]> {
+  Some(match c {
+    '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+    '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+    '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+    '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+    '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+    '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+    '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+    '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+    '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+    '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+    '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+    '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+    _ => return None
+  })
+}
+
+pub struct Series {
+  pub color:  [u8; 3],
+  /// (unix timestamp, value), sorted by time
+  pub points: Vec<(i64, f64)>
+}
+
+struct Canvas {
+  pixels: Vec<u8>
+}
+
+impl Canvas {
+  fn new() -> Self {
+    Self {
+      pixels: BACKGROUND.repeat(WIDTH * HEIGHT)
+    }
+  }
+
+  fn set(
+    &mut self,
+    x: i64,
+    y: i64,
+    color: [u8; 3]
+  ) {
+    if x < 0 || y < 0 || x >= WIDTH as i64 || y >= HEIGHT as i64 {
+      return;
+    }
+    let i = (y as usize * WIDTH + x as usize) * 3;
+    self.pixels[i..i + 3].copy_from_slice(&color);
+  }
+
+  fn hline(
+    &mut self,
+    y: usize,
+    color: [u8; 3]
+  ) {
+    for x in MARGIN_LEFT..WIDTH - MARGIN_RIGHT {
+      self.set(x as i64, y as i64, color);
+    }
+  }
+
+  fn vline(
+    &mut self,
+    x: usize,
+    color: [u8; 3]
+  ) {
+    for y in MARGIN_TOP..HEIGHT - MARGIN_BOTTOM {
+      self.set(x as i64, y as i64, color);
+    }
+  }
+
+  /// Bresenham, drawn 2px thick so it survives Discord's preview scaling
+  fn line(
+    &mut self,
+    (mut x0, mut y0): (i64, i64),
+    (x1, y1): (i64, i64),
+    color: [u8; 3]
+  ) {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let mut err = dx + dy;
+
+    loop {
+      self.set(x0, y0, color);
+      self.set(x0, y0 + 1, color);
+      if x0 == x1 && y0 == y1 {
+        break;
+      }
+      let e2 = 2 * err;
+      if e2 >= dy {
+        err += dy;
+        x0 += sx;
+      }
+      if e2 <= dx {
+        err += dx;
+        y0 += sy;
+      }
+    }
+  }
+
+  /// Right-aligned text ending at `x`, glyphs are scaled up 2x
+  fn text_right(
+    &mut self,
+    text: &str,
+    x: usize,
+    y: usize
+  ) {
+    let mut cursor = x as i64 - text.chars().count() as i64 * 8;
+    for c in text.chars() {
+      if let Some(rows) = glyph(c) {
+        for (row, bits) in rows.iter().enumerate() {
+          for col in 0..3 {
+            if bits & (0b100 >> col) != 0 {
+              for (ox, oy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                self.set(cursor + col * 2 + ox, y as i64 + row as i64 * 2 + oy, LABEL);
+              }
+            }
+          }
+        }
+      }
+      cursor += 8;
+    }
+  }
+
+  fn encode_png(&self) -> io::Result<Vec<u8>> {
+    let mut raw = Vec::with_capacity((WIDTH * 3 + 1) * HEIGHT);
+    for row in self.pixels.chunks(WIDTH * 3) {
+      // Filter type "None" for every scanline
+      raw.push(0);
+      raw.extend_from_slice(row);
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw)?;
+    let idat = encoder.finish()?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    // 8-bit RGB, default compression/filter, no interlacing
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &ihdr);
+    png_chunk(&mut png, b"IDAT", &idat);
+    png_chunk(&mut png, b"IEND", &[]);
+
+    Ok(png)
+  }
+}
+
+fn png_chunk(
+  out: &mut Vec<u8>,
+  kind: &[u8; 4],
+  data: &[u8]
+) {
+  let mut crc = crc32fast::Hasher::new();
+  crc.update(kind);
+  crc.update(data);
+
+  out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+  out.extend_from_slice(kind);
+  out.extend_from_slice(data);
+  out.extend_from_slice(&crc.finalize().to_be_bytes());
+}
+
+/// Render the series as a line chart spanning `start..end`, returns the PNG bytes
+pub fn render_line_chart(
+  series: &[Series],
+  start: i64,
+  end: i64
+) -> io::Result<Vec<u8>> {
+  let mut canvas = Canvas::new();
+
+  let values = series.iter().flat_map(|s| s.points.iter().map(|(_, v)| *v));
+  let (mut min, mut max) = values.fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+  if min > max {
+    (min, max) = (0.0, 1.0);
+  }
+  if (max - min).abs() < f64::EPSILON {
+    (min, max) = (min - 1.0, max + 1.0);
+  }
+  let pad = (max - min) * 0.05;
+  let (min, max) = (min - pad, max + pad);
+
+  let plot_w = (WIDTH - MARGIN_LEFT - MARGIN_RIGHT - 1) as f64;
+  let plot_h = (HEIGHT - MARGIN_TOP - MARGIN_BOTTOM - 1) as f64;
+  let span = (end - start).max(1) as f64;
+
+  for i in 0..=GRID_LINES {
+    let y = MARGIN_TOP + (plot_h * i as f64 / GRID_LINES as f64) as usize;
+    let value = max - (max - min) * i as f64 / GRID_LINES as f64;
+    let label = if max - min < 10.0 {
+      format!("{value:.1}")
+    } else {
+      format!("{value:.0}")
+    };
+
+    canvas.hline(y, GRID);
+    canvas.text_right(&label, MARGIN_LEFT - 6, y.saturating_sub(5));
+  }
+  for i in 0..=6 {
+    canvas.vline(MARGIN_LEFT + (plot_w * i as f64 / 6.0) as usize, GRID);
+  }
+
+  let to_pixel = |(t, v): (i64, f64)| {
+    let x = MARGIN_LEFT as f64 + (t - start) as f64 / span * plot_w;
+    let y = MARGIN_TOP as f64 + (max - v) / (max - min) * plot_h;
+    (x.round() as i64, y.round() as i64)
+  };
+
+  for s in series {
+    let mut points = s.points.iter().copied().map(to_pixel);
+    if let Some(mut prev) = points.next() {
+      canvas.set(prev.0, prev.1, s.color);
+      for point in points {
+        canvas.line(prev, point, s.color);
+        prev = point;
+      }
+    }
+  }
+
+  canvas.encode_png()
+}
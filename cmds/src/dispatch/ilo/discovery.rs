@@ -0,0 +1,162 @@
+use {
+  super::{
+    IloError,
+    hosts::BmcHost,
+    redfish_get
+  },
+  dashmap::DashMap,
+  futures::future::join_all,
+  reqwest::StatusCode,
+  serde::{
+    Deserialize,
+    de::DeserializeOwned
+  },
+  std::sync::LazyLock
+};
+
+/// Fallback for services that list nothing, matches HPE's layout
+const DEFAULT_ID: &str = "1";
+const DEFAULT_LOG_SERVICE: &str = "IML";
+
+/// Log services in the order we'd rather show them
+const PREFERRED_LOGS: [&str; 4] = ["IML", "SEL", "Sel", "Log1"];
+/// Same for the manager's own log, iLO's IEL or iDRAC's Lifecycle Controller log
+const PREFERRED_EVENT_LOGS: [&str; 5] = ["IEL", "Lclog", "SEL", "Sel", "Log1"];
+
+static RESOURCE_IDS: LazyLock<DashMap<&'static str, ResourceIds>> = LazyLock::new(DashMap::new);
+
+/// Member IDs that the endpoints hang off of, every vendor names them differently
+#[derive(Clone)]
+pub struct ResourceIds {
+  pub system:      String,
+  pub chassis:     String,
+  pub manager:     String,
+  pub log_service: String,
+  /// `None` if the manager keeps no log of its own
+  pub event_log:   Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct Collection {
+  #[serde(rename = "Members", default)]
+  pub members: Vec<Link>
+}
+
+#[derive(Deserialize)]
+pub struct Link {
+  #[serde(rename = "@odata.id")]
+  pub odata_id: String
+}
+
+impl Link {
+  pub fn path(&self) -> Option<&str> { redfish_path(&self.odata_id) }
+
+  /// Last segment of the path, the member's ID within its collection
+  pub fn id(&self) -> Option<&str> { self.path().and_then(|p| p.rsplit('/').next()) }
+}
+
+impl Collection {
+  fn ids(&self) -> Vec<&str> {
+    self
+      .members
+      .iter()
+      .filter_map(|m| m.odata_id.trim_end_matches('/').rsplit('/').next())
+      .collect()
+  }
+
+  /// First of the preferred IDs that's listed, otherwise whatever's listed first
+  fn preferred(
+    &self,
+    preferred: &[&str]
+  ) -> Option<String> {
+    let ids = self.ids();
+    preferred.iter().find(|p| ids.contains(p)).or(ids.first()).map(|id| id.to_string())
+  }
+}
+
+/// Strip the service root off of an `@odata.id` or a `Location` header
+pub fn redfish_path(uri: &str) -> Option<&str> { uri.split_once("/redfish/v1/").map(|(_, p)| p.trim_end_matches('/')) }
+
+pub async fn collection(
+  host: &BmcHost,
+  path: &str
+) -> Result<Collection, IloError> {
+  redfish_get(host, path).await
+}
+
+/// Fetch every member of a collection, members that fail to load are left out
+pub async fn expand<T: DeserializeOwned>(
+  host: &BmcHost,
+  path: &str
+) -> Result<Vec<T>, IloError> {
+  Ok(expand_each(host, path).await?.into_iter().filter_map(|(_, member)| member.ok()).collect())
+}
+
+/// Fetch every member of a collection along with its ID, keeping the ones that failed to load
+pub async fn expand_each<T: DeserializeOwned>(
+  host: &BmcHost,
+  path: &str
+) -> Result<Vec<(String, Result<T, IloError>)>, IloError> {
+  Ok(fetch_links(host, &collection(host, path).await?.members).await)
+}
+
+/// Fetch each linked resource along with its ID
+pub async fn fetch_links<T: DeserializeOwned>(
+  host: &BmcHost,
+  links: &[Link]
+) -> Vec<(String, Result<T, IloError>)> {
+  let links: Vec<&Link> = links.iter().filter(|l| l.path().is_some()).collect();
+  let fetched = join_all(links.iter().filter_map(|l| l.path()).map(|p| redfish_get::<T>(host, p))).await;
+
+  links.iter().map(|l| l.id().unwrap_or_default().to_string()).zip(fetched).collect()
+}
+
+/// Discover the host's system, chassis, manager and log service IDs, cached after the first lookup
+pub async fn resource_ids(host: &BmcHost) -> Result<ResourceIds, IloError> {
+  if let Some(ids) = RESOURCE_IDS.get(host.id) {
+    return Ok(ids.clone());
+  }
+
+  let (systems, chassis, managers) = tokio::join!(collection(host, "Systems"), collection(host, "Chassis"), collection(host, "Managers"));
+  let (systems, chassis, managers) = (systems?, chassis?, managers?);
+
+  let system = systems.ids().first().copied().unwrap_or(DEFAULT_ID).to_string();
+
+  // Dell lists enclosures and backplanes next to the main chassis, which shares its ID with the system
+  let chassis_ids = chassis.ids();
+  let chassis = chassis_ids
+    .iter()
+    .find(|id| **id == system)
+    .or(chassis_ids.first())
+    .copied()
+    .unwrap_or(DEFAULT_ID)
+    .to_string();
+
+  let manager = managers.ids().first().copied().unwrap_or(DEFAULT_ID).to_string();
+
+  let log_service = match collection(host, &format!("Systems/{system}/LogServices")).await {
+    Ok(logs) => logs.preferred(&PREFERRED_LOGS).unwrap_or_else(|| DEFAULT_LOG_SERVICE.to_string()),
+    Err(_) => DEFAULT_LOG_SERVICE.to_string()
+  };
+
+  // Only /ilo events needs this, it reports the log as missing rather than every other command failing over it
+  let event_log = match collection(host, &format!("Managers/{manager}/LogServices")).await {
+    Ok(logs) => logs.preferred(&PREFERRED_EVENT_LOGS),
+    Err(IloError::Status(StatusCode::NOT_FOUND, _)) => None,
+    Err(e) => {
+      eprintln!("iLO[Discovery] Couldn't list the manager's log services on {}: {e}", host.name);
+      None
+    }
+  };
+
+  let ids = ResourceIds {
+    system,
+    chassis,
+    manager,
+    log_service,
+    event_log
+  };
+  RESOURCE_IDS.insert(host.id, ids.clone());
+
+  Ok(ids)
+}
@@ -0,0 +1,313 @@
+use {
+  super::{
+    Chassis,
+    Power,
+    chart::{
+      PALETTE,
+      Series,
+      render_line_chart
+    },
+    embed_builder,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    ChoiceParameter,
+    CreateReply,
+    serenity_prelude::CreateAttachment
+  },
+  sqlx::{
+    SqlitePool,
+    sqlite::{
+      SqliteConnectOptions,
+      SqlitePoolOptions
+    }
+  },
+  std::{
+    collections::BTreeMap,
+    time::{
+      SystemTime,
+      UNIX_EPOCH
+    }
+  },
+  tokio::sync::OnceCell
+};
+
+/// Samples older than this get pruned
+const RETENTION_SECS: i64 = 30 * 86400;
+/// Roughly one data point per two pixels of chart width
+const CHART_BUCKETS: i64 = 370;
+
+static POOL: OnceCell<SqlitePool> = OnceCell::const_new();
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum HistoryMetric {
+  Temperature,
+  Fans,
+  Power
+}
+
+impl HistoryMetric {
+  fn key(&self) -> &'static str {
+    match self {
+      HistoryMetric::Temperature => "temperature",
+      HistoryMetric::Fans => "fan",
+      HistoryMetric::Power => "power"
+    }
+  }
+
+  /// Fans read in percent or RPM depending on the BMC, so theirs is stored with each sample
+  fn unit(&self) -> &'static str {
+    match self {
+      HistoryMetric::Temperature => "°C",
+      HistoryMetric::Fans => "",
+      HistoryMetric::Power => "w"
+    }
+  }
+}
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum HistoryRange {
+  #[name = "1 hour"]
+  Hour,
+  #[name = "6 hours"]
+  SixHours,
+  #[name = "24 hours"]
+  Day,
+  #[name = "7 days"]
+  Week,
+  #[name = "30 days"]
+  Month
+}
+
+impl HistoryRange {
+  fn secs(&self) -> i64 {
+    match self {
+      HistoryRange::Hour => 3600,
+      HistoryRange::SixHours => 6 * 3600,
+      HistoryRange::Day => 86400,
+      HistoryRange::Week => 7 * 86400,
+      HistoryRange::Month => 30 * 86400
+    }
+  }
+}
+
+/// Where the samples are kept, override with `KON_ILO_HISTORY`
+fn db_path() -> String { std::env::var("KON_ILO_HISTORY").unwrap_or_else(|_| "ilo_history.db".to_string()) }
+
+fn now() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default()
+}
+
+async fn pool() -> Result<&'static SqlitePool, sqlx::Error> {
+  POOL
+    .get_or_try_init(|| async {
+      let options = SqliteConnectOptions::new().filename(db_path()).create_if_missing(true);
+      let pool = SqlitePoolOptions::new().max_connections(2).connect_with(options).await?;
+
+      sqlx::query(
+        "CREATE TABLE IF NOT EXISTS samples (host TEXT NOT NULL, metric TEXT NOT NULL, sensor TEXT NOT NULL, ts INTEGER NOT NULL, value REAL NOT \
+         NULL, unit TEXT NOT NULL DEFAULT '')"
+      )
+      .execute(&pool)
+      .await?;
+      // Databases from before units were stored, their samples fall back on the metric's unit
+      let (has_unit,): (bool,) = sqlx::query_as("SELECT COUNT(*) > 0 FROM pragma_table_info('samples') WHERE name = 'unit'")
+        .fetch_one(&pool)
+        .await?;
+      if !has_unit {
+        sqlx::query("ALTER TABLE samples ADD COLUMN unit TEXT NOT NULL DEFAULT ''")
+          .execute(&pool)
+          .await?;
+      }
+      sqlx::query("CREATE INDEX IF NOT EXISTS samples_lookup ON samples (host, metric, ts)")
+        .execute(&pool)
+        .await?;
+
+      Ok(pool)
+    })
+    .await
+}
+
+async fn insert(
+  host: &BmcHost,
+  samples: Vec<(&'static str, String, f64, &'static str)>
+) -> Result<(), sqlx::Error> {
+  let pool = pool().await?;
+  let ts = now();
+  let mut tx = pool.begin().await?;
+
+  for (metric, sensor, value, unit) in samples {
+    sqlx::query("INSERT INTO samples (host, metric, sensor, ts, value, unit) VALUES (?, ?, ?, ?, ?, ?)")
+      .bind(host.id)
+      .bind(metric)
+      .bind(sensor)
+      .bind(ts)
+      .bind(value)
+      .bind(unit)
+      .execute(&mut *tx)
+      .await?;
+  }
+
+  sqlx::query("DELETE FROM samples WHERE ts < ?")
+    .bind(ts - RETENTION_SECS)
+    .execute(&mut *tx)
+    .await?;
+
+  tx.commit().await
+}
+
+/// Store a round of readings, called by the monitor on every poll
+pub async fn record_samples(
+  host: &BmcHost,
+  thermal: Option<&Chassis>,
+  power: Option<&Power>
+) {
+  let mut samples = Vec::new();
+
+  if let Some(thermal) = thermal {
+    for temp in &thermal.temperatures {
+      if let Some(reading) = temp.reading_celsius.filter(|r| *r != 0) {
+        samples.push((
+          HistoryMetric::Temperature.key(),
+          temp.name.clone(),
+          reading as f64,
+          HistoryMetric::Temperature.unit()
+        ));
+      }
+    }
+    for fan in &thermal.fans {
+      if let Some(reading) = fan.value() {
+        samples.push((HistoryMetric::Fans.key(), fan.label().to_string(), reading as f64, fan.unit()));
+      }
+    }
+  }
+
+  if let Some(watts) = power.and_then(Power::consumed) {
+    samples.push((
+      HistoryMetric::Power.key(),
+      "Consumed".to_string(),
+      watts as f64,
+      HistoryMetric::Power.unit()
+    ));
+  }
+
+  if samples.is_empty() {
+    return;
+  }
+
+  if let Err(e) = insert(host, samples).await {
+    eprintln!("iLO[History] Couldn't store the samples for {}: {e}", host.name);
+  }
+}
+
+/// Bucketed averages per sensor, oldest first, along with the sensor's unit
+async fn query(
+  host: &BmcHost,
+  metric: HistoryMetric,
+  since: i64,
+  bucket: i64
+) -> Result<BTreeMap<String, (String, Vec<(i64, f64)>)>, sqlx::Error> {
+  let rows: Vec<(String, i64, f64, String)> = sqlx::query_as(
+    "SELECT sensor, (ts / ?) * ? AS bucket, AVG(value), MAX(unit) FROM samples WHERE host = ? AND metric = ? AND ts >= ? GROUP BY sensor, bucket \
+     ORDER BY bucket"
+  )
+  .bind(bucket)
+  .bind(bucket)
+  .bind(host.id)
+  .bind(metric.key())
+  .bind(since)
+  .fetch_all(pool().await?)
+  .await?;
+
+  let mut series: BTreeMap<String, (String, Vec<(i64, f64)>)> = BTreeMap::new();
+  for (sensor, ts, value, unit) in rows {
+    let entry = series.entry(sensor).or_default();
+    if !unit.is_empty() {
+      entry.0 = unit;
+    }
+    entry.1.push((ts, value));
+  }
+
+  Ok(series)
+}
+
+/// Chart the server's readings over time
+#[poise::command(slash_command)]
+pub async fn history(
+  ctx: PoiseCtx<'_>,
+  #[description = "Which readings to chart"] metric: HistoryMetric,
+  #[description = "How far back to go"] range: HistoryRange,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let end = now();
+  let start = end - range.secs();
+  let mut series = query(&host, metric, start, (range.secs() / CHART_BUCKETS).max(1)).await?;
+
+  // Stick to the configured sensors like /ilo temperature does
+  if matches!(metric, HistoryMetric::Temperature) && !host.sensors.is_empty() {
+    series.retain(|name, _| host.sensors.iter().any(|(raw, _)| *raw == name.as_str()));
+  }
+
+  if series.is_empty() {
+    ctx
+      .send(CreateReply::new().embed(embed_builder(
+        &host,
+        "History",
+        Some(format!("No **{}** samples recorded in the last {}.", metric.name(), range.name())),
+        None
+      )))
+      .await?;
+    return Ok(());
+  }
+
+  // One color per series, anything past the palette is left off the chart
+  let omitted = series.len().saturating_sub(PALETTE.len());
+  let mut legend = Vec::new();
+  let mut chart = Vec::new();
+  for ((name, (unit, points)), (color, square)) in series.into_iter().zip(PALETTE) {
+    let (lo, hi) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), (_, v)| (lo.min(*v), hi.max(*v)));
+    let display = host
+      .sensors
+      .iter()
+      .find(|(raw, _)| *raw == name)
+      .map(|(_, f)| *f)
+      .unwrap_or(name.as_str());
+    let avg = points.iter().map(|(_, v)| v).sum::<f64>() / points.len() as f64;
+    let unit = if unit.is_empty() { metric.unit() } else { unit.as_str() };
+
+    legend.push(format!(
+      "{square} **{display}:** min `{lo:.0}{unit}` • max `{hi:.0}{unit}` • avg `{avg:.0}{unit}`"
+    ));
+    chart.push(Series { color, points });
+  }
+
+  let png = render_line_chart(&chart, start, end)?;
+  if omitted > 0 {
+    legend.push(format!("-# {omitted} more sensor(s) not charted, only {} fit", PALETTE.len()));
+  }
+  legend.push(format!("-# <t:{start}:f> → <t:{end}:f>"));
+
+  let embed = embed_builder(&host, &format!("{} ({})", metric.name(), range.name()), Some(legend.join("\n")), None).image("attachment://history.png");
+
+  ctx
+    .send(CreateReply::new().embed(embed).attachment(CreateAttachment::bytes(png, "history.png")))
+    .await?;
+
+  Ok(())
+}
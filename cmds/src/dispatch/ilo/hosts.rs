@@ -0,0 +1,105 @@
+use {
+  kon_libs::{
+    BINARY_PROPERTIES,
+    BmcProtocol,
+    IloHost,
+    IloTls,
+    PoiseCtx
+  },
+  kon_tokens::try_token_path,
+  poise::serenity_prelude::{
+    AutocompleteChoice,
+    CreateAutocompleteResponse
+  }
+};
+
+/// Resolved BMC target with its address and credentials
+pub struct BmcHost {
+  pub id:       &'static str,
+  pub name:     &'static str,
+  pub address:  String,
+  pub username: String,
+  pub password: String,
+  pub protocol: BmcProtocol,
+  pub tls:      &'static IloTls,
+  pub sensors:  &'static [(&'static str, &'static str)]
+}
+
+impl BmcHost {
+  async fn load(meta: &'static IloHost) -> Option<Self> {
+    let (address, username, password) = match meta.credentials_env {
+      Some(var) => {
+        let raw = std::env::var(var).ok()?;
+        let mut parts = raw.splitn(3, ';');
+        (parts.next()?.to_string(), parts.next()?.to_string(), parts.next()?.to_string())
+      },
+      None => {
+        // Runs in the monitor and listener tasks too, a TokenService outage skips the host instead of taking them down
+        let token = match try_token_path().await {
+          Ok(token) => token,
+          Err(e) => {
+            eprintln!("iLO[Hosts] Couldn't reach the TokenService for \"{}\": {e}", meta.id);
+            return None;
+          }
+        };
+        (token.ilo_ip, token.ilo_user, token.ilo_pw)
+      }
+    };
+
+    Some(Self {
+      id: meta.id,
+      name: meta.name,
+      address,
+      username,
+      password,
+      protocol: meta.protocol,
+      tls: &meta.tls,
+      sensors: &meta.sensors
+    })
+  }
+}
+
+/// Look up a host by its ID, or the first configured one if none is given
+pub async fn find_host(id: Option<&str>) -> Option<BmcHost> {
+  let hosts = &BINARY_PROPERTIES.ilo_hosts;
+  let meta = match id {
+    Some(id) => hosts.iter().find(|h| h.id.eq_ignore_ascii_case(id) || h.name.eq_ignore_ascii_case(id))?,
+    None => hosts.first()?
+  };
+
+  match BmcHost::load(meta).await {
+    Some(host) => Some(host),
+    None => {
+      eprintln!("iLO[Hosts] Missing or malformed credentials for \"{}\"", meta.id);
+      None
+    }
+  }
+}
+
+/// Every configured host that has usable credentials
+pub async fn all_hosts() -> Vec<BmcHost> {
+  let mut hosts = Vec::with_capacity(BINARY_PROPERTIES.ilo_hosts.len());
+  for meta in &BINARY_PROPERTIES.ilo_hosts {
+    if let Some(host) = BmcHost::load(meta).await {
+      hosts.push(host);
+    }
+  }
+
+  hosts
+}
+
+pub async fn autocomplete_host<'a>(
+  _: PoiseCtx<'_>,
+  partial: &'a str
+) -> CreateAutocompleteResponse<'a> {
+  let partial = partial.to_lowercase();
+  let choices: Vec<AutocompleteChoice> = BINARY_PROPERTIES
+    .ilo_hosts
+    .iter()
+    .filter(|h| h.id.contains(&partial) || h.name.to_lowercase().contains(&partial))
+    .take(25)
+    .map(|h| AutocompleteChoice::new(h.name, h.id))
+    .collect();
+
+  CreateAutocompleteResponse::new().set_choices(choices)
+}
@@ -0,0 +1,454 @@
+use {
+  super::{
+    IloError,
+    Power,
+    RedfishEndpoint,
+    Status,
+    System,
+    discovery::{
+      expand,
+      resource_ids
+    },
+    embed_builder,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_data,
+    ilo_err,
+    lenient_int,
+    pack_fields,
+    pack_pages,
+    paginate::paginate,
+    redfish_get,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    CreateReply,
+    serenity_prelude::CreateAttachment
+  },
+  serde::{
+    Deserialize,
+    Serialize
+  },
+  std::collections::BTreeMap
+};
+
+/// DMTF's `SoftwareInventory`, HPE's older layout gets converted into this
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Firmware {
+  name:       Option<String>,
+  updateable: Option<bool>,
+  version:    Option<String>
+}
+
+/// iLO 4 groups its firmware by type under `Current` instead of having an inventory collection
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HpeFirmwareInventory {
+  #[serde(default)]
+  current: BTreeMap<String, Vec<HpeFirmware>>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HpeFirmware {
+  name:           Option<String>,
+  version_string: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Processor {
+  #[serde(rename = "MaxSpeedMHz", default, deserialize_with = "lenient_int")]
+  max_speed_mhz: Option<i32>,
+  model:         Option<String>,
+  socket:        Option<String>,
+  #[serde(default)]
+  status:        Status,
+  #[serde(default, deserialize_with = "lenient_int")]
+  total_cores:   Option<i32>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  total_threads: Option<i32>
+}
+
+/// iLO 4 has its own names for nearly everything here, DMTF's come first
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Dimm {
+  #[serde(rename = "CapacityMiB", default, deserialize_with = "lenient_int")]
+  capacity_mib:          Option<i32>,
+  device_locator:        Option<String>,
+  #[serde(rename = "DIMMStatus")]
+  dimm_status:           Option<String>,
+  #[serde(rename = "DIMMType")]
+  dimm_type:             Option<String>,
+  manufacturer:          Option<String>,
+  #[serde(rename = "MaximumFrequencyMHz", default, deserialize_with = "lenient_int")]
+  maximum_frequency_mhz: Option<i32>,
+  memory_device_type:    Option<String>,
+  name:                  Option<String>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  operating_speed_mhz:   Option<i32>,
+  part_number:           Option<String>,
+  #[serde(rename = "SizeMB", default, deserialize_with = "lenient_int")]
+  size_mb:               Option<i32>,
+  socket_locator:        Option<String>,
+  #[serde(default)]
+  status:                Status
+}
+
+impl Dimm {
+  fn is_present(&self) -> bool { !self.status.is_absent() && self.dimm_status.as_deref() != Some("NotPresent") }
+
+  fn label(&self) -> &str {
+    self
+      .device_locator
+      .as_deref()
+      .or(self.socket_locator.as_deref())
+      .or(self.name.as_deref())
+      .unwrap_or("Unknown slot")
+  }
+
+  fn size_gb(&self) -> Option<i32> { self.capacity_mib.or(self.size_mb).map(|mib| mib / 1024) }
+
+  fn health(&self) -> &str {
+    match &self.status {
+      Status { health: None, state: None } => self.dimm_status.as_deref().unwrap_or("Unknown"),
+      status => status.summary()
+    }
+  }
+}
+
+/// DMTF's `NetworkAdapters` under the chassis, or HPE's own under the system
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NetworkAdapter {
+  #[serde(default)]
+  controllers:    Vec<AdapterController>,
+  /// HPE only
+  firmware:       Option<AdapterFirmware>,
+  manufacturer:   Option<String>,
+  model:          Option<String>,
+  name:           Option<String>,
+  /// HPE only
+  #[serde(default)]
+  physical_ports: Vec<serde_json::Value>,
+  #[serde(default)]
+  status:         Status
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AdapterController {
+  controller_capabilities:  Option<ControllerCapabilities>,
+  firmware_package_version: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ControllerCapabilities {
+  #[serde(default, deserialize_with = "lenient_int")]
+  network_port_count: Option<i32>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AdapterFirmware {
+  current: Option<AdapterFirmwareVersion>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AdapterFirmwareVersion {
+  version_string: Option<String>
+}
+
+impl NetworkAdapter {
+  fn version(&self) -> Option<&str> {
+    self.controllers.iter().find_map(|c| c.firmware_package_version.as_deref()).or(
+      self
+        .firmware
+        .as_ref()
+        .and_then(|f| f.current.as_ref())
+        .and_then(|c| c.version_string.as_deref())
+    )
+  }
+
+  fn ports(&self) -> usize {
+    let dmtf: i32 = self
+      .controllers
+      .iter()
+      .filter_map(|c| c.controller_capabilities.as_ref().and_then(|cap| cap.network_port_count))
+      .sum();
+
+    if dmtf > 0 { dmtf as usize } else { self.physical_ports.len() }
+  }
+}
+
+/// Everything `/ilo inventory` shows, also what the JSON export is made of
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Inventory {
+  pub system:           System,
+  pub firmware:         Vec<Firmware>,
+  pub processors:       Vec<Processor>,
+  pub memory:           Vec<Dimm>,
+  pub network_adapters: Vec<NetworkAdapter>,
+  pub power_supplies:   Vec<super::PowerSupply>
+}
+
+fn or_unknown(value: Option<&str>) -> &str { value.map(str::trim).unwrap_or("Unknown") }
+
+impl Inventory {
+  pub fn overview(&self) -> String {
+    let system = &self.system;
+    let cpus = &system.processor_summary;
+    let memory_health = system
+      .memory_summary
+      .as_ref()
+      .or(system.memory.as_ref())
+      .map(|m| m.status.summary())
+      .unwrap_or("Unknown");
+
+    [
+      format!(
+        "**Model:** `{}{}`",
+        system.manufacturer.as_deref().map(|m| format!("{m} ")).unwrap_or_default(),
+        or_unknown(system.model.as_deref())
+      ),
+      format!("**Serial:** `{}`", or_unknown(system.serial_number.as_deref())),
+      format!("**SKU:** `{}`", or_unknown(system.sku.as_deref())),
+      format!("**BIOS:** `{}`", or_unknown(system.bios_version.as_deref())),
+      format!(
+        "**CPUs:** `{}x, {} threads` - `{}`",
+        cpus.count.unwrap_or(0),
+        cpus.logical_processor_count.map(|t| t.to_string()).unwrap_or_else(|| "?".to_string()),
+        cpus.status.summary()
+      ),
+      format!(
+        "**Memory:** `{}` - `{memory_health}`",
+        system
+          .total_memory_gb()
+          .map(|gb| format!("{gb} GB"))
+          .unwrap_or_else(|| "Unknown".to_string())
+      )
+    ]
+    .join("\n")
+  }
+
+  /// Lines for each section, in the order they're shown
+  pub fn sections(&self) -> Vec<(&'static str, Vec<String>)> {
+    let firmware = self
+      .firmware
+      .iter()
+      .map(|f| format!("**{}:** `{}`", or_unknown(f.name.as_deref()), or_unknown(f.version.as_deref())))
+      .collect();
+
+    let processors = self
+      .processors
+      .iter()
+      .map(|p| {
+        format!(
+          "**{}:** {} • {}C/{}T @ {} MHz - `{}`",
+          or_unknown(p.socket.as_deref()),
+          or_unknown(p.model.as_deref()),
+          p.total_cores.unwrap_or(0),
+          p.total_threads.unwrap_or(0),
+          p.max_speed_mhz.unwrap_or(0),
+          p.status.summary()
+        )
+      })
+      .collect();
+
+    let memory = self
+      .memory
+      .iter()
+      .map(|d| {
+        format!(
+          "**{}:** {} GB {} @ {} MHz ({} {}) - `{}`",
+          d.label(),
+          d.size_gb().unwrap_or(0),
+          or_unknown(d.memory_device_type.as_deref().or(d.dimm_type.as_deref())),
+          d.operating_speed_mhz.or(d.maximum_frequency_mhz).unwrap_or(0),
+          or_unknown(d.manufacturer.as_deref()),
+          or_unknown(d.part_number.as_deref()),
+          d.health()
+        )
+      })
+      .collect();
+
+    let adapters = self
+      .network_adapters
+      .iter()
+      .map(|a| {
+        format!(
+          "**{}:** {} port(s) • fw `{}` - `{}`",
+          or_unknown(a.name.as_deref().or(a.model.as_deref())),
+          a.ports(),
+          or_unknown(a.version()),
+          a.status.summary()
+        )
+      })
+      .collect();
+
+    let psus = self
+      .power_supplies
+      .iter()
+      .enumerate()
+      .map(|(i, p)| {
+        format!(
+          "**{}:** {} • {}w • fw `{}` - `{}`",
+          p.name.clone().unwrap_or_else(|| format!("Power Supply {}", i + 1)),
+          or_unknown(p.model.as_deref()),
+          p.power_capacity_watts.unwrap_or(0),
+          or_unknown(p.firmware_version.as_deref()),
+          p.status.summary()
+        )
+      })
+      .collect();
+
+    vec![
+      ("Firmware", firmware),
+      ("Processors", processors),
+      ("Memory", memory),
+      ("Network adapters", adapters),
+      ("Power supplies", psus),
+    ]
+  }
+}
+
+async fn firmware_inventory(
+  host: &BmcHost,
+  system: &str
+) -> Vec<Firmware> {
+  if let Ok(items) = expand::<Firmware>(host, "UpdateService/FirmwareInventory").await
+    && !items.is_empty()
+  {
+    return items;
+  }
+
+  let Ok(legacy) = redfish_get::<HpeFirmwareInventory>(host, &format!("Systems/{system}/FirmwareInventory")).await else {
+    return Vec::new()
+  };
+  legacy
+    .current
+    .into_values()
+    .flatten()
+    .map(|f| Firmware {
+      name:       f.name,
+      updateable: None,
+      version:    f.version_string
+    })
+    .collect()
+}
+
+/// DMTF's location first, then where iLO 5 and iLO 4 keep theirs
+async fn network_adapters(
+  host: &BmcHost,
+  system: &str,
+  chassis: &str
+) -> Vec<NetworkAdapter> {
+  for path in [
+    format!("Chassis/{chassis}/NetworkAdapters"),
+    format!("Systems/{system}/BaseNetworkAdapters"),
+    format!("Systems/{system}/NetworkAdapters")
+  ] {
+    if let Ok(adapters) = expand::<NetworkAdapter>(host, &path).await
+      && !adapters.is_empty()
+    {
+      return adapters;
+    }
+  }
+
+  Vec::new()
+}
+
+/// Collect the host's inventory, sections the BMC doesn't have are left empty
+pub async fn collect_inventory(host: &BmcHost) -> Result<Inventory, IloError> {
+  let ids = resource_ids(host).await?;
+
+  let (system, power, firmware, processors, memory, network_adapters) = tokio::join!(
+    ilo_data::<System>(host, RedfishEndpoint::System),
+    ilo_data::<Power>(host, RedfishEndpoint::Power),
+    firmware_inventory(host, &ids.system),
+    expand::<Processor>(host, &format!("Systems/{}/Processors", ids.system)),
+    expand::<Dimm>(host, &format!("Systems/{}/Memory", ids.system)),
+    network_adapters(host, &ids.system, &ids.chassis)
+  );
+
+  Ok(Inventory {
+    system: system?,
+    firmware,
+    processors: processors.unwrap_or_default().into_iter().filter(|p| !p.status.is_absent()).collect(),
+    memory: memory.unwrap_or_default().into_iter().filter(Dimm::is_present).collect(),
+    network_adapters,
+    power_supplies: power
+      .map(|p| p.power_supplies)
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|p| !p.status.is_absent())
+      .collect()
+  })
+}
+
+/// Retrieve the server's firmware and hardware inventory
+#[poise::command(slash_command)]
+pub async fn inventory(
+  ctx: PoiseCtx<'_>,
+  #[description = "Download the inventory as JSON instead"] export: Option<bool>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let inventory = match collect_inventory(&host).await {
+    Ok(inventory) => inventory,
+    Err(e) => {
+      ilo_err(&ctx, &host, e).await;
+      return Ok(());
+    }
+  };
+
+  if export.unwrap_or(false) {
+    ctx
+      .send(
+        CreateReply::new()
+          .content(format!("Exported the inventory of `{}`", host.name))
+          .attachment(CreateAttachment::bytes(
+            serde_json::to_vec_pretty(&inventory)?,
+            format!("{}-inventory.json", host.id)
+          ))
+      )
+      .await?;
+    return Ok(());
+  }
+
+  let overview = inventory.overview();
+  let mut fields = Vec::new();
+  let mut count = 0;
+  for (name, lines) in inventory.sections() {
+    count += lines.len();
+    fields.extend(pack_fields(name, &lines));
+  }
+
+  let pages = pack_pages(fields)
+    .into_iter()
+    .map(|page| embed_builder(&host, "Inventory", Some(overview.clone()), Some(page)))
+    .collect::<Vec<_>>();
+
+  let pages = if pages.is_empty() {
+    vec![embed_builder(&host, "Inventory", Some(overview), None)]
+  } else {
+    pages
+  };
+
+  paginate(ctx, pages, &format!("{count} components")).await
+}
@@ -0,0 +1,546 @@
+pub mod session;
+
+use {
+  super::{
+    Chassis,
+    Fan,
+    IloError,
+    ImlEntry,
+    Status,
+    Temperature,
+    actions::ResetType,
+    hosts::BmcHost
+  },
+  session::Session,
+  std::collections::HashMap
+};
+
+const NETFN_CHASSIS: u8 = 0x00;
+const NETFN_SENSOR: u8 = 0x04;
+const NETFN_STORAGE: u8 = 0x0A;
+
+const CMD_CHASSIS_STATUS: u8 = 0x01;
+const CMD_CHASSIS_CONTROL: u8 = 0x02;
+const CMD_GET_SENSOR_READING: u8 = 0x2D;
+const CMD_RESERVE_SDR: u8 = 0x22;
+const CMD_GET_SDR: u8 = 0x23;
+const CMD_GET_SEL_ENTRY: u8 = 0x43;
+
+const CONTROL_POWER_DOWN: u8 = 0x00;
+const CONTROL_POWER_UP: u8 = 0x01;
+const CONTROL_HARD_RESET: u8 = 0x03;
+const CONTROL_SOFT_SHUTDOWN: u8 = 0x05;
+
+const CC_RESERVATION_CANCELLED: u8 = 0xC5;
+const CC_NOT_PRESENT: u8 = 0xCB;
+
+const SDR_FULL_SENSOR: u8 = 0x01;
+const SDR_COMPACT_SENSOR: u8 = 0x02;
+const SDR_HEADER_LEN: usize = 5;
+/// Small enough for every BMC's message buffer
+const SDR_CHUNK: usize = 16;
+/// Times the SDR walk starts over when the repository changes under it
+const SDR_RETRIES: usize = 5;
+
+const SENSOR_TEMPERATURE: u8 = 0x01;
+const SENSOR_FAN: u8 = 0x04;
+const READING_THRESHOLD: u8 = 0x01;
+const READING_SENSOR_SPECIFIC: u8 = 0x6F;
+const UNIT_CELSIUS: u8 = 0x01;
+
+/// Sensors behind other controllers need bridged requests, only the BMC's own are read
+const BMC_OWNER: u8 = 0x20;
+const LAST_RECORD: u16 = 0xFFFF;
+/// Walks stop here in case a BMC's record IDs loop back on themselves
+const MAX_RECORDS: usize = 4096;
+
+/// Readable threshold bits of a full sensor record
+const THRESHOLD_UPPER_CRITICAL: u8 = 0x10;
+const THRESHOLD_UPPER_FATAL: u8 = 0x20;
+
+const THRESHOLD_EVENTS: [&str; 12] = [
+  "Lower non-critical going low",
+  "Lower non-critical going high",
+  "Lower critical going low",
+  "Lower critical going high",
+  "Lower non-recoverable going low",
+  "Lower non-recoverable going high",
+  "Upper non-critical going low",
+  "Upper non-critical going high",
+  "Upper critical going low",
+  "Upper critical going high",
+  "Upper non-recoverable going low",
+  "Upper non-recoverable going high"
+];
+
+fn ipmi_err(msg: impl Into<String>) -> IloError { IloError::Ipmi(msg.into()) }
+
+fn sign_extend(
+  value: u16,
+  bits: u32
+) -> i16 {
+  let shift = 16 - bits;
+  ((value << shift) as i16) >> shift
+}
+
+/// What's needed out of a full sensor record to read the sensor and make sense of its reading
+pub struct SensorRecord {
+  pub number:          u8,
+  pub sensor_type:     u8,
+  pub name:            String,
+  owned_by_bmc:        bool,
+  threshold_based:     bool,
+  analog_format:       u8,
+  percentage:          bool,
+  unit:                u8,
+  linear:              bool,
+  m:                   i16,
+  b:                   i16,
+  b_exp:               i8,
+  r_exp:               i8,
+  readable_thresholds: u8,
+  upper_fatal:         u8,
+  upper_critical:      u8
+}
+
+/// ID string of a full or compact sensor record
+fn record_name(record: &[u8]) -> Option<(u8, String)> {
+  let start = match *record.get(3)? {
+    SDR_FULL_SENSOR => 48,
+    SDR_COMPACT_SENSOR => 32,
+    _ => return None
+  };
+  let len = (record.get(start - 1)? & 0x1F) as usize;
+  let raw = record.get(start..(start + len).min(record.len()))?;
+
+  Some((record[7], String::from_utf8_lossy(raw).trim_matches(['\0', ' ']).to_string()))
+}
+
+impl SensorRecord {
+  pub fn parse(record: &[u8]) -> Option<Self> {
+    if record.len() < 48 || record[3] != SDR_FULL_SENSOR {
+      return None;
+    }
+    let (number, name) = record_name(record)?;
+
+    Some(Self {
+      number,
+      sensor_type: record[12],
+      name,
+      owned_by_bmc: record[5] == BMC_OWNER && record[6] & 0x03 == 0,
+      threshold_based: record[13] == READING_THRESHOLD,
+      analog_format: record[20] >> 6,
+      percentage: record[20] & 0x01 != 0,
+      unit: record[21],
+      linear: record[23] & 0x7F == 0,
+      m: sign_extend(u16::from(record[24]) | (u16::from(record[25] >> 6) << 8), 10),
+      b: sign_extend(u16::from(record[26]) | (u16::from(record[27] >> 6) << 8), 10),
+      b_exp: sign_extend(u16::from(record[29] & 0x0F), 4) as i8,
+      r_exp: sign_extend(u16::from(record[29] >> 4), 4) as i8,
+      readable_thresholds: record[18] & 0x3F,
+      upper_fatal: record[36],
+      upper_critical: record[37]
+    })
+  }
+
+  /// `y = (M * x + B * 10^Bexp) * 10^Rexp`, `None` for sensors that aren't analog or linear
+  pub fn convert(
+    &self,
+    raw: u8
+  ) -> Option<f64> {
+    let x = match self.analog_format {
+      0 => f64::from(raw),
+      1 if raw & 0x80 != 0 => -f64::from(!raw),
+      1 => f64::from(raw),
+      2 => f64::from(raw as i8),
+      _ => return None
+    };
+    if !self.linear {
+      return None;
+    }
+
+    Some((f64::from(self.m) * x + f64::from(self.b) * 10f64.powi(self.b_exp.into())) * 10f64.powi(self.r_exp.into()))
+  }
+
+  fn threshold(
+    &self,
+    bit: u8,
+    raw: u8
+  ) -> Option<i32> {
+    if self.readable_thresholds & bit == 0 {
+      return None;
+    }
+
+    self.convert(raw).map(|v| v.round() as i32)
+  }
+}
+
+/// Health out of the threshold comparison bits of a sensor reading
+pub fn threshold_health(state: u8) -> &'static str {
+  if state & 0b0011_0110 != 0 {
+    "Critical"
+  } else if state & 0b0000_1001 != 0 {
+    "Warning"
+  } else {
+    "OK"
+  }
+}
+
+/// Lay the readings out like Redfish's Thermal resource so everything downstream stays the same,
+/// each reading is the raw response to Get Sensor Reading
+pub fn sensor_readings(readings: Vec<(SensorRecord, Vec<u8>)>) -> Chassis {
+  let mut chassis = Chassis {
+    fans:         Vec::new(),
+    temperatures: Vec::new()
+  };
+
+  for (record, data) in readings {
+    let (Some(&raw), Some(&flags)) = (data.first(), data.get(1)) else {
+      continue
+    };
+    // Reading unavailable or scanning disabled
+    if flags & 0x20 != 0 || flags & 0x40 == 0 || !record.threshold_based {
+      continue;
+    }
+    let Some(value) = record.convert(raw) else { continue };
+
+    let status = Status {
+      health: Some(threshold_health(data.get(2).copied().unwrap_or(0)).to_string()),
+      state:  Some("Enabled".to_string())
+    };
+
+    match record.sensor_type {
+      SENSOR_TEMPERATURE if record.unit == UNIT_CELSIUS => chassis.temperatures.push(Temperature {
+        upper_threshold_critical: record.threshold(THRESHOLD_UPPER_CRITICAL, record.upper_critical),
+        upper_threshold_fatal: record.threshold(THRESHOLD_UPPER_FATAL, record.upper_fatal),
+        name: record.name,
+        reading_celsius: Some(value.round() as i32),
+        status
+      }),
+      SENSOR_FAN => chassis.fans.push(Fan {
+        current_reading: None,
+        fan_name: None,
+        name: Some(record.name),
+        reading: Some(value.round() as i32),
+        reading_units: Some(if record.percentage { "Percent" } else { "RPM" }.to_string()),
+        status,
+        units: None
+      }),
+      _ => ()
+    }
+  }
+
+  chassis
+}
+
+fn sensor_type_name(sensor_type: u8) -> String {
+  match sensor_type {
+    0x01 => "Temperature",
+    0x02 => "Voltage",
+    0x03 => "Current",
+    0x04 => "Fan",
+    0x05 => "Chassis intrusion",
+    0x07 => "Processor",
+    0x08 => "Power supply",
+    0x09 => "Power unit",
+    0x0C => "Memory",
+    0x0D => "Drive slot",
+    0x0F => "System firmware",
+    0x10 => "Event logging",
+    0x12 => "System event",
+    0x13 => "Critical interrupt",
+    0x14 => "Button",
+    0x1D => "System boot",
+    0x20 => "OS stop",
+    0x23 => "Watchdog",
+    other => return format!("Sensor type {other:#04X}")
+  }
+  .to_string()
+}
+
+/// The sensor-specific offsets worth spelling out along with their severity
+fn sensor_specific_event(
+  sensor_type: u8,
+  offset: u8
+) -> Option<(&'static str, &'static str)> {
+  Some(match (sensor_type, offset) {
+    (0x05, 0x00) => ("General chassis intrusion", "Warning"),
+    (0x07, 0x00) => ("IERR", "Critical"),
+    (0x07, 0x01) => ("Thermal trip", "Critical"),
+    (0x07, 0x07) => ("Presence detected", "OK"),
+    (0x07, 0x08) => ("Disabled", "Warning"),
+    (0x08, 0x00) => ("Presence detected", "OK"),
+    (0x08, 0x01) => ("Failure detected", "Critical"),
+    (0x08, 0x02) => ("Predictive failure", "Warning"),
+    (0x08, 0x03) => ("Input lost", "Critical"),
+    (0x09, 0x00) => ("Powered off", "OK"),
+    (0x09, 0x01) => ("Power cycled", "OK"),
+    (0x09, 0x04) => ("AC lost", "Critical"),
+    (0x09, 0x06) => ("Power unit failure", "Critical"),
+    (0x0C, 0x00) => ("Correctable ECC", "Warning"),
+    (0x0C, 0x01) => ("Uncorrectable ECC", "Critical"),
+    (0x0C, 0x05) => ("Correctable ECC logging limit reached", "Warning"),
+    (0x0D, 0x00) => ("Drive present", "OK"),
+    (0x0D, 0x01) => ("Drive fault", "Critical"),
+    (0x0D, 0x02) => ("Predictive failure", "Warning"),
+    (0x10, 0x02) => ("Log cleared", "OK"),
+    (0x10, 0x04) => ("SEL full", "Warning"),
+    (0x12, 0x00) => ("System reconfigured", "OK"),
+    (0x13, 0x00) => ("Front panel NMI", "Critical"),
+    (0x14, 0x00) => ("Power button pressed", "OK"),
+    (0x14, 0x02) => ("Reset button pressed", "OK"),
+    (0x1D, 0x00) => ("Initiated by power up", "OK"),
+    (0x1D, 0x01) => ("Initiated by hard reset", "OK"),
+    (0x1D, 0x02) => ("Initiated by warm reset", "OK"),
+    (0x20, 0x01) => ("Run-time critical stop", "Critical"),
+    (0x20, 0x03) => ("Graceful shutdown", "OK"),
+    (0x23, 0x00) => ("Timer expired", "Warning"),
+    (0x23, 0x01) => ("Hard reset", "Critical"),
+    (0x23, 0x02) => ("Power down", "Critical"),
+    (0x23, 0x03) => ("Power cycle", "Critical"),
+    _ => return None
+  })
+}
+
+/// SEL timestamps are seconds since 1970, anything up to `0x20000000` counts from when the BMC came up instead
+fn sel_timestamp(secs: u32) -> String {
+  if secs <= 0x2000_0000 || secs == u32::MAX {
+    return String::new();
+  }
+
+  // Days to a civil date, from Howard Hinnant's `civil_from_days`
+  let z = i64::from(secs / 86400) + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+  let time = secs % 86400;
+
+  format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Turn a 16-byte SEL record into an entry, sensor names come from the SDR when it could be read
+pub fn sel_entry(
+  record: &[u8],
+  names: &HashMap<u8, String>
+) -> Option<ImlEntry> {
+  let record: &[u8; 16] = record.try_into().ok()?;
+  let kind = record[2];
+  let timestamp = u32::from_le_bytes([record[3], record[4], record[5], record[6]]);
+
+  let (message, severity) = match kind {
+    0x02 => {
+      let (sensor_type, number, dir_type, offset) = (record[10], record[11], record[12], record[13] & 0x0F);
+      let sensor = names
+        .get(&number)
+        .cloned()
+        .unwrap_or_else(|| format!("{} #{number}", sensor_type_name(sensor_type)));
+
+      let (what, severity) = match dir_type & 0x7F {
+        READING_THRESHOLD => (
+          THRESHOLD_EVENTS
+            .get(offset as usize)
+            .map(|e| e.to_string())
+            .unwrap_or_else(|| format!("Threshold offset {offset}")),
+          match offset {
+            0x00 | 0x01 | 0x06 | 0x07 => "Warning",
+            _ => "Critical"
+          }
+        ),
+        READING_SENSOR_SPECIFIC => match sensor_specific_event(sensor_type, offset) {
+          Some((what, severity)) => (what.to_string(), severity),
+          None => (format!("Event offset {offset}"), "OK")
+        },
+        event_type => (format!("Event type {event_type:#04X}, offset {offset}"), "OK")
+      };
+
+      if dir_type & 0x80 != 0 {
+        (format!("{sensor}: {what} (deasserted)"), "OK")
+      } else {
+        (format!("{sensor}: {what}"), severity)
+      }
+    },
+    0xC0..=0xDF => (format!("OEM record {kind:#04X}: {}", hex::encode_upper(&record[10..])), "OK"),
+    _ => (format!("OEM record {kind:#04X}: {}", hex::encode_upper(&record[3..])), "OK")
+  };
+
+  Some(ImlEntry {
+    // Non-timestamped OEM records have their data where the timestamp would be
+    created: if kind < 0xE0 { sel_timestamp(timestamp) } else { String::new() },
+    message,
+    severity: severity.to_string()
+  })
+}
+
+/// Run `f` in a fresh session and log out afterwards, whether it worked or not
+async fn with_session<T>(
+  host: &BmcHost,
+  f: impl AsyncFnOnce(&mut Session) -> Result<T, IloError>
+) -> Result<T, IloError> {
+  let mut session = Session::open(host).await?;
+  let result = f(&mut session).await;
+  session.close().await;
+
+  result
+}
+
+async fn reserve_sdr(session: &mut Session) -> Result<[u8; 2], IloError> {
+  let data = session.command(NETFN_STORAGE, CMD_RESERVE_SDR, &[]).await?;
+  data
+    .get(..2)
+    .and_then(|r| r.try_into().ok())
+    .ok_or_else(|| IloError::UnexpectedSchema("BMC sent a truncated SDR reservation".to_string()))
+}
+
+/// A record and the ID of the next one, `None` when the reservation got cancelled halfway
+async fn read_sdr(
+  session: &mut Session,
+  reservation: [u8; 2],
+  id: u16
+) -> Result<Option<(u16, Vec<u8>)>, IloError> {
+  let [id_lo, id_hi] = id.to_le_bytes();
+  let mut record = Vec::new();
+  let mut next = LAST_RECORD;
+  // The header comes first since it says how long the rest is
+  let mut len = SDR_HEADER_LEN;
+
+  while record.len() < len {
+    let count = (len - record.len()).min(SDR_CHUNK) as u8;
+    let request = [reservation[0], reservation[1], id_lo, id_hi, record.len() as u8, count];
+
+    let data = match session.request(NETFN_STORAGE, CMD_GET_SDR, &request).await? {
+      (0x00, data) if data.len() > 2 => data,
+      (0x00, _) => return Err(IloError::UnexpectedSchema(format!("BMC sent an empty chunk of SDR {id:#06X}"))),
+      (CC_RESERVATION_CANCELLED, _) => return Ok(None),
+      (code, _) => return Err(ipmi_err(format!("reading SDR {id:#06X} failed with completion code {code:#04X}")))
+    };
+
+    next = u16::from_le_bytes([data[0], data[1]]);
+    record.extend(&data[2..]);
+    if len == SDR_HEADER_LEN && record.len() >= SDR_HEADER_LEN {
+      len += record[4] as usize;
+    }
+  }
+
+  Ok(Some((next, record)))
+}
+
+/// Every record in the SDR repository, read in chunks since few BMCs hand out a whole record at once
+async fn sdr_records(session: &mut Session) -> Result<Vec<Vec<u8>>, IloError> {
+  let mut reservation = reserve_sdr(session).await?;
+  let mut records = Vec::new();
+  let mut id = 0x0000;
+  let mut retries = 0;
+
+  while id != LAST_RECORD && records.len() < MAX_RECORDS {
+    match read_sdr(session, reservation, id).await? {
+      Some((next, record)) => {
+        records.push(record);
+        id = next;
+      },
+      None if retries < SDR_RETRIES => {
+        retries += 1;
+        reservation = reserve_sdr(session).await?;
+      },
+      None => return Err(ipmi_err("SDR repository kept changing while it was being read"))
+    }
+  }
+
+  Ok(records)
+}
+
+async fn read_sensors(session: &mut Session) -> Result<Chassis, IloError> {
+  let mut readings = Vec::new();
+
+  for record in sdr_records(session).await?.iter().filter_map(|r| SensorRecord::parse(r)) {
+    if !record.owned_by_bmc || !matches!(record.sensor_type, SENSOR_TEMPERATURE | SENSOR_FAN) {
+      continue;
+    }
+
+    // Sensors that aren't fitted answer with an error, they're left out like Redfish's absent ones
+    if let (0x00, data) = session.request(NETFN_SENSOR, CMD_GET_SENSOR_READING, &[record.number]).await? {
+      readings.push((record, data));
+    }
+  }
+
+  Ok(sensor_readings(readings))
+}
+
+async fn read_sel(session: &mut Session) -> Result<Vec<ImlEntry>, IloError> {
+  // Names are a nicety, the SEL is still worth showing without them
+  let names: HashMap<u8, String> = match sdr_records(session).await {
+    Ok(records) => records.iter().filter_map(|r| record_name(r)).collect(),
+    Err(_) => HashMap::new()
+  };
+
+  let mut entries = Vec::new();
+  let mut id = 0x0000;
+
+  while id != LAST_RECORD && entries.len() < MAX_RECORDS {
+    let [id_lo, id_hi] = id.to_le_bytes();
+    let data = match session
+      .request(NETFN_STORAGE, CMD_GET_SEL_ENTRY, &[0x00, 0x00, id_lo, id_hi, 0x00, 0xFF])
+      .await?
+    {
+      (0x00, data) if data.len() >= 18 => data,
+      // An empty SEL has no first entry to hand out
+      (CC_NOT_PRESENT, _) if entries.is_empty() => break,
+      (code, _) => return Err(ipmi_err(format!("reading SEL entry {id:#06X} failed with completion code {code:#04X}")))
+    };
+
+    entries.extend(sel_entry(&data[2..18], &names));
+    id = u16::from_le_bytes([data[0], data[1]]);
+  }
+
+  Ok(entries)
+}
+
+async fn is_powered_on(session: &mut Session) -> Result<bool, IloError> {
+  let data = session.command(NETFN_CHASSIS, CMD_CHASSIS_STATUS, &[]).await?;
+  data
+    .first()
+    .map(|state| state & 0x01 != 0)
+    .ok_or_else(|| IloError::UnexpectedSchema("BMC sent an empty chassis status".to_string()))
+}
+
+async fn chassis_control(
+  session: &mut Session,
+  action: ResetType
+) -> Result<(), IloError> {
+  let control = match action {
+    ResetType::On => CONTROL_POWER_UP,
+    ResetType::ForceOff => CONTROL_POWER_DOWN,
+    ResetType::ForceRestart => CONTROL_HARD_RESET,
+    ResetType::GracefulShutdown => CONTROL_SOFT_SHUTDOWN,
+    // There's no button to press over IPMI, do what pressing it would
+    ResetType::PushPowerButton => {
+      if is_powered_on(session).await? {
+        CONTROL_SOFT_SHUTDOWN
+      } else {
+        CONTROL_POWER_UP
+      }
+    },
+  };
+
+  session.command(NETFN_CHASSIS, CMD_CHASSIS_CONTROL, &[control]).await.map(|_| ())
+}
+
+pub async fn sensors(host: &BmcHost) -> Result<Chassis, IloError> { with_session(host, async |s| read_sensors(s).await).await }
+
+pub async fn sel(host: &BmcHost) -> Result<Vec<ImlEntry>, IloError> { with_session(host, async |s| read_sel(s).await).await }
+
+pub async fn power_state(host: &BmcHost) -> Result<String, IloError> {
+  let on = with_session(host, async |s| is_powered_on(s).await).await?;
+  Ok(if on { "On" } else { "Off" }.to_string())
+}
+
+pub async fn power_control(
+  host: &BmcHost,
+  action: ResetType
+) -> Result<(), IloError> {
+  with_session(host, async |s| chassis_control(s, action).await).await
+}
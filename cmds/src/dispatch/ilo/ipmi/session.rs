@@ -0,0 +1,448 @@
+//! RMCP+ sessions with cipher suite 3 (RAKP-HMAC-SHA1, HMAC-SHA1-96, AES-CBC-128),
+//! the one suite every IPMI 2.0 BMC has to support
+
+use {
+  super::super::{
+    IloError,
+    hosts::BmcHost
+  },
+  aes::Aes128,
+  cbc::cipher::{
+    BlockDecryptMut,
+    BlockEncryptMut,
+    KeyIvInit,
+    block_padding::NoPadding
+  },
+  hmac::{
+    Hmac,
+    Mac
+  },
+  sha1::Sha1,
+  std::net::{
+    Ipv6Addr,
+    SocketAddr
+  },
+  tokio::{
+    net::{
+      UdpSocket,
+      lookup_host
+    },
+    time::{
+      Duration,
+      timeout
+    }
+  }
+};
+
+const IPMI_PORT: u16 = 623;
+const RECV_TIMEOUT: Duration = Duration::from_secs(2);
+const ATTEMPTS: usize = 3;
+
+/// RMCP version 1.0, no ACK, class IPMI
+const RMCP_HEADER: [u8; 4] = [0x06, 0x00, 0xFF, 0x07];
+const AUTH_FORMAT_RMCP_PLUS: u8 = 0x06;
+
+const PAYLOAD_IPMI: u8 = 0x00;
+const PAYLOAD_OPEN_SESSION_REQUEST: u8 = 0x10;
+const PAYLOAD_OPEN_SESSION_RESPONSE: u8 = 0x11;
+const PAYLOAD_RAKP_1: u8 = 0x12;
+const PAYLOAD_RAKP_2: u8 = 0x13;
+const PAYLOAD_RAKP_3: u8 = 0x14;
+const PAYLOAD_RAKP_4: u8 = 0x15;
+const PAYLOAD_ENCRYPTED: u8 = 0x80;
+const PAYLOAD_AUTHENTICATED: u8 = 0x40;
+
+const AUTH_RAKP_HMAC_SHA1: u8 = 0x01;
+const INTEGRITY_HMAC_SHA1_96: u8 = 0x01;
+const CONFIDENTIALITY_AES_CBC_128: u8 = 0x01;
+
+const PRIVILEGE_ADMIN: u8 = 0x04;
+/// Administrator, looked up by name only
+const RAKP_ROLE: u8 = 0x10 | PRIVILEGE_ADMIN;
+
+const BMC_ADDRESS: u8 = 0x20;
+const CONSOLE_ADDRESS: u8 = 0x81;
+
+const NETFN_APP: u8 = 0x06;
+const CMD_SET_SESSION_PRIVILEGE: u8 = 0x3B;
+const CMD_CLOSE_SESSION: u8 = 0x3C;
+
+const AUTH_CODE_LEN: usize = 12;
+
+type HmacSha1 = Hmac<Sha1>;
+
+fn hmac_sha1(
+  key: &[u8],
+  parts: &[&[u8]]
+) -> [u8; 20] {
+  let mut mac = HmacSha1::new_from_slice(key).expect("HMAC takes keys of any length");
+  for part in parts {
+    mac.update(part);
+  }
+  mac.finalize().into_bytes().into()
+}
+
+/// From the OS's CSPRNG, the console random, session ID and IVs must not be guessable
+fn random_bytes<const N: usize>() -> [u8; N] {
+  let mut out = [0; N];
+  getrandom::fill(&mut out).expect("the OS should be able to provide random bytes");
+  out
+}
+
+fn ipmi_err(msg: impl Into<String>) -> IloError { IloError::Ipmi(msg.into()) }
+
+fn unreachable(msg: impl Into<String>) -> IloError { IloError::Unreachable(msg.into()) }
+
+/// Two's complement of the byte sum, what IPMB uses to guard both halves of a message
+pub fn checksum(bytes: &[u8]) -> u8 { bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg() }
+
+/// Wrap a command the way it would travel on IPMB
+pub fn ipmi_message(
+  netfn: u8,
+  cmd: u8,
+  rq_seq: u8,
+  data: &[u8]
+) -> Vec<u8> {
+  let mut msg = vec![BMC_ADDRESS, netfn << 2];
+  msg.push(checksum(&msg));
+  msg.extend([CONSOLE_ADDRESS, rq_seq << 2, cmd]);
+  msg.extend(data);
+  msg.push(checksum(&msg[3..]));
+  msg
+}
+
+/// Completion code and data of the response to this exact request, `None` if it's for something else
+pub fn parse_response(
+  msg: &[u8],
+  netfn: u8,
+  cmd: u8,
+  rq_seq: u8
+) -> Option<(u8, Vec<u8>)> {
+  if msg.len() < 8 || checksum(&msg[..3]) != 0 || checksum(&msg[3..]) != 0 {
+    return None;
+  }
+  if msg[1] >> 2 != netfn | 1 || msg[4] >> 2 != rq_seq || msg[5] != cmd {
+    return None;
+  }
+
+  Some((msg[6], msg[7..msg.len() - 1].to_vec()))
+}
+
+fn packet(
+  payload_type: u8,
+  session_id: u32,
+  sequence: u32,
+  payload: &[u8]
+) -> Vec<u8> {
+  let mut packet = RMCP_HEADER.to_vec();
+  packet.extend([AUTH_FORMAT_RMCP_PLUS, payload_type]);
+  packet.extend(session_id.to_le_bytes());
+  packet.extend(sequence.to_le_bytes());
+  packet.extend((payload.len() as u16).to_le_bytes());
+  packet.extend(payload);
+  packet
+}
+
+/// Payload type and payload of an RMCP+ packet, before any decryption
+fn unpack(packet: &[u8]) -> Option<(u8, &[u8])> {
+  if packet.len() < 16 || packet[..4] != RMCP_HEADER || packet[4] != AUTH_FORMAT_RMCP_PLUS {
+    return None;
+  }
+
+  let len = u16::from_le_bytes([packet[14], packet[15]]) as usize;
+  Some((packet[5], packet.get(16..16 + len)?))
+}
+
+/// Keys derived from the RAKP exchange
+pub struct SessionKeys {
+  /// Session integrity key, the other two are derived from it
+  sik: [u8; 20],
+  /// Signs every packet
+  k1:  [u8; 20],
+  /// First 16 bytes make the AES key
+  k2:  [u8; 16]
+}
+
+impl SessionKeys {
+  pub fn derive(
+    password: &[u8],
+    console_random: &[u8; 16],
+    bmc_random: &[u8; 16],
+    username: &[u8]
+  ) -> Self {
+    // The BMC key (Kg) is rarely set, the user's password stands in for it when it isn't
+    let sik = hmac_sha1(password, &[console_random, bmc_random, &[RAKP_ROLE, username.len() as u8], username]);
+    let k1 = hmac_sha1(&sik, &[&[0x01; 20]]);
+    let k2 = hmac_sha1(&sik, &[&[0x02; 20]]);
+
+    Self {
+      sik,
+      k1,
+      k2: k2[..16].try_into().unwrap()
+    }
+  }
+
+  /// Encrypt and sign an IPMI message for the session
+  pub fn seal(
+    &self,
+    session_id: u32,
+    sequence: u32,
+    msg: &[u8]
+  ) -> Vec<u8> {
+    let iv = random_bytes::<16>();
+    let pad = (16 - (msg.len() + 1) % 16) % 16;
+    let mut plain = msg.to_vec();
+    plain.extend(1..=pad as u8);
+    plain.push(pad as u8);
+
+    let mut payload = iv.to_vec();
+    payload.extend(cbc::Encryptor::<Aes128>::new(&self.k2.into(), &iv.into()).encrypt_padded_vec_mut::<NoPadding>(&plain));
+
+    let mut packet = packet(PAYLOAD_IPMI | PAYLOAD_ENCRYPTED | PAYLOAD_AUTHENTICATED, session_id, sequence, &payload);
+    // Everything from the auth type through the next header byte has to line up on 4 bytes
+    let integrity_pad = (4 - (packet.len() - RMCP_HEADER.len() + 2) % 4) % 4;
+    packet.extend(std::iter::repeat_n(0xFF, integrity_pad));
+    packet.extend([integrity_pad as u8, 0x07]);
+
+    let auth_code = hmac_sha1(&self.k1, &[&packet[RMCP_HEADER.len()..]]);
+    packet.extend(&auth_code[..AUTH_CODE_LEN]);
+    packet
+  }
+
+  /// Check the signature and decrypt the IPMI message inside, `None` for anything that doesn't hold up
+  pub fn open(
+    &self,
+    packet: &[u8]
+  ) -> Option<Vec<u8>> {
+    let (signed, auth_code) = packet.split_at_checked(packet.len().checked_sub(AUTH_CODE_LEN)?)?;
+    if hmac_sha1(&self.k1, &[signed.get(RMCP_HEADER.len()..)?])[..AUTH_CODE_LEN] != *auth_code {
+      return None;
+    }
+
+    let (payload_type, payload) = unpack(packet)?;
+    if payload_type != PAYLOAD_IPMI | PAYLOAD_ENCRYPTED | PAYLOAD_AUTHENTICATED || payload.len() < 32 || !payload.len().is_multiple_of(16) {
+      return None;
+    }
+
+    let (iv, data) = payload.split_at(16);
+    let iv: [u8; 16] = iv.try_into().ok()?;
+    let plain = cbc::Decryptor::<Aes128>::new(&self.k2.into(), &iv.into())
+      .decrypt_padded_vec_mut::<NoPadding>(data)
+      .ok()?;
+
+    let pad = *plain.last()? as usize;
+    plain.get(..plain.len().checked_sub(pad + 1)?).map(<[u8]>::to_vec)
+  }
+}
+
+/// What the BMC says when it turns down a session
+fn rakp_status(code: u8) -> IloError {
+  match code {
+    0x01 => ipmi_err("BMC is out of sessions"),
+    0x09 => IloError::AuthFailed("user isn't allowed administrator access".to_string()),
+    0x0D => IloError::AuthFailed("unknown username".to_string()),
+    0x0F => IloError::AuthFailed("wrong password".to_string()),
+    0x10..=0x13 | 0x15..=0x18 => ipmi_err("BMC doesn't support cipher suite 3"),
+    code => ipmi_err(format!("session refused with status {code:#04X}"))
+  }
+}
+
+/// Accept `host`, `host:port` and bare IPv6 addresses, the port defaults to 623
+fn target(address: &str) -> String {
+  if address.parse::<Ipv6Addr>().is_ok() {
+    return format!("[{address}]:{IPMI_PORT}");
+  }
+  if address.parse::<SocketAddr>().is_ok()
+    || address
+      .rsplit_once(':')
+      .is_some_and(|(h, p)| !h.contains(':') && p.parse::<u16>().is_ok())
+  {
+    return address.to_string();
+  }
+
+  format!("{address}:{IPMI_PORT}")
+}
+
+/// An authenticated, encrypted session with administrator privilege
+pub struct Session {
+  socket:          UdpSocket,
+  keys:            SessionKeys,
+  /// ID the BMC knows the session by, every packet we send carries it
+  bmc_session:     u32,
+  /// ID we picked, every packet the BMC sends carries it
+  console_session: u32,
+  sequence:        u32,
+  rq_seq:          u8
+}
+
+impl Session {
+  pub async fn open(host: &BmcHost) -> Result<Self, IloError> {
+    let addr = lookup_host(target(&host.address))
+      .await
+      .map_err(|e| unreachable(format!("couldn't resolve {}: {e}", host.address)))?
+      .next()
+      .ok_or_else(|| unreachable(format!("couldn't resolve {}", host.address)))?;
+    let local = if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(local).await.map_err(|e| unreachable(e.to_string()))?;
+    socket.connect(addr).await.map_err(|e| unreachable(e.to_string()))?;
+
+    let console_session = u32::from_le_bytes(random_bytes::<4>()) | 1;
+    let username = host.username.as_bytes();
+    let password = host.password.as_bytes();
+    if username.len() > 16 || password.len() > 20 {
+      return Err(IloError::AuthFailed(
+        "IPMI usernames are limited to 16 bytes and passwords to 20".to_string()
+      ));
+    }
+
+    let mut open = vec![0x00, PRIVILEGE_ADMIN, 0x00, 0x00];
+    open.extend(console_session.to_le_bytes());
+    open.extend([0x00, 0x00, 0x00, 0x08, AUTH_RAKP_HMAC_SHA1, 0x00, 0x00, 0x00]);
+    open.extend([0x01, 0x00, 0x00, 0x08, INTEGRITY_HMAC_SHA1_96, 0x00, 0x00, 0x00]);
+    open.extend([0x02, 0x00, 0x00, 0x08, CONFIDENTIALITY_AES_CBC_128, 0x00, 0x00, 0x00]);
+    let res = handshake(&socket, PAYLOAD_OPEN_SESSION_REQUEST, &open, PAYLOAD_OPEN_SESSION_RESPONSE, 36).await?;
+    let bmc_session = u32::from_le_bytes(res[8..12].try_into().unwrap());
+
+    let console_random = random_bytes::<16>();
+    let mut rakp1 = vec![0x00, 0x00, 0x00, 0x00];
+    rakp1.extend(bmc_session.to_le_bytes());
+    rakp1.extend(console_random);
+    rakp1.extend([RAKP_ROLE, 0x00, 0x00, username.len() as u8]);
+    rakp1.extend(username);
+    let res = handshake(&socket, PAYLOAD_RAKP_1, &rakp1, PAYLOAD_RAKP_2, 60).await?;
+
+    let bmc_random: [u8; 16] = res[8..24].try_into().unwrap();
+    let bmc_guid = &res[24..40];
+    let expected = hmac_sha1(
+      password,
+      &[
+        &console_session.to_le_bytes(),
+        &bmc_session.to_le_bytes(),
+        &console_random,
+        &bmc_random,
+        bmc_guid,
+        &[RAKP_ROLE, username.len() as u8],
+        username
+      ]
+    );
+    if res[40..60] != expected {
+      return Err(rakp_status(0x0F));
+    }
+
+    let keys = SessionKeys::derive(password, &console_random, &bmc_random, username);
+    let mut rakp3 = vec![0x00, 0x00, 0x00, 0x00];
+    rakp3.extend(bmc_session.to_le_bytes());
+    rakp3.extend(hmac_sha1(
+      password,
+      &[&bmc_random, &console_session.to_le_bytes(), &[RAKP_ROLE, username.len() as u8], username]
+    ));
+    let res = handshake(&socket, PAYLOAD_RAKP_3, &rakp3, PAYLOAD_RAKP_4, 20).await?;
+
+    let integrity = hmac_sha1(&keys.sik, &[&console_random, &bmc_session.to_le_bytes(), bmc_guid]);
+    if res[8..20] != integrity[..AUTH_CODE_LEN] {
+      return Err(ipmi_err("BMC failed to prove it derived the same session key"));
+    }
+
+    let mut session = Self {
+      socket,
+      keys,
+      bmc_session,
+      console_session,
+      sequence: 0,
+      rq_seq: 0
+    };
+    // Sessions start out at user privilege no matter what was asked for
+    session.command(NETFN_APP, CMD_SET_SESSION_PRIVILEGE, &[PRIVILEGE_ADMIN]).await?;
+
+    Ok(session)
+  }
+
+  /// Send a command and return its completion code with the response data
+  pub async fn request(
+    &mut self,
+    netfn: u8,
+    cmd: u8,
+    data: &[u8]
+  ) -> Result<(u8, Vec<u8>), IloError> {
+    self.rq_seq = (self.rq_seq + 1) & 0x3F;
+    let msg = ipmi_message(netfn, cmd, self.rq_seq, data);
+    let mut buf = [0; 1024];
+
+    for _ in 0..ATTEMPTS {
+      // Zero is reserved for packets outside a session
+      self.sequence = self.sequence.wrapping_add(1).max(1);
+      let packet = self.keys.seal(self.bmc_session, self.sequence, &msg);
+      self.socket.send(&packet).await.map_err(|e| unreachable(e.to_string()))?;
+
+      while let Ok(received) = timeout(RECV_TIMEOUT, self.socket.recv(&mut buf)).await {
+        let len = received.map_err(|e| unreachable(e.to_string()))?;
+        let packet = &buf[..len];
+        // Late answers to earlier attempts and anything not meant for us are dropped
+        if packet.get(6..10) == Some(&self.console_session.to_le_bytes()[..])
+          && let Some(res) = self.keys.open(packet).and_then(|m| parse_response(&m, netfn, cmd, self.rq_seq))
+        {
+          return Ok(res);
+        }
+      }
+    }
+
+    Err(IloError::Timeout(format!("no response to command {netfn:#04X}/{cmd:#04X}")))
+  }
+
+  /// Like [`Session::request`] but anything other than success is an error
+  pub async fn command(
+    &mut self,
+    netfn: u8,
+    cmd: u8,
+    data: &[u8]
+  ) -> Result<Vec<u8>, IloError> {
+    match self.request(netfn, cmd, data).await? {
+      (0x00, data) => Ok(data),
+      (code, _) => Err(ipmi_err(format!(
+        "command {netfn:#04X}/{cmd:#04X} failed with completion code {code:#04X}"
+      )))
+    }
+  }
+
+  /// Log out, the BMC times the session out on its own if this doesn't make it
+  pub async fn close(mut self) {
+    let id = self.bmc_session.to_le_bytes();
+    if let Err(e) = self.command(NETFN_APP, CMD_CLOSE_SESSION, &id).await {
+      eprintln!("iLO[IPMI] Couldn't close the session: {e}");
+    }
+  }
+}
+
+/// One step of the session setup, these go unauthenticated with a session ID of 0
+async fn handshake(
+  socket: &UdpSocket,
+  request_type: u8,
+  payload: &[u8],
+  response_type: u8,
+  min_len: usize
+) -> Result<Vec<u8>, IloError> {
+  let packet = packet(request_type, 0, 0, payload);
+  let mut buf = [0; 1024];
+
+  for _ in 0..ATTEMPTS {
+    socket.send(&packet).await.map_err(|e| unreachable(e.to_string()))?;
+
+    while let Ok(received) = timeout(RECV_TIMEOUT, socket.recv(&mut buf)).await {
+      let len = received.map_err(|e| unreachable(e.to_string()))?;
+      let Some((kind, res)) = unpack(&buf[..len]) else { continue };
+      if kind & 0x3F != response_type || res.len() < 2 {
+        continue;
+      }
+      if res[1] != 0x00 {
+        return Err(rakp_status(res[1]));
+      }
+      if res.len() < min_len {
+        return Err(IloError::UnexpectedSchema("BMC sent a truncated session setup response".to_string()));
+      }
+
+      return Ok(res.to_vec());
+    }
+  }
+
+  Err(unreachable("BMC didn't answer, is IPMI over LAN enabled?"))
+}
@@ -0,0 +1,338 @@
+use {
+  super::{
+    Iml,
+    ImlEntry,
+    RedfishEndpoint,
+    actions::{
+      confirm,
+      log_action,
+      require_developer
+    },
+    backend::{
+      Bmc,
+      backend
+    },
+    discovery::resource_ids,
+    embed_builder,
+    failed,
+    fmt_dt,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_action,
+    ilo_data,
+    ilo_err,
+    paginate::paginate,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    ChoiceParameter,
+    CreateReply,
+    serenity_prelude::CreateAttachment
+  },
+  serde::Serialize
+};
+
+const PAGE_SIZE: usize = 10;
+/// Keeps a page of entries under the embed description limit
+const MAX_MESSAGE_LEN: usize = 300;
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum LogSeverity {
+  Critical,
+  Warning,
+  Informational,
+  Repaired
+}
+
+impl LogSeverity {
+  fn matches(
+    &self,
+    severity: &str
+  ) -> bool {
+    match self {
+      LogSeverity::Critical => severity == "Critical",
+      // iLO 4 calls it Caution
+      LogSeverity::Warning => matches!(severity, "Warning" | "Caution"),
+      LogSeverity::Informational => matches!(severity, "Informational" | "OK"),
+      LogSeverity::Repaired => severity == "Repaired"
+    }
+  }
+}
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum ExportFormat {
+  #[name = "CSV"]
+  Csv,
+  #[name = "JSON"]
+  Json
+}
+
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum LogKind {
+  #[name = "Integrated Management Log (IML)"]
+  Iml,
+  #[name = "Manager event log"]
+  Events
+}
+
+impl LogKind {
+  async fn short_name(
+    &self,
+    host: &BmcHost
+  ) -> String {
+    match self {
+      LogKind::Iml => "IML".to_string(),
+      LogKind::Events => event_log_name(host).await
+    }
+  }
+
+  fn clear_endpoint(&self) -> RedfishEndpoint {
+    match self {
+      LogKind::Iml => RedfishEndpoint::ClearSystemLog,
+      LogKind::Events => RedfishEndpoint::ClearEventLog
+    }
+  }
+}
+
+/// ID of the manager log discovery settled on, like iLO's `IEL` or iDRAC's `Lclog`
+async fn event_log_name(host: &BmcHost) -> String {
+  resource_ids(host)
+    .await
+    .ok()
+    .and_then(|ids| ids.event_log)
+    .unwrap_or_else(|| "Event log".to_string())
+}
+
+/// `LogService.ClearLog` takes no parameters
+#[derive(Serialize)]
+struct ClearLogRequest {}
+
+pub struct LogFilter {
+  pub severity: Option<LogSeverity>,
+  pub since:    Option<String>,
+  pub until:    Option<String>
+}
+
+impl LogFilter {
+  pub fn matches(
+    &self,
+    entry: &ImlEntry
+  ) -> bool {
+    // ISO 8601 dates compare just fine as strings
+    let date = entry.created.get(..10).unwrap_or_default();
+
+    self.severity.is_none_or(|s| s.matches(&entry.severity))
+      && self.since.as_deref().is_none_or(|since| date >= since)
+      && self.until.as_deref().is_none_or(|until| date <= until)
+  }
+}
+
+/// Check for `YYYY-MM-DD`
+fn valid_date(input: &str) -> bool {
+  let bytes = input.as_bytes();
+  bytes.len() == 10
+    && bytes
+      .iter()
+      .enumerate()
+      .all(|(i, c)| if i == 4 || i == 7 { *c == b'-' } else { c.is_ascii_digit() })
+}
+
+pub fn fmt_entry(entry: &ImlEntry) -> String {
+  let dt = fmt_dt(&entry.created).unwrap_or_else(|| "Unknown".to_string());
+  let message = match entry.message.char_indices().nth(MAX_MESSAGE_LEN) {
+    Some((i, _)) => format!("{}…", &entry.message[..i]),
+    None => entry.message.clone()
+  };
+
+  format!("**[{}:{dt}]:** {message}", entry.severity)
+}
+
+fn csv_field(value: &str) -> String { format!("\"{}\"", value.replace('"', "\"\"")) }
+
+fn export_bytes(
+  entries: &[&ImlEntry],
+  format: ExportFormat
+) -> KonResult<Vec<u8>> {
+  Ok(match format {
+    ExportFormat::Csv => {
+      let mut csv = String::from("created,severity,message\n");
+      for entry in entries {
+        csv.push_str(&format!(
+          "{},{},{}\n",
+          csv_field(&entry.created),
+          csv_field(&entry.severity),
+          csv_field(&entry.message)
+        ));
+      }
+      csv.into_bytes()
+    },
+    ExportFormat::Json => serde_json::to_vec_pretty(entries)?
+  })
+}
+
+/// Reply with the filtered log, either as pages or as a file
+pub async fn render_log(
+  ctx: PoiseCtx<'_>,
+  host: &BmcHost,
+  title: &str,
+  log: &Iml,
+  filter: &LogFilter,
+  export: Option<ExportFormat>
+) -> KonResult<()> {
+  let entries: Vec<&ImlEntry> = log.entries().iter().rev().filter(|e| filter.matches(e)).collect();
+
+  if entries.is_empty() {
+    ctx
+      .send(CreateReply::new().embed(embed_builder(host, title, Some("No entries matched the filters.".to_string()), None)))
+      .await?;
+    return Ok(());
+  }
+
+  if let Some(format) = export {
+    let extension = match format {
+      ExportFormat::Csv => "csv",
+      ExportFormat::Json => "json"
+    };
+    let filename = format!("{}-{}.{extension}", host.id, title.to_lowercase());
+
+    ctx
+      .send(
+        CreateReply::new()
+          .content(format!("Exported **{}** {title} entries from `{}`", entries.len(), host.name))
+          .attachment(CreateAttachment::bytes(export_bytes(&entries, format)?, filename))
+      )
+      .await?;
+    return Ok(());
+  }
+
+  let pages = entries
+    .chunks(PAGE_SIZE)
+    .map(|chunk| {
+      let lines: Vec<String> = chunk.iter().map(|e| fmt_entry(e)).collect();
+      embed_builder(host, title, Some(lines.join("\n")), None)
+    })
+    .collect();
+
+  paginate(ctx, pages, &format!("{} entries", entries.len())).await
+}
+
+/// Reply early when either date is malformed, returns whether the dates are usable
+async fn check_dates(
+  ctx: PoiseCtx<'_>,
+  since: &Option<String>,
+  until: &Option<String>
+) -> KonResult<bool> {
+  if [since, until].into_iter().flatten().all(|d| valid_date(d)) {
+    return Ok(true);
+  }
+
+  ctx
+    .send(CreateReply::new().content("Dates need to be in `YYYY-MM-DD` format!").ephemeral(true))
+    .await?;
+  Ok(false)
+}
+
+/// Browse the server's IML data, or its SEL over IPMI
+#[poise::command(slash_command)]
+pub async fn logs(
+  ctx: PoiseCtx<'_>,
+  #[description = "Only show entries with this severity"] severity: Option<LogSeverity>,
+  #[description = "Oldest date to show (YYYY-MM-DD)"] since: Option<String>,
+  #[description = "Newest date to show (YYYY-MM-DD)"] until: Option<String>,
+  #[description = "Download the filtered log instead"] export: Option<ExportFormat>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !check_dates(ctx, &since, &until).await? {
+    return Ok(());
+  }
+
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let bmc = backend(&host);
+  match bmc.event_log().await {
+    Ok(data) => render_log(ctx, &host, bmc.log_name(), &data, &LogFilter { severity, since, until }, export).await?,
+    Err(e) => ilo_err(&ctx, &host, e).await
+  }
+
+  Ok(())
+}
+
+/// Browse the BMC's own event log (logins, firmware changes, resets)
+#[poise::command(slash_command)]
+pub async fn events(
+  ctx: PoiseCtx<'_>,
+  #[description = "Only show entries with this severity"] severity: Option<LogSeverity>,
+  #[description = "Oldest date to show (YYYY-MM-DD)"] since: Option<String>,
+  #[description = "Newest date to show (YYYY-MM-DD)"] until: Option<String>,
+  #[description = "Download the filtered log instead"] export: Option<ExportFormat>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !check_dates(ctx, &since, &until).await? {
+    return Ok(());
+  }
+
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  match ilo_data::<Iml>(&host, RedfishEndpoint::EventLog).await {
+    Ok(data) => {
+      let title = event_log_name(&host).await;
+      render_log(ctx, &host, &title, &data, &LogFilter { severity, since, until }, export).await?
+    },
+    Err(e) => ilo_err(&ctx, &host, e).await
+  }
+
+  Ok(())
+}
+
+/// Wipe one of the server's logs
+#[poise::command(slash_command, rename = "clear-log")]
+pub async fn clear_log(
+  ctx: PoiseCtx<'_>,
+  #[description = "Which log to clear"] log: LogKind,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  if !confirm(
+    ctx,
+    format!(
+      "Are you sure you want to clear the **{}** on `{}`? This can't be undone.",
+      log.name(),
+      host.name
+    )
+  )
+  .await?
+  {
+    return Ok(());
+  }
+
+  let outcome = match ilo_action(&host, log.clear_endpoint(), &ClearLogRequest {}).await {
+    Ok(()) => "Cleared".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  log_action(ctx, &host, &format!("ClearLog ({})", log.short_name(&host).await), &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**{}** on `{}`: {outcome}", log.name(), host.name)))
+    .await?;
+
+  Ok(())
+}
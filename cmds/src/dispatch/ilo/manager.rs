@@ -0,0 +1,284 @@
+use {
+  super::{
+    IloError,
+    RedfishEndpoint,
+    Status,
+    actions::{
+      confirm,
+      log_action,
+      require_developer
+    },
+    discovery::{
+      expand,
+      resource_ids
+    },
+    embed_builder,
+    failed,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_action,
+    ilo_data,
+    ilo_err,
+    lenient_int,
+    pack_fields,
+    resolve_host
+  },
+  asahi::utils::format_duration,
+  kon_libs::{
+    KonResult,
+    PoiseCtx,
+    is_developer
+  },
+  poise::{
+    CreateReply,
+    serenity_prelude::Timestamp
+  },
+  serde::{
+    Deserialize,
+    Serialize
+  }
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Manager {
+  date_time:        Option<String>,
+  firmware_version: Option<String>,
+  /// Newer firmware only, there's no other way to tell the uptime
+  last_reset_time:  Option<String>,
+  model:            Option<String>,
+  oem:              Option<ManagerOem>,
+  #[serde(default)]
+  status:           Status
+}
+
+#[derive(Deserialize)]
+struct ManagerOem {
+  #[serde(rename = "Hp")]
+  hp:  Option<HpeManager>,
+  #[serde(rename = "Hpe")]
+  hpe: Option<HpeManager>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HpeManager {
+  license:    Option<License>,
+  #[serde(rename = "iLOSelfTestResults", default)]
+  self_tests: Vec<SelfTest>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct License {
+  license_string: Option<String>,
+  license_type:   Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SelfTest {
+  notes:          Option<String>,
+  self_test_name: Option<String>,
+  status:         Option<String>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EthernetInterface {
+  #[serde(rename = "FQDN")]
+  fqdn:              Option<String>,
+  host_name:         Option<String>,
+  interface_enabled: Option<bool>,
+  #[serde(rename = "IPv4Addresses", default)]
+  ipv4_addresses:    Vec<Ipv4Address>,
+  #[serde(rename = "MACAddress")]
+  mac_address:       Option<String>,
+  name:              Option<String>,
+  #[serde(default, deserialize_with = "lenient_int")]
+  speed_mbps:        Option<i32>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Ipv4Address {
+  address:        Option<String>,
+  address_origin: Option<String>,
+  gateway:        Option<String>,
+  subnet_mask:    Option<String>
+}
+
+/// `ResetType` is optional for `Manager.Reset` and iLO 4 rejects it
+#[derive(Serialize)]
+struct ManagerResetRequest {}
+
+impl Manager {
+  fn hpe(&self) -> Option<&HpeManager> { self.oem.as_ref().and_then(|o| o.hpe.as_ref().or(o.hp.as_ref())) }
+
+  /// Measured against the BMC's own clock so a skewed one doesn't throw it off
+  fn uptime(&self) -> Option<u64> {
+    let now = Timestamp::parse(self.date_time.as_deref()?).ok()?;
+    let reset = Timestamp::parse(self.last_reset_time.as_deref()?).ok()?;
+    u64::try_from(now.unix_timestamp() - reset.unix_timestamp()).ok()
+  }
+}
+
+/// Description and the self-test fields for `/ilo manager`
+pub fn manager_summary(manager: &Manager) -> (String, Vec<(String, String, bool)>) {
+  let license = manager
+    .hpe()
+    .and_then(|h| h.license.as_ref())
+    .and_then(|l| l.license_string.as_deref().or(l.license_type.as_deref()))
+    .unwrap_or("Unknown");
+
+  let description = [
+    format!("**Model:** `{}`", manager.model.as_deref().unwrap_or("Unknown")),
+    format!("**Firmware:** `{}`", manager.firmware_version.as_deref().unwrap_or("Unknown")),
+    format!("**License:** `{license}`"),
+    format!("**Health:** `{}`", manager.status.summary()),
+    format!(
+      "**Uptime:** `{}`",
+      manager.uptime().map(format_duration).unwrap_or_else(|| "Unknown".to_string())
+    )
+  ]
+  .join("\n");
+
+  let self_tests: Vec<String> = manager
+    .hpe()
+    .map(|h| h.self_tests.as_slice())
+    .unwrap_or_default()
+    .iter()
+    .map(|t| {
+      let mut line = format!(
+        "**{}:** `{}`",
+        t.self_test_name.as_deref().unwrap_or("Unknown"),
+        t.status.as_deref().unwrap_or("Unknown")
+      );
+      if let Some(notes) = t.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        line.push_str(&format!(" - {notes}"));
+      }
+      line
+    })
+    .collect();
+
+  (description, pack_fields("Self-test", &self_tests))
+}
+
+/// Addresses, hostnames and MACs of the BMC's interfaces, only shown to developers
+pub fn network_lines(interfaces: &[EthernetInterface]) -> Vec<String> {
+  interfaces
+    .iter()
+    .filter(|i| i.interface_enabled != Some(false))
+    .map(|i| {
+      let address = i
+        .ipv4_addresses
+        .iter()
+        .find(|a| a.address.as_deref().is_some_and(|a| a != "0.0.0.0"))
+        .map(|a| {
+          format!(
+            "`{}/{}` via `{}` ({})",
+            a.address.as_deref().unwrap_or_default(),
+            a.subnet_mask.as_deref().unwrap_or("?"),
+            a.gateway.as_deref().unwrap_or("?"),
+            a.address_origin.as_deref().unwrap_or("Unknown")
+          )
+        })
+        .unwrap_or_else(|| "No IPv4 address".to_string());
+
+      format!(
+        "**{}:** {address} • `{}` • `{}` • {}",
+        i.name.as_deref().unwrap_or("Interface"),
+        i.fqdn.as_deref().or(i.host_name.as_deref()).unwrap_or("No hostname"),
+        i.mac_address.as_deref().unwrap_or("Unknown MAC"),
+        i.speed_mbps.map(|s| format!("{s} Mbps")).unwrap_or_else(|| "Unknown speed".to_string())
+      )
+    })
+    .collect()
+}
+
+pub async fn manager_data(host: &BmcHost) -> Result<(Manager, Vec<EthernetInterface>), IloError> {
+  let ids = resource_ids(host).await?;
+  let (manager, interfaces) = tokio::join!(
+    ilo_data::<Manager>(host, RedfishEndpoint::Manager),
+    expand::<EthernetInterface>(host, &format!("Managers/{}/EthernetInterfaces", ids.manager))
+  );
+
+  Ok((manager?, interfaces.unwrap_or_default()))
+}
+
+/// Retrieve the status of the server's management controller
+#[poise::command(slash_command)]
+pub async fn manager(
+  ctx: PoiseCtx<'_>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  match manager_data(&host).await {
+    Ok((manager, interfaces)) => {
+      let (description, fields) = manager_summary(&manager);
+      ctx
+        .send(CreateReply::new().embed(embed_builder(&host, "Manager", Some(description), Some(fields))))
+        .await?;
+
+      // Anyone can run this, so where the BMC sits on the network stays between the developers
+      let network = network_lines(&interfaces);
+      if is_developer(ctx) && !network.is_empty() {
+        ctx
+          .send(
+            CreateReply::new()
+              .embed(embed_builder(&host, "Manager network", None, Some(pack_fields("Network", &network))))
+              .ephemeral(true)
+          )
+          .await?;
+      }
+    },
+    Err(e) => ilo_err(&ctx, &host, e).await
+  }
+
+  Ok(())
+}
+
+/// Restart the management controller, the server itself keeps running
+#[poise::command(slash_command, rename = "manager-reset")]
+pub async fn manager_reset(
+  ctx: PoiseCtx<'_>,
+  #[description = "Server whose BMC to restart"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  if !confirm(
+    ctx,
+    format!(
+      "Are you sure you want to **restart the BMC** of `{}`? It'll be unreachable for a minute or two.",
+      host.name
+    )
+  )
+  .await?
+  {
+    return Ok(());
+  }
+
+  let outcome = match ilo_action(&host, RedfishEndpoint::ManagerReset, &ManagerResetRequest {}).await {
+    Ok(()) => "Sent".to_string(),
+    Err(e) => failed(ctx, &host, &e)
+  };
+
+  log_action(ctx, &host, "Manager.Reset", &outcome).await;
+  ctx
+    .send(CreateReply::new().content(format!("**BMC restart** on `{}`: {outcome}", host.name)))
+    .await?;
+
+  Ok(())
+}
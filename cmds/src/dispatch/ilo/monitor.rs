@@ -0,0 +1,251 @@
+use {
+  super::{
+    Chassis,
+    IloError,
+    Power,
+    RedfishEndpoint,
+    backend::{
+      Bmc,
+      backend
+    },
+    embed_builder,
+    history::record_samples,
+    hosts::{
+      BmcHost,
+      all_hosts
+    },
+    ilo_data,
+    post_kon_logs
+  },
+  kon_libs::{
+    BINARY_PROPERTIES,
+    BmcProtocol
+  },
+  poise::serenity_prelude::Http,
+  std::{
+    collections::HashMap,
+    sync::{
+      Arc,
+      atomic::{
+        AtomicBool,
+        Ordering
+      }
+    }
+  },
+  tokio::time::{
+    Duration,
+    MissedTickBehavior,
+    interval
+  }
+};
+
+/// How many degrees a sensor has to drop below the threshold it tripped
+/// before it's considered recovered, stops the alerts from flapping
+const TEMP_HYSTERESIS: i32 = 3;
+
+/// Consecutive polls a fan has to report the same health before it's announced
+const FAN_DEBOUNCE: u8 = 2;
+
+const ALERT_COLOR: u32 = 0xE74C3C;
+const RECOVER_COLOR: u32 = 0x57F287;
+
+static MONITOR_STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+  Normal,
+  Critical,
+  Fatal
+}
+
+impl Level {
+  fn as_str(&self) -> &'static str {
+    match self {
+      Level::Normal => "Normal",
+      Level::Critical => "Critical",
+      Level::Fatal => "Fatal"
+    }
+  }
+}
+
+#[derive(Default)]
+struct MonitorState {
+  temps:        HashMap<String, Level>,
+  fans:         HashMap<String, (Level, Level, u8)>,
+  /// Pin mismatches are only announced once until the host is reachable again
+  pin_reported: bool
+}
+
+/// Work out the sensor's level, the thresholds only clear
+/// once the reading has dropped [`TEMP_HYSTERESIS`] below them
+pub fn temp_level(
+  reading: i32,
+  critical: i32,
+  fatal: i32,
+  prev: Level
+) -> Level {
+  // Missing thresholds come through as 0, a previous level holds before the plain thresholds are looked at
+  // so a reading hovering just under fatal doesn't flip between Fatal and Critical
+  if prev == Level::Fatal && fatal > 0 && reading > fatal - TEMP_HYSTERESIS {
+    return Level::Fatal;
+  }
+  if fatal > 0 && reading >= fatal {
+    return Level::Fatal;
+  }
+  if critical > 0 && reading >= critical {
+    return Level::Critical;
+  }
+
+  match prev {
+    Level::Fatal | Level::Critical if critical > 0 && reading > critical - TEMP_HYSTERESIS => Level::Critical,
+    _ => Level::Normal
+  }
+}
+
+fn fan_level(health: Option<&str>) -> Level {
+  match health {
+    Some("Critical") => Level::Fatal,
+    Some("Warning") => Level::Critical,
+    _ => Level::Normal
+  }
+}
+
+impl MonitorState {
+  /// Compare the readings against the previous poll and return the transitions worth reporting
+  fn evaluate(
+    &mut self,
+    data: &Chassis
+  ) -> Vec<(Level, String)> {
+    let mut changes = Vec::new();
+
+    for temp in &data.temperatures {
+      let reading = temp.reading_celsius.unwrap_or(0);
+      if reading == 0 {
+        continue;
+      }
+
+      let critical = temp.upper_threshold_critical.unwrap_or(0);
+      let fatal = temp.upper_threshold_fatal.unwrap_or(0);
+      let prev = self.temps.get(&temp.name).copied().unwrap_or(Level::Normal);
+      let level = temp_level(reading, critical, fatal, prev);
+
+      if level != prev {
+        let threshold = match level {
+          Level::Fatal => fatal,
+          _ => critical
+        };
+        changes.push((
+          level,
+          format!(
+            "**{}:** `{reading}°C` (threshold `{threshold}°C`) - {} → {}",
+            temp.name,
+            prev.as_str(),
+            level.as_str()
+          )
+        ));
+        self.temps.insert(temp.name.clone(), level);
+      }
+    }
+
+    for fan in &data.fans {
+      let observed = fan_level(fan.status.health.as_deref());
+      let (reported, pending, streak) = self.fans.entry(fan.label().to_string()).or_insert((Level::Normal, Level::Normal, 0));
+
+      if observed == *pending {
+        *streak = streak.saturating_add(1);
+      } else {
+        *pending = observed;
+        *streak = 1;
+      }
+
+      if *pending != *reported && *streak >= FAN_DEBOUNCE {
+        changes.push((
+          *pending,
+          format!(
+            "**{}:** health `{}` - {} → {}",
+            fan.label(),
+            fan.status.health.as_deref().unwrap_or("Unknown"),
+            reported.as_str(),
+            pending.as_str()
+          )
+        ));
+        *reported = *pending;
+      }
+    }
+
+    changes
+  }
+}
+
+async fn post_alert(
+  http: &Http,
+  host: &BmcHost,
+  changes: Vec<(Level, String)>
+) {
+  let worst = changes.iter().map(|(l, _)| *l).fold(Level::Normal, |a, b| if b > a { b } else { a });
+  let (title, color) = if worst == Level::Normal {
+    ("Sensors recovered", RECOVER_COLOR)
+  } else {
+    ("Sensor alert", ALERT_COLOR)
+  };
+
+  let description = changes.into_iter().map(|(_, line)| line).collect::<Vec<_>>().join("\n");
+  post_kon_logs(http, embed_builder(host, title, Some(description), None).color(color)).await;
+}
+
+/// Start polling the iLO in the background, only the first call spawns the task
+pub fn spawn_ilo_monitor(http: Arc<Http>) {
+  if MONITOR_STARTED.swap(true, Ordering::SeqCst) {
+    return;
+  }
+
+  tokio::spawn(async move {
+    let mut states: HashMap<&'static str, MonitorState> = HashMap::new();
+    let mut ticker = interval(Duration::from_secs(BINARY_PROPERTIES.ilo_poll_secs));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    println!("iLO[Monitor] Polling every {}s", BINARY_PROPERTIES.ilo_poll_secs);
+
+    loop {
+      ticker.tick().await;
+
+      for host in all_hosts().await {
+        let state = states.entry(host.id).or_default();
+
+        let thermal = match backend(&host).sensors().await {
+          Ok(data) => {
+            state.pin_reported = false;
+            let changes = state.evaluate(&data);
+            if !changes.is_empty() {
+              post_alert(&http, &host, changes).await;
+            }
+            Some(data)
+          },
+          Err(e @ IloError::PinMismatch { .. }) => {
+            eprintln!("iLO[Monitor] {}: {e}", host.name);
+            if !state.pin_reported {
+              state.pin_reported = true;
+              post_kon_logs(
+                &http,
+                embed_builder(&host, "Certificate pin mismatch", Some(e.to_string()), None).color(ALERT_COLOR)
+              )
+              .await;
+            }
+            None
+          },
+          Err(e) => {
+            eprintln!("iLO[Monitor] Failed to poll the thermal data from {}: {e}", host.name);
+            None
+          }
+        };
+
+        // No point asking for power readings if the host didn't answer the thermal poll, IPMI has none to give
+        let power = match thermal {
+          Some(_) if host.protocol == BmcProtocol::Redfish => ilo_data::<Power>(&host, RedfishEndpoint::Power).await.ok(),
+          _ => None
+        };
+        record_samples(&host, thermal.as_ref(), power.as_ref()).await;
+      }
+    }
+  });
+}
@@ -0,0 +1,116 @@
+use {
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    CreateReply,
+    serenity_prelude::{
+      ComponentInteractionCollector,
+      CreateActionRow,
+      CreateButton,
+      CreateEmbed,
+      CreateEmbedFooter,
+      CreateInteractionResponse,
+      CreateInteractionResponseMessage
+    }
+  },
+  tokio::time::Duration
+};
+
+/// How long the buttons stay usable after the last press
+const PAGE_TIMEOUT: Duration = Duration::from_secs(300);
+
+fn page_buttons(
+  prev_id: &str,
+  next_id: &str,
+  page: usize,
+  total: usize
+) -> CreateActionRow<'static> {
+  CreateActionRow::Buttons(
+    vec![
+      CreateButton::new(prev_id.to_string()).emoji('◀').disabled(page == 0),
+      CreateButton::new(next_id.to_string()).emoji('▶').disabled(page + 1 >= total),
+    ]
+    .into()
+  )
+}
+
+fn with_footer(
+  embed: &CreateEmbed<'static>,
+  page: usize,
+  total: usize,
+  footer: &str
+) -> CreateEmbed<'static> {
+  embed
+    .clone()
+    .footer(CreateEmbedFooter::new(format!("Page {}/{total} • {footer}", page + 1)))
+}
+
+/// Send the embeds as pages that the invoker can flip through with buttons
+pub async fn paginate(
+  ctx: PoiseCtx<'_>,
+  pages: Vec<CreateEmbed<'static>>,
+  footer: &str
+) -> KonResult<()> {
+  let Some(first) = pages.first() else { return Ok(()) };
+  let total = pages.len();
+
+  if total == 1 {
+    ctx.send(CreateReply::new().embed(with_footer(first, 0, total, footer))).await?;
+    return Ok(());
+  }
+
+  let ctx_id = ctx.id().to_string();
+  let prev_id = format!("{ctx_id}-prev");
+  let next_id = format!("{ctx_id}-next");
+
+  let reply = ctx
+    .send(
+      CreateReply::new()
+        .embed(with_footer(first, 0, total, footer))
+        .components(vec![page_buttons(&prev_id, &next_id, 0, total)])
+    )
+    .await?;
+
+  let mut page = 0;
+  while let Some(press) = ComponentInteractionCollector::new(ctx.serenity_context().shard.clone())
+    .author_id(ctx.author().id)
+    .filter({
+      let ctx_id = ctx_id.clone();
+      move |i| i.data.custom_id.starts_with(&ctx_id)
+    })
+    .timeout(PAGE_TIMEOUT)
+    .await
+  {
+    if press.data.custom_id.as_str() == next_id {
+      page = (page + 1).min(total - 1);
+    } else if press.data.custom_id.as_str() == prev_id {
+      page = page.saturating_sub(1);
+    } else {
+      continue;
+    }
+
+    press
+      .create_response(
+        ctx.http(),
+        CreateInteractionResponse::UpdateMessage(
+          CreateInteractionResponseMessage::new()
+            .embed(with_footer(&pages[page], page, total, footer))
+            .components(vec![page_buttons(&prev_id, &next_id, page, total)])
+        )
+      )
+      .await?;
+  }
+
+  reply
+    .edit(
+      ctx,
+      CreateReply::new()
+        .embed(with_footer(&pages[page], page, total, footer))
+        .components(vec![])
+    )
+    .await?;
+
+  Ok(())
+}
@@ -0,0 +1,190 @@
+use {
+  super::{
+    Power,
+    RedfishEndpoint,
+    actions::{
+      confirm,
+      log_action,
+      require_developer
+    },
+    embed_builder,
+    failed,
+    fmt_watts,
+    hosts::autocomplete_host,
+    ilo_data,
+    ilo_err,
+    ilo_patch,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  poise::{
+    ChoiceParameter,
+    CreateReply
+  },
+  serde::Serialize
+};
+
+/// What the BMC does when the cap can't be held
+#[derive(ChoiceParameter, Clone, Copy)]
+pub enum LimitException {
+  #[name = "Do nothing"]
+  NoAction,
+  #[name = "Log an event"]
+  LogEventOnly,
+  #[name = "Power off"]
+  HardPowerOff
+}
+
+impl LimitException {
+  fn as_redfish(&self) -> &'static str {
+    match self {
+      LimitException::NoAction => "NoAction",
+      LimitException::LogEventOnly => "LogEventOnly",
+      LimitException::HardPowerOff => "HardPowerOff"
+    }
+  }
+}
+
+/// Only the first `PowerControl` is touched, that's the one for the whole server
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PowerCapRequest {
+  power_control: [PowerControlPatch; 1]
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PowerControlPatch {
+  power_limit: PowerLimitPatch
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PowerLimitPatch {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  limit_exception: Option<&'static str>,
+  /// Sending `null` lifts the cap
+  limit_in_watts:  Option<i32>
+}
+
+impl PowerCapRequest {
+  pub fn new(
+    watts: Option<i32>,
+    exception: Option<LimitException>
+  ) -> Self {
+    Self {
+      power_control: [PowerControlPatch {
+        power_limit: PowerLimitPatch {
+          limit_exception: exception.map(|e| e.as_redfish()),
+          limit_in_watts:  watts
+        }
+      }]
+    }
+  }
+}
+
+/// The cap next to what the server is rated for and what it's actually been drawing
+pub fn powercap_summary(data: &Power) -> String {
+  let limit = data.control().and_then(|c| c.power_limit.as_ref());
+  let cap = limit.and_then(|l| l.limit_in_watts).filter(|w| *w > 0);
+  let metrics = data.metrics();
+  let max = metrics.and_then(|m| m.max_consumed_watts);
+
+  let mut lines = vec![
+    match cap {
+      Some(watts) => format!(
+        "**Power Cap:** `{watts}w` (`{}` when exceeded)",
+        limit.and_then(|l| l.limit_exception.as_deref()).unwrap_or("Unknown")
+      ),
+      None => "**Power Cap:** `None`".to_string()
+    },
+    format!("**Power Capacity:** `{}`", fmt_watts(data.capacity())),
+    format!("**Average Power:** `{}`", fmt_watts(metrics.and_then(|m| m.average_consumed_watts))),
+    format!("**Max Consumed:** `{}`", fmt_watts(max)),
+  ];
+
+  if let (Some(cap), Some(max)) = (cap, max) {
+    lines.push(match cap - max {
+      headroom if headroom >= 0 => format!("**Headroom:** `{headroom}w` above the max draw"),
+      over => format!("**Headroom:** None, the max draw is over the cap by `{}w`", -over)
+    });
+  }
+
+  lines.join("\n")
+}
+
+/// Show or change the server's power cap
+#[poise::command(slash_command)]
+pub async fn powercap(
+  ctx: PoiseCtx<'_>,
+  #[description = "New cap in watts"]
+  #[min = 1]
+  watts: Option<i32>,
+  #[description = "What to do when the cap can't be held"] exception: Option<LimitException>,
+  #[description = "Lift the cap entirely"] remove: Option<bool>,
+  #[description = "Server to control"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  if !require_developer(ctx).await? {
+    return Ok(());
+  }
+
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+  let remove = remove.unwrap_or(false);
+
+  if watts.is_some() || exception.is_some() || remove {
+    let (prompt, request) = match (watts, remove) {
+      (Some(_), true) => {
+        ctx.reply("Pick either a new cap or removing it, not both!").await?;
+        return Ok(());
+      },
+      (_, true) => (
+        format!("Are you sure you want to **remove the power cap** of `{}`?", host.name),
+        PowerCapRequest::new(None, None)
+      ),
+      (Some(watts), false) => (
+        format!("Are you sure you want to **cap** `{}` at **{watts}w**?", host.name),
+        PowerCapRequest::new(Some(watts), exception)
+      ),
+      (None, false) => {
+        ctx.reply("Changing the exception needs a cap to go with it!").await?;
+        return Ok(());
+      }
+    };
+
+    if !confirm(ctx, prompt).await? {
+      return Ok(());
+    }
+
+    let action = match watts {
+      Some(watts) => format!("PowerLimit={watts}w"),
+      None => "PowerLimit=null".to_string()
+    };
+    let outcome = match ilo_patch(&host, RedfishEndpoint::Power, &request).await {
+      Ok(()) => "Applied".to_string(),
+      Err(e) => failed(ctx, &host, &e)
+    };
+
+    log_action(ctx, &host, &action, &outcome).await;
+    ctx
+      .send(CreateReply::new().content(format!("**Power cap** on `{}`: {outcome}", host.name)))
+      .await?;
+    return Ok(());
+  }
+
+  ctx.defer().await?;
+  match ilo_data::<Power>(&host, RedfishEndpoint::Power).await {
+    Ok(data) => {
+      ctx
+        .send(CreateReply::new().embed(embed_builder(&host, "Power Cap", Some(powercap_summary(&data)), None)))
+        .await?;
+    },
+    Err(e) => ilo_err(&ctx, &host, e).await
+  }
+
+  Ok(())
+}
@@ -0,0 +1,120 @@
+use {
+  super::{
+    IloError,
+    hosts::BmcHost,
+    tls::client_for
+  },
+  dashmap::DashMap,
+  reqwest::{
+    Client,
+    StatusCode
+  },
+  serde::Serialize,
+  std::sync::{
+    Arc,
+    LazyLock
+  },
+  tokio::sync::Mutex
+};
+
+pub const AUTH_TOKEN_HEADER: &str = "X-Auth-Token";
+
+static SESSIONS: LazyLock<DashMap<&'static str, Arc<Mutex<Option<SessionState>>>>> = LazyLock::new(DashMap::new);
+
+enum SessionState {
+  Active(Session),
+  /// BMC has no session service or didn't hand out a token, stick to basic auth
+  Unsupported
+}
+
+struct Session {
+  token:  String,
+  /// Absolute URL of the session resource, needed to log out
+  url:    String,
+  client: Client
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct LoginRequest<'a> {
+  user_name: &'a str,
+  password:  &'a str
+}
+
+async fn login(host: &BmcHost) -> Result<SessionState, IloError> {
+  let client = client_for(host).await?;
+  let res = client
+    .post(format!("https://{}/redfish/v1/SessionService/Sessions", host.address))
+    .json(&LoginRequest {
+      user_name: &host.username,
+      password:  &host.password
+    })
+    .send()
+    .await?;
+
+  if matches!(res.status(), StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED) {
+    return Ok(SessionState::Unsupported);
+  }
+
+  let res = res.error_for_status()?;
+  let headers = res.headers();
+  let token = headers.get(AUTH_TOKEN_HEADER).and_then(|v| v.to_str().ok());
+  let location = headers.get(reqwest::header::LOCATION).and_then(|v| v.to_str().ok());
+
+  Ok(match (token, location) {
+    (Some(token), Some(location)) => {
+      let url = if location.starts_with("http") {
+        location.to_string()
+      } else {
+        format!("https://{}{location}", host.address)
+      };
+
+      SessionState::Active(Session {
+        token: token.to_string(),
+        url,
+        client
+      })
+    },
+    _ => SessionState::Unsupported
+  })
+}
+
+/// Session token for the host, logs in if there isn't one yet or if `stale` was rejected.
+/// Returns `None` when the host only does basic auth.
+pub async fn session_token(
+  host: &BmcHost,
+  stale: Option<&str>
+) -> Result<Option<String>, IloError> {
+  let slot = SESSIONS.entry(host.id).or_default().clone();
+  let mut state = slot.lock().await;
+
+  let needs_login = match (&*state, stale) {
+    (None, _) => true,
+    (Some(SessionState::Active(s)), Some(stale)) => s.token == stale,
+    _ => false
+  };
+
+  if needs_login {
+    *state = Some(login(host).await?);
+  }
+
+  Ok(match &*state {
+    Some(SessionState::Active(s)) => Some(s.token.clone()),
+    _ => None
+  })
+}
+
+/// Log out of every open Redfish session so they don't linger on the BMCs
+pub async fn close_ilo_sessions() {
+  let slots: Vec<_> = SESSIONS.iter().map(|e| e.value().clone()).collect();
+
+  for slot in slots {
+    if let Some(SessionState::Active(session)) = slot.lock().await.take() {
+      match session.client.delete(&session.url).header(AUTH_TOKEN_HEADER, &session.token).send().await {
+        Ok(res) if res.status().is_success() => (),
+        Ok(res) => eprintln!("iLO[Session] Logout returned HTTP {}", res.status().as_u16()),
+        Err(e) => eprintln!("iLO[Session] Couldn't log out: {e}")
+      }
+    }
+  }
+}
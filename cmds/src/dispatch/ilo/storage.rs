@@ -0,0 +1,437 @@
+use {
+  super::{
+    IloError,
+    Status,
+    discovery::{
+      Link,
+      expand_each,
+      fetch_links,
+      redfish_path,
+      resource_ids
+    },
+    embed_builder,
+    hosts::{
+      BmcHost,
+      autocomplete_host
+    },
+    ilo_err,
+    pack_fields,
+    pack_pages,
+    paginate::paginate,
+    resolve_host
+  },
+  kon_libs::{
+    KonResult,
+    PoiseCtx
+  },
+  serde::Deserialize
+};
+
+const FAILING_COLOR: u32 = 0xE74C3C;
+
+/// DMTF's storage model, what everyone but older HPE firmware uses
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Storage {
+  #[serde(default)]
+  storage_controllers: Vec<StorageController>,
+  #[serde(default)]
+  drives:              Vec<Link>,
+  volumes:             Option<Link>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StorageController {
+  model:  Option<String>,
+  name:   Option<String>,
+  #[serde(default)]
+  status: Status
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Drive {
+  capacity_bytes: Option<f64>,
+  failure_predicted: Option<bool>,
+  media_type: Option<String>,
+  model: Option<String>,
+  name: Option<String>,
+  predicted_media_life_left_percent: Option<f64>,
+  #[serde(default)]
+  status: Status
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Volume {
+  capacity_bytes: Option<f64>,
+  name:           Option<String>,
+  #[serde(rename = "RAIDType")]
+  raid_type:      Option<String>,
+  #[serde(default)]
+  status:         Status,
+  /// Deprecated in favor of `RAIDType`, older firmware only sends this
+  volume_type:    Option<String>
+}
+
+/// HPE's Smart Array controllers, exposed under `SmartStorage`
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArrayController {
+  location: Option<String>,
+  model:    Option<String>,
+  #[serde(rename = "@odata.id", default)]
+  odata_id: String,
+  #[serde(default)]
+  status:   Status
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LogicalDrive {
+  #[serde(rename = "CapacityMiB")]
+  capacity_mib:         Option<f64>,
+  logical_drive_number: Option<u32>,
+  raid:                 Option<String>,
+  #[serde(default)]
+  status:               Status
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DiskDrive {
+  #[serde(rename = "CapacityMiB")]
+  capacity_mib: Option<f64>,
+  #[serde(default)]
+  disk_drive_status_reasons: Vec<String>,
+  location: Option<String>,
+  media_type: Option<String>,
+  model: Option<String>,
+  /// Percentage of the SSD's endurance that's been used up
+  #[serde(rename = "SSDEnduranceUtilizationPercentage")]
+  ssd_endurance_utilization_percentage: Option<f64>,
+  #[serde(default)]
+  status: Status
+}
+
+/// A physical drive, normalized from either storage model
+struct DriveEntry<'a> {
+  label:     &'a str,
+  model:     Option<&'a str>,
+  media:     Option<&'a str>,
+  capacity:  Option<f64>,
+  status:    &'a Status,
+  predicted: bool,
+  life_left: Option<f64>
+}
+
+#[derive(Default)]
+pub struct StorageReport {
+  pub controllers: Vec<String>,
+  pub volumes:     Vec<String>,
+  pub drives:      Vec<String>,
+  /// Drives and volumes that are failing, predicted to or couldn't be loaded,
+  /// a controller that couldn't be loaded counts once since its drives are unknown
+  pub failing:     usize
+}
+
+impl StorageReport {
+  fn is_empty(&self) -> bool { self.controllers.is_empty() && self.volumes.is_empty() && self.drives.is_empty() }
+
+  fn push_volume(
+    &mut self,
+    name: String,
+    raid: Option<&str>,
+    capacity: Option<f64>,
+    status: &Status
+  ) {
+    let marker = marker(status, false);
+    if !marker.is_empty() {
+      self.failing += 1;
+    }
+
+    self.volumes.push(format!(
+      "{marker}**{name}:** {} • {} - `{}`",
+      raid.map(fmt_raid).unwrap_or_else(|| "Unknown RAID".to_string()),
+      fmt_capacity(capacity),
+      status.summary()
+    ));
+  }
+
+  /// Something that couldn't be loaded, there's no telling whether it's healthy
+  fn push_unreachable(
+    &mut self,
+    section: fn(&mut StorageReport) -> &mut Vec<String>,
+    label: &str
+  ) {
+    self.failing += 1;
+    section(self).push(format!("🔴 **{label}:** `Unknown/unreachable`"));
+  }
+
+  fn push_drive(
+    &mut self,
+    drive: DriveEntry<'_>
+  ) {
+    let marker = marker(drive.status, drive.predicted);
+    if !marker.is_empty() {
+      self.failing += 1;
+    }
+
+    let mut line = format!(
+      "{marker}**{}:** {} ({}, {}) - `{}`",
+      drive.label,
+      drive.model.map(str::trim).unwrap_or("Unknown model"),
+      drive.media.unwrap_or("Unknown"),
+      fmt_capacity(drive.capacity),
+      drive.status.summary()
+    );
+    if let Some(left) = drive.life_left {
+      line.push_str(&format!(" • `{left:.0}%` life left"));
+    }
+    if drive.predicted {
+      line.push_str(" • **predicted to fail**");
+    }
+
+    self.drives.push(line);
+  }
+}
+
+/// Flags anything that needs attention so it stands out in the list
+fn marker(
+  status: &Status,
+  predicted: bool
+) -> &'static str {
+  match status.health.as_deref() {
+    Some("Critical") => "🔴 ",
+    Some("Warning") => "🟡 ",
+    _ if predicted => "🟡 ",
+    _ => ""
+  }
+}
+
+/// Decimal units like the drive vendors use
+fn fmt_capacity(bytes: Option<f64>) -> String {
+  match bytes {
+    Some(b) if b >= 1e12 => format!("{:.1} TB", b / 1e12),
+    Some(b) => format!("{:.0} GB", b / 1e9),
+    None => "Unknown size".to_string()
+  }
+}
+
+fn mib_to_bytes(mib: Option<f64>) -> Option<f64> { mib.map(|m| m * 1024.0 * 1024.0) }
+
+/// HPE sends just the level (`"1"`), DMTF sends `RAID1`
+fn fmt_raid(raid: &str) -> String {
+  match raid.strip_prefix("RAID") {
+    Some(level) => format!("RAID {level}"),
+    None if raid.chars().all(|c| c.is_ascii_digit() || c == '+') => format!("RAID {raid}"),
+    None => raid.to_string()
+  }
+}
+
+async fn smart_storage_report(
+  host: &BmcHost,
+  system: &str
+) -> Result<StorageReport, IloError> {
+  let controllers = expand_each::<ArrayController>(host, &format!("Systems/{system}/SmartStorage/ArrayControllers")).await?;
+  let mut report = StorageReport::default();
+
+  for (id, controller) in &controllers {
+    let Ok(controller) = controller else {
+      report.push_unreachable(|r| &mut r.controllers, &format!("Controller {id}"));
+      continue;
+    };
+
+    report.controllers.push(format!(
+      "{}**{}** ({}) - `{}`",
+      marker(&controller.status, false),
+      controller.model.as_deref().unwrap_or("Unknown controller"),
+      controller.location.as_deref().unwrap_or("Unknown location"),
+      controller.status.summary()
+    ));
+
+    let Some(path) = redfish_path(&controller.odata_id) else { continue };
+
+    let location = controller.location.as_deref().unwrap_or(id.as_str());
+    let (logical, physical) = tokio::join!(
+      expand_each::<LogicalDrive>(host, &format!("{path}/LogicalDrives")),
+      expand_each::<DiskDrive>(host, &format!("{path}/DiskDrives"))
+    );
+
+    match logical {
+      Ok(logical) => {
+        for (id, drive) in logical {
+          let Ok(drive) = drive else {
+            report.push_unreachable(|r| &mut r.volumes, &format!("Logical Drive {id}"));
+            continue;
+          };
+
+          let name = match drive.logical_drive_number {
+            Some(n) => format!("Logical Drive {n}"),
+            None => "Logical Drive".to_string()
+          };
+          report.push_volume(name, drive.raid.as_deref(), mib_to_bytes(drive.capacity_mib), &drive.status);
+        }
+      },
+      Err(_) => report.push_unreachable(|r| &mut r.volumes, &format!("Logical drives on {location}"))
+    }
+
+    let physical = match physical {
+      Ok(physical) => physical,
+      Err(_) => {
+        report.push_unreachable(|r| &mut r.drives, &format!("Drives on {location}"));
+        continue;
+      }
+    };
+
+    for (id, drive) in &physical {
+      let Ok(drive) = drive else {
+        report.push_unreachable(|r| &mut r.drives, &format!("Drive {id}"));
+        continue;
+      };
+      if drive.status.is_absent() {
+        continue;
+      }
+
+      report.push_drive(DriveEntry {
+        label:     drive.location.as_deref().unwrap_or("Unknown bay"),
+        model:     drive.model.as_deref(),
+        media:     drive.media_type.as_deref(),
+        capacity:  mib_to_bytes(drive.capacity_mib),
+        status:    &drive.status,
+        predicted: drive.disk_drive_status_reasons.iter().any(|r| r == "PredictiveFailure"),
+        life_left: drive.ssd_endurance_utilization_percentage.map(|used| 100.0 - used)
+      });
+    }
+  }
+
+  Ok(report)
+}
+
+async fn dmtf_storage_report(
+  host: &BmcHost,
+  system: &str
+) -> Result<StorageReport, IloError> {
+  let subsystems = expand_each::<Storage>(host, &format!("Systems/{system}/Storage")).await?;
+  let mut report = StorageReport::default();
+
+  for (id, storage) in &subsystems {
+    let Ok(storage) = storage else {
+      report.push_unreachable(|r| &mut r.controllers, &format!("Storage {id}"));
+      continue;
+    };
+
+    for controller in &storage.storage_controllers {
+      report.controllers.push(format!(
+        "{}**{}** - `{}`",
+        marker(&controller.status, false),
+        controller.model.as_deref().or(controller.name.as_deref()).unwrap_or("Unknown controller"),
+        controller.status.summary()
+      ));
+    }
+
+    if let Some(path) = storage.volumes.as_ref().and_then(Link::path) {
+      match expand_each::<Volume>(host, path).await {
+        Ok(volumes) => {
+          for (id, volume) in volumes {
+            let Ok(volume) = volume else {
+              report.push_unreachable(|r| &mut r.volumes, &id);
+              continue;
+            };
+
+            report.push_volume(
+              volume.name.clone().unwrap_or_else(|| "Volume".to_string()),
+              volume.raid_type.as_deref().or(volume.volume_type.as_deref()),
+              volume.capacity_bytes,
+              &volume.status
+            );
+          }
+        },
+        Err(_) => report.push_unreachable(|r| &mut r.volumes, &format!("Volumes of {id}"))
+      }
+    }
+
+    for (id, drive) in &fetch_links::<Drive>(host, &storage.drives).await {
+      let Ok(drive) = drive else {
+        report.push_unreachable(|r| &mut r.drives, id);
+        continue;
+      };
+      if drive.status.is_absent() {
+        continue;
+      }
+
+      report.push_drive(DriveEntry {
+        label:     drive.name.as_deref().unwrap_or("Unknown drive"),
+        model:     drive.model.as_deref(),
+        media:     drive.media_type.as_deref(),
+        capacity:  drive.capacity_bytes,
+        status:    &drive.status,
+        predicted: drive.failure_predicted.unwrap_or(false),
+        life_left: drive.predicted_media_life_left_percent
+      });
+    }
+  }
+
+  Ok(report)
+}
+
+/// Walk the host's storage, HPE's Smart Array view is preferred since newer
+/// iLO firmware mirrors the same controllers into the DMTF `Storage` collection
+pub async fn storage_report(host: &BmcHost) -> Result<StorageReport, IloError> {
+  let system = resource_ids(host).await?.system;
+
+  match smart_storage_report(host, &system).await {
+    Ok(report) if !report.is_empty() => Ok(report),
+    _ => dmtf_storage_report(host, &system).await
+  }
+}
+
+/// Retrieve the server's controllers, volumes and drives
+#[poise::command(slash_command)]
+pub async fn storage(
+  ctx: PoiseCtx<'_>,
+  #[description = "Server to query"]
+  #[autocomplete = "autocomplete_host"]
+  host: Option<String>
+) -> KonResult<()> {
+  ctx.defer().await?;
+  let Some(host) = resolve_host(&ctx, host).await? else { return Ok(()) };
+
+  let report = match storage_report(&host).await {
+    Ok(report) => report,
+    Err(e) => {
+      ilo_err(&ctx, &host, e).await;
+      return Ok(());
+    }
+  };
+
+  let mut fields = pack_fields("Controllers", &report.controllers);
+  fields.extend(pack_fields("Logical drives", &report.volumes));
+  fields.extend(pack_fields("Physical drives", &report.drives));
+  fields.retain(|(_, value, _)| !value.is_empty());
+
+  let description = if report.is_empty() {
+    Some("No storage was reported by the BMC.".to_string())
+  } else if report.failing > 0 {
+    Some(format!("🔴 **{}** drive(s)/volume(s) need attention!", report.failing))
+  } else {
+    None
+  };
+
+  let pages = pack_pages(fields)
+    .into_iter()
+    .map(|page| {
+      let embed = embed_builder(&host, "Storage", description.clone(), Some(page));
+      if report.failing > 0 { embed.color(FAILING_COLOR) } else { embed }
+    })
+    .collect::<Vec<_>>();
+
+  let pages = if pages.is_empty() {
+    vec![embed_builder(&host, "Storage", description, None)]
+  } else {
+    pages
+  };
+
+  paginate(ctx, pages, &format!("{} drives", report.drives.len())).await
+}
@@ -0,0 +1,465 @@
+use {
+  super::{
+    IloError,
+    discovery::{
+      Link,
+      collection,
+      redfish_path
+    },
+    embed_builder,
+    fmt_dt,
+    hosts::{
+      BmcHost,
+      all_hosts,
+      find_host
+    },
+    redfish_get,
+    redfish_send
+  },
+  dashmap::DashMap,
+  http_body_util::{
+    BodyExt,
+    Full,
+    LengthLimitError,
+    Limited
+  },
+  hyper::{
+    Request,
+    Response,
+    body::{
+      Bytes,
+      Incoming
+    },
+    server::conn::http1,
+    service::service_fn
+  },
+  hyper_util::rt::TokioIo,
+  kon_libs::{
+    BINARY_PROPERTIES,
+    BmcProtocol
+  },
+  poise::serenity_prelude::{
+    CreateEmbed,
+    CreateMessage,
+    GenericChannelId,
+    Http
+  },
+  reqwest::{
+    Method,
+    StatusCode,
+    header::LOCATION
+  },
+  serde::{
+    Deserialize,
+    Serialize
+  },
+  std::{
+    convert::Infallible,
+    fs,
+    sync::{
+      Arc,
+      LazyLock,
+      atomic::{
+        AtomicBool,
+        Ordering
+      }
+    }
+  },
+  tokio::{
+    io::{
+      AsyncRead,
+      AsyncWrite
+    },
+    net::TcpListener,
+    sync::mpsc,
+    time::{
+      Duration,
+      sleep
+    }
+  },
+  tokio_native_tls::{
+    TlsAcceptor,
+    native_tls
+  }
+};
+
+/// URL the BMCs should push events to, each host gets its ID appended to it
+const EVENTS_URL_ENV: &str = "KON_ILO_EVENTS_URL";
+/// PEM certificate and key, the listener speaks HTTPS when both are set
+const EVENTS_CERT_ENV: &str = "KON_ILO_EVENTS_CERT";
+const EVENTS_KEY_ENV: &str = "KON_ILO_EVENTS_KEY";
+
+/// Event batches are tiny, anything bigger than this isn't from a BMC
+const MAX_BODY: usize = 256 * 1024;
+const MAX_EVENTS: usize = 10;
+
+const CRITICAL_COLOR: u32 = 0xE74C3C;
+const WARNING_COLOR: u32 = 0xF1C40F;
+
+pub type EventSender = mpsc::Sender<(&'static str, EventPayload)>;
+
+/// Subscriptions Kon registered, keyed by host ID
+pub static SUBSCRIPTIONS: LazyLock<DashMap<&'static str, Subscription>> = LazyLock::new(DashMap::new);
+static LISTENER_STARTED: AtomicBool = AtomicBool::new(false);
+
+pub struct Subscription {
+  /// Secret the BMC echoes back in every event, anything without it is rejected
+  pub context: String,
+  /// Subscription resource on the BMC, needed to remove it again
+  pub path:    Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SubscriptionRequest<'a> {
+  destination: &'a str,
+  event_types: [&'a str; 2],
+  context:     &'a str,
+  protocol:    &'a str
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SubscriptionInfo {
+  #[serde(default)]
+  destination: String
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventPayload {
+  context:    Option<String>,
+  #[serde(default)]
+  pub events: Vec<EventRecord>
+}
+
+/// Older schemas put the context and severity on each record instead
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventRecord {
+  context:             Option<String>,
+  event_timestamp:     Option<String>,
+  message:             Option<String>,
+  message_id:          Option<String>,
+  message_severity:    Option<String>,
+  origin_of_condition: Option<Link>,
+  severity:            Option<String>
+}
+
+impl EventPayload {
+  fn has_context(
+    &self,
+    context: &str
+  ) -> bool {
+    self.context.as_deref() == Some(context) || (!self.events.is_empty() && self.events.iter().all(|e| e.context.as_deref() == Some(context)))
+  }
+}
+
+impl EventRecord {
+  fn severity(&self) -> &str { self.message_severity.as_deref().or(self.severity.as_deref()).unwrap_or("OK") }
+}
+
+fn destination_for(
+  base: &str,
+  host: &BmcHost
+) -> String {
+  format!("{}/{}", base.trim_end_matches('/'), host.id)
+}
+
+/// 256 bits from the OS's CSPRNG, it's the only thing vouching for the events that come in
+fn new_context() -> String {
+  let mut secret = [0u8; 32];
+  getrandom::fill(&mut secret).expect("the OS should be able to provide random bytes");
+  hex::encode(secret)
+}
+
+fn tls_acceptor() -> Result<Option<TlsAcceptor>, String> {
+  let (Ok(cert), Ok(key)) = (std::env::var(EVENTS_CERT_ENV), std::env::var(EVENTS_KEY_ENV)) else {
+    return Ok(None);
+  };
+
+  let cert = fs::read(cert).map_err(|e| format!("Couldn't read the certificate: {e}"))?;
+  let key = fs::read(key).map_err(|e| format!("Couldn't read the key: {e}"))?;
+  let identity = native_tls::Identity::from_pkcs8(&cert, &key).map_err(|e| e.to_string())?;
+  let acceptor = native_tls::TlsAcceptor::new(identity).map_err(|e| e.to_string())?;
+
+  Ok(Some(acceptor.into()))
+}
+
+/// Remove subscriptions left behind by an earlier run that didn't shut down cleanly
+async fn remove_stale(
+  host: &BmcHost,
+  base: &str
+) -> Result<(), IloError> {
+  for link in collection(host, "EventService/Subscriptions").await?.members {
+    let Some(path) = link.path() else { continue };
+    let info: SubscriptionInfo = redfish_get(host, path).await?;
+
+    if info.destination.starts_with(base.trim_end_matches('/')) {
+      redfish_send::<()>(host, Method::DELETE, path, None).await?.error_for_status()?;
+    }
+  }
+
+  Ok(())
+}
+
+pub async fn subscribe(
+  host: &BmcHost,
+  base: &str
+) -> Result<(), IloError> {
+  if let Err(e) = remove_stale(host, base).await {
+    eprintln!("iLO[Events] Couldn't clear old subscriptions on {}: {e}", host.name);
+  }
+
+  let context = new_context();
+  let destination = destination_for(base, host);
+
+  // Known before the BMC is told about it, some send a test event straight away
+  SUBSCRIPTIONS.insert(
+    host.id,
+    Subscription {
+      context: context.clone(),
+      path:    None
+    }
+  );
+
+  let request = SubscriptionRequest {
+    destination: &destination,
+    event_types: ["Alert", "StatusChange"],
+    context:     &context,
+    protocol:    "Redfish"
+  };
+  let res = redfish_send(host, Method::POST, "EventService/Subscriptions", Some(&request))
+    .await?
+    .error_for_status()?;
+
+  let path = res
+    .headers()
+    .get(LOCATION)
+    .and_then(|v| v.to_str().ok())
+    .and_then(redfish_path)
+    .map(str::to_string);
+
+  if let Some(mut sub) = SUBSCRIPTIONS.get_mut(host.id) {
+    sub.path = path;
+  }
+
+  Ok(())
+}
+
+/// Check the request against the host's subscription and hand the events off
+async fn receive(
+  req: Request<Incoming>,
+  tx: &EventSender
+) -> StatusCode {
+  if req.method() != Method::POST {
+    return StatusCode::METHOD_NOT_ALLOWED;
+  }
+
+  let id = req.uri().path().trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+  let Some((host_id, context)) = SUBSCRIPTIONS.get(id).map(|s| (*s.key(), s.context.clone())) else {
+    return StatusCode::NOT_FOUND;
+  };
+
+  let body = match Limited::new(req.into_body(), MAX_BODY).collect().await {
+    Ok(body) => body.to_bytes(),
+    Err(e) if e.downcast_ref::<LengthLimitError>().is_some() => return StatusCode::PAYLOAD_TOO_LARGE,
+    Err(_) => return StatusCode::BAD_REQUEST
+  };
+
+  let Ok(payload) = serde_json::from_slice::<EventPayload>(&body) else {
+    return StatusCode::BAD_REQUEST;
+  };
+
+  if !payload.has_context(&context) {
+    eprintln!("iLO[Events] Rejected an event for \"{host_id}\" with the wrong context");
+    return StatusCode::UNAUTHORIZED;
+  }
+
+  match tx.send((host_id, payload)).await {
+    Ok(()) => StatusCode::OK,
+    Err(_) => StatusCode::SERVICE_UNAVAILABLE
+  }
+}
+
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+  stream: S,
+  tx: EventSender
+) {
+  let service = service_fn(move |req| {
+    let tx = tx.clone();
+    async move {
+      let mut res = Response::new(Full::new(Bytes::new()));
+      *res.status_mut() = receive(req, &tx).await;
+      Ok::<_, Infallible>(res)
+    }
+  });
+
+  if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+    eprintln!("iLO[Events] Connection error: {e}");
+  }
+}
+
+/// Accept event deliveries on `listener` and forward the verified ones to `tx`
+pub async fn serve(
+  listener: TcpListener,
+  acceptor: Option<TlsAcceptor>,
+  tx: EventSender
+) {
+  loop {
+    let stream = match listener.accept().await {
+      Ok((stream, _)) => stream,
+      Err(e) => {
+        eprintln!("iLO[Events] Couldn't accept a connection: {e}");
+        sleep(Duration::from_millis(100)).await;
+        continue;
+      }
+    };
+
+    let (acceptor, tx) = (acceptor.clone(), tx.clone());
+    tokio::spawn(async move {
+      match acceptor {
+        Some(acceptor) => match acceptor.accept(stream).await {
+          Ok(stream) => serve_connection(stream, tx).await,
+          Err(e) => eprintln!("iLO[Events] TLS handshake failed: {e}")
+        },
+        None => serve_connection(stream, tx).await
+      }
+    });
+  }
+}
+
+pub fn fmt_event(event: &EventRecord) -> String {
+  let dt = event.event_timestamp.as_deref().and_then(fmt_dt).unwrap_or_else(|| "Unknown".to_string());
+  let mut line = format!("**[{}:{dt}]:** {}", event.severity(), event.message.as_deref().unwrap_or("No message"));
+
+  let origin = event.origin_of_condition.as_ref().and_then(Link::path);
+  match (event.message_id.as_deref(), origin) {
+    (Some(id), Some(origin)) => line.push_str(&format!("\n-# {id} • {origin}")),
+    (Some(info), None) | (None, Some(info)) => line.push_str(&format!("\n-# {info}")),
+    (None, None) => ()
+  }
+
+  line
+}
+
+fn event_embed(
+  host: &BmcHost,
+  payload: &EventPayload
+) -> CreateEmbed<'static> {
+  let mut lines: Vec<String> = payload.events.iter().take(MAX_EVENTS).map(fmt_event).collect();
+  if payload.events.len() > MAX_EVENTS {
+    lines.push(format!("-# …and {} more", payload.events.len() - MAX_EVENTS));
+  }
+  if lines.is_empty() {
+    lines.push("Received an event without any records.".to_string());
+  }
+
+  let embed = embed_builder(host, "Redfish event", Some(lines.join("\n")), None);
+  let severities: Vec<&str> = payload.events.iter().map(EventRecord::severity).collect();
+
+  if severities.contains(&"Critical") {
+    embed.color(CRITICAL_COLOR)
+  } else if severities.iter().any(|s| matches!(*s, "Warning" | "Caution")) {
+    embed.color(WARNING_COLOR)
+  } else {
+    embed
+  }
+}
+
+async fn post_events(
+  http: &Http,
+  host_id: &str,
+  payload: EventPayload
+) {
+  let Some(host) = find_host(Some(host_id)).await else { return };
+
+  if let Err(e) = GenericChannelId::new(BINARY_PROPERTIES.ilo_events_channel)
+    .send_message(http, CreateMessage::new().add_embed(event_embed(&host, &payload)))
+    .await
+  {
+    eprintln!("iLO[Events] Couldn't post the event from {}: {e}", host.name);
+  }
+}
+
+/// Start the event listener and subscribe every host to it, only the first call does anything
+pub fn spawn_ilo_listener(http: Arc<Http>) {
+  if LISTENER_STARTED.swap(true, Ordering::SeqCst) {
+    return;
+  }
+
+  let Ok(base) = std::env::var(EVENTS_URL_ENV) else {
+    println!("iLO[Events] {EVENTS_URL_ENV} isn't set, not subscribing to Redfish events");
+    return;
+  };
+
+  tokio::spawn(async move {
+    let acceptor = match tls_acceptor() {
+      Ok(acceptor) => acceptor,
+      Err(e) => {
+        eprintln!("iLO[Events] Couldn't set up TLS for the listener: {e}");
+        return;
+      }
+    };
+    let listener = match TcpListener::bind(BINARY_PROPERTIES.ilo_events_bind).await {
+      Ok(listener) => listener,
+      Err(e) => {
+        eprintln!("iLO[Events] Couldn't bind to {}: {e}", BINARY_PROPERTIES.ilo_events_bind);
+        return;
+      }
+    };
+
+    // Without TLS the context secrets go over the wire in the clear, keep that off the network
+    if acceptor.is_none()
+      && let Ok(addr) = listener.local_addr()
+      && !addr.ip().is_loopback()
+    {
+      eprintln!("iLO[Events] Refusing to listen on {addr} over plain HTTP, set {EVENTS_CERT_ENV} and {EVENTS_KEY_ENV} or bind to a loopback address");
+      return;
+    }
+
+    println!(
+      "iLO[Events] Listening on {} over {}",
+      BINARY_PROPERTIES.ilo_events_bind,
+      if acceptor.is_some() { "HTTPS" } else { "HTTP" }
+    );
+
+    let (tx, mut rx) = mpsc::channel(32);
+    tokio::spawn(serve(listener, acceptor, tx));
+
+    // IPMI has nothing to push events with
+    for host in all_hosts().await.into_iter().filter(|h| h.protocol == BmcProtocol::Redfish) {
+      match subscribe(&host, &base).await {
+        Ok(()) => println!("iLO[Events] Subscribed to {}'s events", host.name),
+        Err(e) => {
+          SUBSCRIPTIONS.remove(host.id);
+          eprintln!("iLO[Events] Couldn't subscribe to {}'s events: {e}", host.name);
+        }
+      }
+    }
+
+    while let Some((host_id, payload)) = rx.recv().await {
+      post_events(&http, host_id, payload).await;
+    }
+  });
+}
+
+/// Remove the event subscriptions from the BMCs, has to run before the sessions are closed
+pub async fn close_ilo_subscriptions() {
+  let Ok(base) = std::env::var(EVENTS_URL_ENV) else { return };
+
+  for host in all_hosts().await {
+    let Some((_, sub)) = SUBSCRIPTIONS.remove(host.id) else { continue };
+
+    let result = match sub.path {
+      Some(path) => redfish_send::<()>(&host, Method::DELETE, &path, None)
+        .await
+        .and_then(|res| res.error_for_status().map(|_| ()).map_err(IloError::from)),
+      None => remove_stale(&host, &base).await
+    };
+
+    if let Err(e) = result {
+      eprintln!("iLO[Events] Couldn't unsubscribe from {}: {e}", host.name);
+    }
+  }
+}
@@ -0,0 +1,1107 @@
+pub mod mock;
+
+use {
+  super::{
+    actions::ResetType,
+    bios::{
+      bios_data,
+      bios_lines,
+      coerce_value
+    },
+    boot::{
+      BootOverrideRequest,
+      BootTarget,
+      MediaAction,
+      MediaRequest,
+      apply_media,
+      media_request,
+      optical_slot
+    },
+    inventory::collect_inventory,
+    ipmi::{
+      SensorRecord,
+      sel_entry,
+      sensor_readings,
+      session::{
+        SessionKeys,
+        ipmi_message,
+        parse_response
+      }
+    },
+    logs::{
+      LogFilter,
+      LogSeverity,
+      fmt_entry
+    },
+    manager::{
+      manager_data,
+      manager_summary,
+      network_lines
+    },
+    monitor::{
+      Level,
+      temp_level
+    },
+    powercap::{
+      LimitException,
+      PowerCapRequest,
+      powercap_summary
+    },
+    session::close_ilo_sessions,
+    storage::storage_report,
+    subscriptions::{
+      EventPayload,
+      SUBSCRIPTIONS,
+      Subscription,
+      fmt_event,
+      serve,
+      subscribe
+    },
+    *
+  },
+  asahi::utils::format_duration,
+  kon_libs::IloTls,
+  mock::{
+    MOCK_TLS,
+    MockBmc
+  },
+  reqwest::Client,
+  std::{
+    collections::HashMap,
+    fs
+  },
+  tokio::{
+    net::TcpListener,
+    sync::mpsc
+  }
+};
+
+async fn fetch<T: DeserializeOwned>(
+  host: &BmcHost,
+  endpoint: RedfishEndpoint
+) -> T {
+  match ilo_data::<T>(host, endpoint).await {
+    Ok(data) => data,
+    Err(e) => panic!("{e}")
+  }
+}
+
+fn lines(raw: &[&str]) -> Vec<String> { raw.iter().map(|l| l.to_string()).collect() }
+
+#[tokio::test]
+async fn temperature_ilo4() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-ilo4.json")]).await;
+  let data: Chassis = fetch(&bmc.host("thermal-ilo4"), RedfishEndpoint::Thermal).await;
+
+  let (temps, fans) = sensor_lines(&data, &[], false);
+  assert_eq!(temps, lines(&["**01-Inlet Ambient:** `21°C`", "**02-CPU 1:** `40°C`"]));
+  assert_eq!(fans, lines(&["**Fan 1:** `23%`"]));
+
+  let (temps, _) = sensor_lines(&data, &[("01-Inlet Ambient", "Inlet Ambient")], false);
+  assert_eq!(temps, lines(&["**Inlet Ambient:** `21°C`"]));
+
+  let (temps, _) = sensor_lines(&data, &[("01-Inlet Ambient", "Inlet Ambient")], true);
+  assert_eq!(
+    temps,
+    lines(&[
+      "**01-Inlet Ambient:** `21°C` (crit `42°C`, fatal `47°C`) - OK",
+      "**02-CPU 1:** `40°C` (crit `70°C`, fatal `-`) - OK"
+    ])
+  );
+}
+
+#[tokio::test]
+async fn temperature_dmtf() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-dmtf.json")]).await;
+  let data: Chassis = fetch(&bmc.host("thermal-dmtf"), RedfishEndpoint::Thermal).await;
+
+  let (temps, fans) = sensor_lines(&data, &[], true);
+  assert_eq!(
+    temps,
+    lines(&[
+      "**System Board Inlet Temp:** `24°C` (crit `42°C`, fatal `47°C`) - OK",
+      "**CPU1 Temp:** `51°C` (crit `-`, fatal `-`) - OK"
+    ])
+  );
+  assert_eq!(fans, lines(&["**System Board Fan1A:** `5880 RPM`", "**System Board Fan1B:** `5640 RPM`"]));
+}
+
+#[tokio::test]
+async fn temperature_partial_and_malformed() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-partial.json")]).await;
+  let data: Chassis = fetch(&bmc.host("thermal-partial"), RedfishEndpoint::Thermal).await;
+  let (temps, fans) = sensor_lines(&data, &[], false);
+  assert_eq!(temps, lines(&["**Inlet:** `19°C`", "**:** `30°C`"]));
+  assert!(fans.is_empty());
+
+  // A placeholder reading shouldn't take the whole payload down with it
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-malformed.json")]).await;
+  let data: Chassis = fetch(&bmc.host("thermal-malformed"), RedfishEndpoint::Thermal).await;
+  let (temps, fans) = sensor_lines(&data, &[], true);
+  assert!(temps.is_empty());
+  assert!(fans.is_empty());
+}
+
+#[tokio::test]
+async fn power_readings() {
+  for (id, name, expected) in [
+    ("power-ilo4", "power-ilo4.json", ["460w", "112w", "110w", "168w", "104w"]),
+    ("power-dmtf", "power-dmtf.json", ["800w", "232w", "228w", "302w", "197w"]),
+    ("power-partial", "power-partial.json", ["N/A", "N/A", "N/A", "N/A", "N/A"])
+  ] {
+    let bmc = MockBmc::start(&[("Chassis/1/Power", name)]).await;
+    let data: Power = fetch(&bmc.host(id), RedfishEndpoint::Power).await;
+
+    assert_eq!(
+      power_summary(&data),
+      format!(
+        "**Power Capacity:** `{}`\n**Power Consumed:** `{}`\n**Average Power:** `{}`\n**Max Consumed:** `{}`\n**Min Consumed:** `{}`",
+        expected[0], expected[1], expected[2], expected[3], expected[4]
+      ),
+      "{name}"
+    );
+  }
+}
+
+#[tokio::test]
+async fn system_summaries() {
+  let cases = [
+    (
+      "system-hpe",
+      "system-hpe.json",
+      "**Health:** `OK`\n**POST:** `Finished POST`\n**Power:** `On`\n**Model:** `HPE ProLiant DL380 Gen9`",
+      ("CPU (2x)", "Intel(R) Xeon(R) CPU E5-2680 v4 @ 2.40GHz", "128 GB")
+    ),
+    (
+      "system-ilo4",
+      "system-ilo4.json",
+      "**Health:** `OK`\n**POST:** `In POST (Booting)`\n**Power:** `On`\n**Model:** `HP ProLiant DL360p Gen8`",
+      ("CPU (1x)", "Intel(R) Xeon(R) CPU E5-2640 0 @ 2.50GHz", "64 GB")
+    ),
+    (
+      "system-dell",
+      "system-dell.json",
+      "**Health:** `OK`\n**Generation:** `14G Monolithic`\n**Rollup Status:** `OK`\n**Power:** `On`\n**Model:** `Dell Inc. PowerEdge R740`",
+      ("CPU (2x)", "Intel(R) Xeon(R) Gold 6130 CPU @ 2.10GHz", "192 GB")
+    ),
+    (
+      "system-partial",
+      "system-partial.json",
+      "**Health:** `OK`\n**Power:** `Off`\n**Model:** `Unknown`",
+      ("CPU (0x)", "Unknown", "Unknown")
+    )
+  ];
+
+  for (id, name, description, (cpu, model, ram)) in cases {
+    let bmc = MockBmc::start(&[("Systems/1", name), ("EventService", "eventservice.json")]).await;
+    let host = bmc.host(id);
+    let system: System = fetch(&host, RedfishEndpoint::System).await;
+    let event: Event = fetch(&host, RedfishEndpoint::EventService).await;
+
+    let (data, fields) = system_summary(&system, &event);
+    assert_eq!(data, description, "{name}");
+    assert_eq!(
+      fields,
+      vec![(cpu.to_string(), model.to_string(), true), ("RAM".to_string(), ram.to_string(), true)],
+      "{name}"
+    );
+  }
+}
+
+#[tokio::test]
+async fn iml_entries() {
+  let bmc = MockBmc::start(&[("Systems/1/LogServices/IML/Entries", "iml-ilo4.json")]).await;
+  let log: Iml = fetch(&bmc.host("iml-ilo4"), RedfishEndpoint::LogServices).await;
+
+  let rendered: Vec<String> = log.entries().iter().map(fmt_entry).collect();
+  assert_eq!(
+    rendered,
+    lines(&[
+      "**[OK:02/03/2024 08:15:00]:** Server power restored.",
+      "**[Caution:10/03/2024 22:41:12]:** Network Adapter Link Down (Slot 0, Port 2)",
+      "**[Critical:01/04/2024 03:00:59]:** POST Error: 1785-Drive Array not Configured"
+    ])
+  );
+
+  let count = |filter: LogFilter| log.entries().iter().filter(|e| filter.matches(e)).count();
+  assert_eq!(
+    count(LogFilter {
+      severity: Some(LogSeverity::Warning),
+      since:    None,
+      until:    None
+    }),
+    1
+  );
+  assert_eq!(
+    count(LogFilter {
+      severity: None,
+      since:    Some("2024-03-05".to_string()),
+      until:    Some("2024-03-31".to_string())
+    }),
+    1
+  );
+}
+
+#[tokio::test]
+async fn iml_dmtf_and_malformed() {
+  let bmc = MockBmc::start(&[
+    ("Systems/1/LogServices/IML/Entries", "iml-dmtf.json"),
+    ("Managers/1/LogServices/IEL/Entries", "iml-malformed.json")
+  ])
+  .await;
+  let host = bmc.host("iml-dmtf");
+
+  let log: Iml = fetch(&host, RedfishEndpoint::LogServices).await;
+  assert_eq!(log.entries().len(), 3);
+  assert_eq!(fmt_entry(&log.entries()[2]), "**[Critical:03/05/2024 18:45:00]:** ");
+
+  assert!(ilo_data::<Iml>(&host, RedfishEndpoint::EventLog).await.is_err());
+}
+
+#[tokio::test]
+async fn manager_event_log_discovery() {
+  let bmc = MockBmc::start(&[
+    ("Managers/1/LogServices", "manager-logservices-dell.json"),
+    ("Managers/1/LogServices/Lclog/Entries", "iml-dmtf.json")
+  ])
+  .await;
+  let host = bmc.host("event-log-dell");
+
+  let log: Iml = fetch(&host, RedfishEndpoint::EventLog).await;
+  assert_eq!(log.entries().len(), 3);
+  assert_eq!(
+    RedfishEndpoint::ClearEventLog
+      .url(&resource_ids(&host).await.unwrap_or_else(|e| panic!("{e}")))
+      .ok()
+      .as_deref(),
+    Some("Managers/1/LogServices/Lclog/Actions/LogService.ClearLog")
+  );
+
+  let mut ids = resource_ids(&host).await.unwrap_or_else(|e| panic!("{e}"));
+  ids.event_log = None;
+  assert!(matches!(RedfishEndpoint::EventLog.url(&ids), Err(IloError::Missing(_))));
+}
+
+#[tokio::test]
+async fn session_relogin_and_logout() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-ilo4.json")]).await;
+  bmc.enable_sessions();
+  let host = bmc.host("session-relogin");
+
+  let _: Chassis = fetch(&host, RedfishEndpoint::Thermal).await;
+  let _: Chassis = fetch(&host, RedfishEndpoint::Thermal).await;
+  assert_eq!(bmc.sessions(), (1, 1), "the token should be reused");
+
+  // The BMC forgot the session, the 401 gets one fresh login and the request goes through
+  bmc.expire_sessions();
+  let data: Chassis = fetch(&host, RedfishEndpoint::Thermal).await;
+  assert_eq!(data.temperatures.len(), 3);
+  assert_eq!(bmc.sessions(), (2, 1));
+
+  close_ilo_sessions().await;
+  assert_eq!(bmc.sessions(), (2, 0), "shutting down should log out");
+}
+
+#[tokio::test]
+async fn missing_endpoints_and_bad_credentials() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-ilo4.json")]).await;
+
+  assert!(ilo_data::<Power>(&bmc.host("missing-power"), RedfishEndpoint::Power).await.is_err());
+  assert!(
+    ilo_data::<Chassis>(&bmc.host_with("bad-password", "hunter2", &[]), RedfishEndpoint::Thermal)
+      .await
+      .is_err()
+  );
+}
+
+#[tokio::test]
+async fn smart_storage_drives() {
+  let array = "Systems/1/SmartStorage/ArrayControllers";
+  let bmc = MockBmc::start(&[
+    (array, "smartstorage/controllers.json"),
+    (&format!("{array}/0"), "smartstorage/controller.json"),
+    (&format!("{array}/0/LogicalDrives"), "smartstorage/logical-drives.json"),
+    (&format!("{array}/0/LogicalDrives/1"), "smartstorage/logical-drive.json"),
+    (&format!("{array}/0/DiskDrives"), "smartstorage/disk-drives.json"),
+    (&format!("{array}/0/DiskDrives/0"), "smartstorage/disk-drive-0.json"),
+    (&format!("{array}/0/DiskDrives/1"), "smartstorage/disk-drive-1.json"),
+    (&format!("{array}/0/DiskDrives/2"), "smartstorage/disk-drive-2.json")
+  ])
+  .await;
+  let report = storage_report(&bmc.host("storage-smart")).await.unwrap_or_else(|e| panic!("{e}"));
+
+  assert_eq!(report.controllers, lines(&["**Smart Array P440ar Controller** (Slot 0) - `OK`"]));
+  assert_eq!(report.volumes, lines(&["**Logical Drive 1:** RAID 1 • 300 GB - `OK`"]));
+  assert_eq!(
+    report.drives,
+    lines(&[
+      "**1I:1:1:** EG0300FCSPH (HDD, 300 GB) - `OK`",
+      "🟡 **1I:1:2:** MO0400JDVEU (SSD, 400 GB) - `Warning` • `8%` life left • **predicted to fail**"
+    ])
+  );
+  assert_eq!(report.failing, 1);
+}
+
+#[tokio::test]
+async fn dmtf_storage_drives() {
+  let storage = "Systems/1/Storage/RAID.Integrated.1-1";
+  let bmc = MockBmc::start(&[
+    ("Systems/1/Storage", "storage/collection.json"),
+    (storage, "storage/storage.json"),
+    (&format!("{storage}/Volumes"), "storage/volumes.json"),
+    (&format!("{storage}/Volumes/Disk.Virtual.0"), "storage/volume.json"),
+    (&format!("{storage}/Drives/Disk.Bay.0"), "storage/drive-0.json"),
+    (&format!("{storage}/Drives/Disk.Bay.1"), "storage/drive-1.json")
+  ])
+  .await;
+  // No SmartStorage here, so it has to fall back to the DMTF collection
+  let report = storage_report(&bmc.host("storage-dmtf")).await.unwrap_or_else(|e| panic!("{e}"));
+
+  assert_eq!(report.controllers, lines(&["**PERC H730P Mini** - `OK`"]));
+  assert_eq!(report.volumes, lines(&["**Virtual Disk 0:** RAID 1 • 480 GB - `OK`"]));
+  assert_eq!(
+    report.drives,
+    lines(&[
+      "**Solid State Disk 0:1:0:** SSDSC2KB480G8R (SSD, 480 GB) - `OK` • `99%` life left",
+      "🔴 **Physical Disk 0:1:1:** ST2000NM0055 (HDD, 2.0 TB) - `Critical` • **predicted to fail**",
+      "🔴 **Disk.Bay.2:** `Unknown/unreachable`"
+    ])
+  );
+  assert_eq!(report.failing, 2, "a drive that didn't load can't be called healthy");
+}
+
+#[tokio::test]
+async fn inventory_dmtf() {
+  let bmc = MockBmc::start(&[
+    ("Systems/1", "inventory/system.json"),
+    ("Chassis/1/Power", "inventory/power.json"),
+    ("UpdateService/FirmwareInventory", "inventory/firmware.json"),
+    ("UpdateService/FirmwareInventory/1", "inventory/firmware-1.json"),
+    ("UpdateService/FirmwareInventory/2", "inventory/firmware-2.json"),
+    ("Systems/1/Processors", "inventory/processors.json"),
+    ("Systems/1/Processors/1", "inventory/processor-1.json"),
+    ("Systems/1/Processors/2", "inventory/processor-2.json"),
+    ("Systems/1/Memory", "inventory/memory.json"),
+    ("Systems/1/Memory/proc1dimm1", "inventory/dimm-1.json"),
+    ("Systems/1/Memory/proc1dimm2", "inventory/dimm-2.json"),
+    ("Chassis/1/NetworkAdapters", "inventory/adapters.json"),
+    ("Chassis/1/NetworkAdapters/DC080000", "inventory/adapter.json")
+  ])
+  .await;
+  let inventory = collect_inventory(&bmc.host("inventory-dmtf")).await.unwrap_or_else(|e| panic!("{e}"));
+
+  assert_eq!(
+    inventory.overview(),
+    "**Model:** `HPE ProLiant DL380 Gen10`\n**Serial:** `CZJ0000ABC`\n**SKU:** `868703-B21`\n**BIOS:** `U30 v2.50 (11/18/2020)`\n**CPUs:** `2x, 56 \
+     threads` - `OK`\n**Memory:** `64 GB` - `OK`"
+  );
+  assert_eq!(
+    inventory.sections(),
+    vec![
+      (
+        "Firmware",
+        lines(&["**iLO 5:** `2.44 Apr 30 2021`", "**System ROM:** `U30 v2.50 (11/18/2020)`"])
+      ),
+      (
+        "Processors",
+        lines(&["**Proc 1:** Intel(R) Xeon(R) Gold 6132 CPU @ 2.60GHz • 14C/28T @ 4000 MHz - `OK`"])
+      ),
+      ("Memory", lines(&["**PROC 1 DIMM 1:** 32 GB DDR4 @ 2666 MHz (HPE 840758-091) - `OK`"])),
+      (
+        "Network adapters",
+        lines(&["**HPE Ethernet 1Gb 4-port 366FLR Adapter:** 4 port(s) • fw `20.14.54` - `OK`"])
+      ),
+      (
+        "Power supplies",
+        lines(&[
+          "**HpeServerPowerSupply:** 865414-B21 • 800w • fw `1.00` - `OK`",
+          "**Power Supply 2:** Unknown • 800w • fw `1.00` - `Warning`"
+        ])
+      )
+    ]
+  );
+}
+
+#[tokio::test]
+async fn inventory_ilo4_fallbacks() {
+  let bmc = MockBmc::start(&[
+    ("Systems/1", "system-ilo4.json"),
+    ("Systems/1/FirmwareInventory", "inventory/firmware-ilo4.json"),
+    ("Systems/1/Memory", "inventory/memory-ilo4.json"),
+    ("Systems/1/Memory/proc1dimm1", "inventory/dimm-ilo4-1.json"),
+    ("Systems/1/Memory/proc1dimm2", "inventory/dimm-ilo4-2.json"),
+    ("Systems/1/NetworkAdapters", "inventory/base-adapters.json"),
+    ("Systems/1/NetworkAdapters/1", "inventory/base-adapter.json")
+  ])
+  .await;
+  let inventory = collect_inventory(&bmc.host("inventory-ilo4")).await.unwrap_or_else(|e| panic!("{e}"));
+
+  assert_eq!(
+    inventory.sections(),
+    vec![
+      ("Firmware", lines(&["**iLO:** `2.78 Apr 28 2021`", "**System ROM:** `P71 05/21/2019`"])),
+      ("Processors", Vec::new()),
+      (
+        "Memory",
+        lines(&["**PROC  1 DIMM  1:** 16 GB DDR3 @ 1600 MHz (HP 713985-B21) - `GoodInUse`"])
+      ),
+      (
+        "Network adapters",
+        lines(&["**HP Ethernet 1Gb 4-port 331FLR Adapter:** 2 port(s) • fw `1.1200.0` - `OK`"])
+      ),
+      ("Power supplies", Vec::new())
+    ]
+  );
+
+  // Nothing in the JSON export should depend on which layout the BMC used
+  let export = serde_json::to_value(&inventory).unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(export["Firmware"][1]["Version"], "P71 05/21/2019");
+  assert_eq!(export["System"]["Model"], "ProLiant DL360p Gen8");
+}
+
+#[tokio::test]
+async fn manager_status() {
+  let bmc = MockBmc::start(&[
+    ("Managers/1", "manager/ilo5.json"),
+    ("Managers/1/EthernetInterfaces", "manager/interfaces.json"),
+    ("Managers/1/EthernetInterfaces/1", "manager/interface-1.json"),
+    ("Managers/1/EthernetInterfaces/2", "manager/interface-2.json")
+  ])
+  .await;
+  let (manager, interfaces) = manager_data(&bmc.host("manager-ilo5")).await.unwrap_or_else(|e| panic!("{e}"));
+  let (description, fields) = manager_summary(&manager);
+
+  assert_eq!(
+    description,
+    format!(
+      "**Model:** `iLO 5`\n**Firmware:** `iLO 5 v2.44`\n**License:** `iLO Advanced`\n**Health:** `OK`\n**Uptime:** `{}`",
+      format_duration(93824)
+    )
+  );
+  assert_eq!(
+    fields,
+    vec![(
+      "Self-test".to_string(),
+      "**NVRAMData:** `OK`\n**EmbeddedFlash:** `OK` - Controller firmware revision  2.10.00\n**HostRom:** `Degraded`\n".to_string(),
+      false
+    )]
+  );
+  assert_eq!(
+    network_lines(&interfaces),
+    lines(&[
+      "**Manager Dedicated Network Interface:** `10.0.0.21/255.255.255.0` via `10.0.0.1` (DHCP) • `ilo-web01.lan` • `94:18:82:00:00:01` • 1000 Mbps"
+    ])
+  );
+
+  // No uptime, self-tests or network on older firmware
+  let bmc = MockBmc::start(&[("Managers/1", "manager/ilo4.json")]).await;
+  let (manager, interfaces) = manager_data(&bmc.host("manager-ilo4")).await.unwrap_or_else(|e| panic!("{e}"));
+  let (description, fields) = manager_summary(&manager);
+  assert_eq!(
+    description,
+    "**Model:** `iLO 4`\n**Firmware:** `iLO 4 v2.78`\n**License:** `iLO 4 Advanced`\n**Health:** `Enabled`\n**Uptime:** `Unknown`"
+  );
+  assert!(fields.is_empty());
+  assert!(network_lines(&interfaces).is_empty());
+}
+
+#[tokio::test]
+async fn power_cap() {
+  let bmc = MockBmc::start(&[("Chassis/1/Power", "power-capped.json")]).await;
+  let host = bmc.host("power-capped");
+
+  let data: Power = fetch(&host, RedfishEndpoint::Power).await;
+  assert_eq!(
+    powercap_summary(&data),
+    "**Power Cap:** `400w` (`LogEventOnly` when exceeded)\n**Power Capacity:** `800w`\n**Average Power:** `228w`\n**Max Consumed:** \
+     `302w`\n**Headroom:** `98w` above the max draw"
+  );
+
+  let over = mock::fixture("power-capped.json").replace("\"MaxConsumedWatts\": 302", "\"MaxConsumedWatts\": 430");
+  let data: Power = serde_json::from_str(&over).unwrap_or_else(|e| panic!("{e}"));
+  assert!(
+    powercap_summary(&data).ends_with("**Headroom:** None, the max draw is over the cap by `30w`"),
+    "{}",
+    powercap_summary(&data)
+  );
+
+  let data: Power = serde_json::from_str(&mock::fixture("power-dmtf.json")).unwrap_or_else(|e| panic!("{e}"));
+  assert!(powercap_summary(&data).starts_with("**Power Cap:** `None`\n"));
+
+  for (request, expected) in [
+    (
+      PowerCapRequest::new(Some(350), Some(LimitException::HardPowerOff)),
+      serde_json::json!({ "PowerControl": [{ "PowerLimit": { "LimitException": "HardPowerOff", "LimitInWatts": 350 } }] })
+    ),
+    (
+      PowerCapRequest::new(None, None),
+      serde_json::json!({ "PowerControl": [{ "PowerLimit": { "LimitInWatts": null } }] })
+    )
+  ] {
+    ilo_patch(&host, RedfishEndpoint::Power, &request).await.unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(bmc.patches().last(), Some(&("Chassis/1/Power".to_string(), expected)));
+  }
+}
+
+#[tokio::test]
+async fn bios_attributes_and_pending_changes() {
+  let bmc = MockBmc::start(&[("Systems/1/Bios", "bios/current.json"), ("Systems/1/Bios/Settings", "bios/settings.json")]).await;
+  let host = bmc.host("bios-ilo5");
+  let (current, staged, settings_path) = bios_data(&host).await.unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(settings_path, "Systems/1/Bios/Settings");
+
+  let (pending, attributes) = bios_lines(&current, staged.as_ref(), None);
+  assert_eq!(
+    pending,
+    lines(&[
+      "🟡 **ProcVirtualization:** `Enabled` → `Disabled`",
+      "🟡 **SerialConsoleBaud:** `115200` → `57600`"
+    ])
+  );
+  assert_eq!(attributes.len(), 5);
+  assert!(attributes.contains(&"🟡 **ProcVirtualization:** `Enabled`".to_string()));
+  assert!(attributes.contains(&"**BootMode:** `Uefi`".to_string()));
+
+  let (pending, attributes) = bios_lines(&current, staged.as_ref(), Some("boot"));
+  assert!(pending.is_empty());
+  assert_eq!(attributes, lines(&["**BootMode:** `Uefi`"]));
+
+  let attrs = current.attributes();
+  assert_eq!(coerce_value(attrs["SerialConsoleBaud"], "9600"), Some(serde_json::json!(9600)));
+  assert_eq!(coerce_value(attrs["SerialConsoleBaud"], "fast"), None);
+  assert_eq!(coerce_value(attrs["BootMode"], "LegacyBios"), Some(serde_json::json!("LegacyBios")));
+
+  let body = current.settings_body("BootMode", serde_json::json!("LegacyBios"));
+  redfish_patch(&host, &settings_path, &body).await.unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(
+    bmc.patches(),
+    vec![(settings_path, serde_json::json!({ "Attributes": { "BootMode": "LegacyBios" } }))]
+  );
+}
+
+#[tokio::test]
+async fn bios_unreadable_settings() {
+  let bmc = MockBmc::start(&[("Systems/1/Bios", "bios/current.json"), ("Systems/1/Bios/Settings", "iml-malformed.json")]).await;
+
+  // Saying nothing's pending would be wrong when the staged settings couldn't be read
+  assert!(matches!(
+    bios_data(&bmc.host("bios-unreadable")).await,
+    Err(IloError::UnexpectedSchema(_))
+  ));
+}
+
+#[tokio::test]
+async fn bios_ilo4_flat_attributes() {
+  let bmc = MockBmc::start(&[("Systems/1/Bios", "bios/ilo4.json")]).await;
+  let (current, staged, settings_path) = bios_data(&bmc.host("bios-ilo4")).await.unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(settings_path, "Systems/1/Bios/Settings");
+  assert!(staged.is_none());
+
+  let (pending, attributes) = bios_lines(&current, staged.as_ref(), None);
+  assert!(pending.is_empty());
+  assert_eq!(
+    attributes,
+    lines(&[
+      "**BootMode:** `LegacyBios`",
+      "**EmbeddedSerialPort:** `Com2Irq3`",
+      "**ProcVirtualization:** `Enabled`"
+    ])
+  );
+  assert_eq!(
+    current.settings_body("ProcVirtualization", serde_json::json!("Disabled")),
+    serde_json::json!({ "ProcVirtualization": "Disabled" })
+  );
+}
+
+#[tokio::test]
+async fn boot_override() {
+  let bmc = MockBmc::start(&[("Systems/1", "system-hpe.json")]).await;
+  let host = bmc.host("boot-override");
+
+  for (target, enabled, redfish) in [(BootTarget::Cd, "Once", "Cd"), (BootTarget::None, "Disabled", "None")] {
+    ilo_patch(&host, RedfishEndpoint::System, &BootOverrideRequest::new(target))
+      .await
+      .unwrap_or_else(|e| panic!("{e}"));
+    assert_eq!(
+      bmc.patches().last(),
+      Some(&(
+        "Systems/1".to_string(),
+        serde_json::json!({ "Boot": { "BootSourceOverrideEnabled": enabled, "BootSourceOverrideTarget": redfish } })
+      ))
+    );
+  }
+}
+
+#[tokio::test]
+async fn virtual_media_actions() {
+  let iso = "http://10.0.0.5/isos/debian-12.iso";
+  let bmc = MockBmc::start(&[
+    ("Managers/1/VirtualMedia", "media/slots.json"),
+    ("Managers/1/VirtualMedia/1", "media/floppy.json"),
+    ("Managers/1/VirtualMedia/2", "media/cd-ilo5.json")
+  ])
+  .await;
+  let host = bmc.host("media-ilo5");
+
+  let slot = optical_slot(&host).await.unwrap_or_else(|e| panic!("{e}")).expect("CD slot");
+  assert_eq!(slot.image(), Some("http://10.0.0.5/isos/systemrescue-11.00.iso"));
+
+  apply_media(&host, &slot, MediaAction::Insert, Some(iso))
+    .await
+    .unwrap_or_else(|e| panic!("{e}"));
+  apply_media(&host, &slot, MediaAction::Eject, None)
+    .await
+    .unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(
+    bmc.posts(),
+    vec![
+      (
+        "Managers/1/VirtualMedia/2/Actions/VirtualMedia.InsertMedia".to_string(),
+        serde_json::json!({ "Image": iso, "Inserted": true, "WriteProtected": true })
+      ),
+      (
+        "Managers/1/VirtualMedia/2/Actions/VirtualMedia.EjectMedia".to_string(),
+        serde_json::json!({})
+      ),
+    ]
+  );
+
+  // iLO 4 has no actions, the image gets PATCHed in and out instead
+  let bmc = MockBmc::start(&[
+    ("Managers/1/VirtualMedia", "media/slots.json"),
+    ("Managers/1/VirtualMedia/1", "media/floppy.json"),
+    ("Managers/1/VirtualMedia/2", "media/cd-ilo4.json")
+  ])
+  .await;
+  let slot = optical_slot(&bmc.host("media-ilo4"))
+    .await
+    .unwrap_or_else(|e| panic!("{e}"))
+    .expect("CD slot");
+  assert_eq!(slot.image(), None);
+  assert_eq!(
+    media_request(&slot, MediaAction::Insert, Some(iso)),
+    MediaRequest::Patch("Managers/1/VirtualMedia/2".to_string(), serde_json::json!({ "Image": iso }))
+  );
+  assert_eq!(
+    media_request(&slot, MediaAction::Eject, Some(iso)),
+    MediaRequest::Patch("Managers/1/VirtualMedia/2".to_string(), serde_json::json!({ "Image": null }))
+  );
+
+  // Only a floppy under the manager and nothing under the system, that's no slot rather than a failure
+  let bmc = MockBmc::start(&[
+    ("Managers/1/VirtualMedia", "media/slots.json"),
+    ("Managers/1/VirtualMedia/1", "media/floppy.json")
+  ])
+  .await;
+  assert!(matches!(optical_slot(&bmc.host("media-floppy")).await, Ok(None)));
+
+  let bmc = MockBmc::start(&[]).await;
+  assert!(matches!(
+    optical_slot(&bmc.host("media-none")).await,
+    Err(IloError::Status(StatusCode::NOT_FOUND, _))
+  ));
+}
+
+/// Full sensor record for a threshold sensor owned by the BMC, with `M` as the only conversion factor
+fn full_record(
+  number: u8,
+  sensor_type: u8,
+  name: &str,
+  units: (u8, u8),
+  m: u8,
+  readable: u8,
+  (fatal, critical): (u8, u8)
+) -> Vec<u8> {
+  let mut record = vec![0; 48];
+  record[3] = 0x01;
+  record[4] = (43 + name.len()) as u8;
+  record[5] = 0x20;
+  record[7] = number;
+  record[12] = sensor_type;
+  record[13] = 0x01;
+  record[18] = readable;
+  (record[20], record[21]) = units;
+  record[24] = m;
+  record[36] = fatal;
+  record[37] = critical;
+  record[47] = 0xC0 | name.len() as u8;
+  record.extend(name.as_bytes());
+  record
+}
+
+fn sel_record(
+  kind: u8,
+  timestamp: u32,
+  event: [u8; 4]
+) -> Vec<u8> {
+  let mut record = vec![0x01, 0x00, kind];
+  record.extend(timestamp.to_le_bytes());
+  record.extend([0x20, 0x00, 0x04]);
+  record.extend(event);
+  record.extend([0xFF, 0xFF]);
+  record
+}
+
+#[test]
+fn ipmi_sensor_records() {
+  let temp = SensorRecord::parse(&full_record(0x01, 0x01, "CPU Temp", (0x00, 0x01), 1, 0x30, (95, 85))).expect("full record");
+  let fan = SensorRecord::parse(&full_record(0x02, 0x04, "Fan 1", (0x00, 0x12), 50, 0x00, (0, 0))).expect("full record");
+  let gone = SensorRecord::parse(&full_record(0x03, 0x04, "Fan 2", (0x00, 0x12), 50, 0x00, (0, 0))).expect("full record");
+  assert_eq!((temp.number, temp.name.as_str()), (0x01, "CPU Temp"));
+
+  // Readings come as (raw, flags, threshold state), the last fan's is unavailable
+  let data = sensor_readings(vec![
+    (temp, vec![0x2A, 0xC0, 0x00]),
+    (fan, vec![0x3C, 0xC0, 0x08]),
+    (gone, vec![0x00, 0xE0, 0x00]),
+  ]);
+  let (temps, fans) = sensor_lines(&data, &[], true);
+  assert_eq!(temps, lines(&["**CPU Temp:** `42°C` (crit `85°C`, fatal `95°C`) - OK"]));
+  assert_eq!(fans, lines(&["**Fan 1:** `3000 RPM`"]));
+  assert_eq!(data.fans[0].status.health.as_deref(), Some("Warning"));
+
+  // Two's complement readings and a negative result exponent
+  let mut signed = full_record(0x04, 0x01, "Ambient", (0x80, 0x01), 1, 0x00, (0, 0));
+  assert_eq!(SensorRecord::parse(&signed).and_then(|r| r.convert(0xF6)), Some(-10.0));
+  (signed[20], signed[24], signed[29]) = (0x00, 5, 0xF0);
+  let scaled = SensorRecord::parse(&signed).and_then(|r| r.convert(84)).expect("linear sensor");
+  assert!((scaled - 42.0).abs() < 1e-9);
+
+  // Only full records carry conversion factors
+  let mut compact = full_record(0x05, 0x01, "Compact", (0x00, 0x01), 1, 0x00, (0, 0));
+  compact[3] = 0x02;
+  assert!(SensorRecord::parse(&compact).is_none());
+}
+
+#[test]
+fn ipmi_sel_entries() {
+  let names = HashMap::from([(0x01, "CPU Temp".to_string())]);
+  let entry = |record: Vec<u8>| {
+    let entry = sel_entry(&record, &names).expect("16 byte record");
+    (entry.created, entry.message, entry.severity)
+  };
+  let expect = |created: &str, message: &str, severity: &str| (created.to_string(), message.to_string(), severity.to_string());
+
+  assert_eq!(
+    entry(sel_record(0x02, 1714566896, [0x01, 0x01, 0x01, 0x59])),
+    expect("2024-05-01T12:34:56Z", "CPU Temp: Upper critical going high", "Critical")
+  );
+  assert_eq!(
+    entry(sel_record(0x02, 1704067200, [0x01, 0x01, 0x81, 0x59])),
+    expect("2024-01-01T00:00:00Z", "CPU Temp: Upper critical going high (deasserted)", "OK")
+  );
+  assert_eq!(
+    entry(sel_record(0x02, 1714566896, [0x08, 0x30, 0x6F, 0x01])),
+    expect("2024-05-01T12:34:56Z", "Power supply #48: Failure detected", "Critical")
+  );
+  // Timestamps from before the BMC's clock was set are relative to its boot
+  assert_eq!(
+    entry(sel_record(0x02, 0x100, [0x0C, 0x10, 0x6F, 0x00])),
+    expect("", "Memory #16: Correctable ECC", "Warning")
+  );
+  assert_eq!(
+    entry(sel_record(0xE0, 0x04030201, [0x05, 0x06, 0x07, 0x08])),
+    expect("", "OEM record 0xE0: 0102030420000405060708FFFF", "OK")
+  );
+  assert!(sel_entry(&[0x00; 15], &names).is_none());
+}
+
+#[test]
+fn ipmi_session_packets() {
+  // Chassis status response from a BMC, encrypted and signed with the keys of
+  // `admin:password` with 00..0F as our random number and 10..1F as the BMC's
+  const RESPONSE: &str =
+    "0600FF0706C00D0C0B0A010000002000A5A5A5A5A5A5A5A5A5A5A5A5A5A5A5A57B4BCC3A55E4310B8278AA1355657949FFFF0207EB66D28D8A038589C756239C";
+  let console_random: [u8; 16] = std::array::from_fn(|i| i as u8);
+  let bmc_random: [u8; 16] = std::array::from_fn(|i| i as u8 + 16);
+  let keys = SessionKeys::derive(b"password", &console_random, &bmc_random, b"admin");
+
+  let packet = hex::decode(RESPONSE).unwrap();
+  let msg = keys.open(&packet).expect("valid packet");
+  assert_eq!(parse_response(&msg, 0x00, 0x01, 1), Some((0x00, vec![0x01, 0x00, 0x40])));
+  assert_eq!(parse_response(&msg, 0x00, 0x01, 2), None);
+
+  let mut tampered = packet.clone();
+  tampered[20] ^= 0x01;
+  assert!(keys.open(&tampered).is_none());
+  let other = SessionKeys::derive(b"hunter2", &console_random, &bmc_random, b"admin");
+  assert!(other.open(&packet).is_none());
+
+  let request = ipmi_message(0x00, 0x01, 1, &[]);
+  assert_eq!(request, vec![0x20, 0x00, 0xE0, 0x81, 0x04, 0x01, 0x7A]);
+  for len in 0..40 {
+    let msg: Vec<u8> = (0..len).collect();
+    assert_eq!(keys.open(&keys.seal(0x0A0B0C0D, 1, &msg)).as_deref(), Some(msg.as_slice()), "{len} bytes");
+  }
+}
+
+#[tokio::test]
+async fn redfish_backend() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-ilo4.json"), ("Systems/1", "system-ilo4.json")]).await;
+  let host = bmc.host("backend-redfish");
+  let redfish = backend(&host);
+
+  assert_eq!(redfish.log_name(), "IML");
+  let data = redfish.sensors().await.unwrap_or_else(|e| panic!("{e}"));
+  let (temps, _) = sensor_lines(&data, &[], false);
+  assert_eq!(temps, lines(&["**01-Inlet Ambient:** `21°C`", "**02-CPU 1:** `40°C`"]));
+  assert_eq!(redfish.power_state().await.unwrap_or_else(|e| panic!("{e}")), "On");
+
+  redfish.power_control(ResetType::ForceRestart).await.unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(
+    bmc.posts(),
+    vec![(
+      "Systems/1/Actions/ComputerSystem.Reset".to_string(),
+      serde_json::json!({ "ResetType": "ForceRestart" })
+    )]
+  );
+
+  // Hosts spoken to over IPMI never reach for Redfish
+  let mut host = bmc.host("backend-ipmi");
+  host.protocol = BmcProtocol::Ipmi;
+  assert_eq!(backend(&host).log_name(), "SEL");
+  assert!(matches!(
+    ilo_data::<System>(&host, RedfishEndpoint::System).await,
+    Err(IloError::Unsupported)
+  ));
+}
+
+#[tokio::test]
+async fn error_taxonomy() {
+  let bmc = MockBmc::start(&[("Chassis/1/Thermal", "thermal-ilo4.json")]).await;
+  let host = bmc.host("errors-ok");
+
+  let missing = redfish_get::<Chassis>(&host, "Chassis/9/Thermal").await;
+  assert!(matches!(missing, Err(IloError::Status(StatusCode::NOT_FOUND, _))));
+
+  let mismatched = redfish_get::<Vec<u32>>(&host, "Chassis/1/Thermal").await;
+  assert!(matches!(mismatched, Err(IloError::UnexpectedSchema(_))));
+
+  let wrong_password = bmc.host_with("errors-auth", "hunter2", &[]);
+  let refused = ilo_data::<Chassis>(&wrong_password, RedfishEndpoint::Thermal).await;
+  assert!(matches!(refused, Err(IloError::AuthFailed(_))));
+
+  let mut offline = bmc.host("errors-offline");
+  offline.address = "127.0.0.1:1".to_string();
+  let unreachable = ilo_data::<Chassis>(&offline, RedfishEndpoint::Thermal).await;
+  assert!(matches!(unreachable, Err(IloError::Unreachable(_))));
+
+  // The details name the BMC, what users get to see doesn't
+  for err in [missing.err(), mismatched.err(), refused.err(), unreachable.err()].into_iter().flatten() {
+    let shown = err.user_message();
+    assert!(!shown.contains("127.0.0.1") && !shown.contains("redfish"), "{shown}");
+  }
+  assert!(correlation_id().chars().all(|c| c.is_ascii_hexdigit()));
+  assert_ne!(correlation_id(), correlation_id());
+}
+
+#[tokio::test]
+async fn certificate_pinning() {
+  let routes = [("Chassis/1/Thermal", "thermal-ilo4.json")];
+  let bmc = MockBmc::start(&routes).await;
+  let mut host = bmc.host("pin-tofu");
+  host.tls = &IloTls::TrustOnFirstUse;
+
+  ilo_data::<Chassis>(&host, RedfishEndpoint::Thermal)
+    .await
+    .unwrap_or_else(|e| panic!("{e}"));
+  let pins: HashMap<String, String> = serde_json::from_str(&fs::read_to_string(tls::pin_store_path()).unwrap()).unwrap();
+  let pinned = pins.get("pin-tofu").cloned().expect("the certificate should be pinned on first contact");
+
+  // Same host, but now it presents a certificate that isn't the pinned one
+  let replaced = MockBmc::start_presenting(&routes, "mock-cert-replaced.pem", "mock-key-replaced.pem").await;
+  host.address = replaced.address.clone();
+  match ilo_data::<Chassis>(&host, RedfishEndpoint::Thermal).await {
+    Err(IloError::PinMismatch { expected, presented }) => {
+      assert_eq!(expected, pinned);
+      assert_ne!(presented, pinned);
+    },
+    _ => panic!("a replaced certificate should fail the pin")
+  }
+
+  // Configured fingerprints match with colons and in lowercase too
+  let colons: Vec<String> = pinned.as_bytes().chunks(2).map(|c| String::from_utf8_lossy(c).to_lowercase()).collect();
+  let fingerprint: &'static IloTls = Box::leak(Box::new(IloTls::Fingerprint(colons.join(":").leak())));
+
+  let mut configured = bmc.host("pin-fingerprint");
+  configured.tls = fingerprint;
+  assert!(ilo_data::<Chassis>(&configured, RedfishEndpoint::Thermal).await.is_ok());
+
+  let mut stale = replaced.host("pin-fingerprint-stale");
+  stale.tls = fingerprint;
+  assert!(matches!(
+    ilo_data::<Chassis>(&stale, RedfishEndpoint::Thermal).await,
+    Err(IloError::PinMismatch { .. })
+  ));
+
+  // iLO's out of the box certificate: issued by "Default Issuer (Do not trust)", long expired and made out to another name
+  let default = MockBmc::start_presenting(&routes, "mock-cert-ilo-default.pem", "mock-key-ilo-default.pem").await;
+  let mut untrusted = default.host("pin-ilo-default");
+  untrusted.tls = &IloTls::TrustOnFirstUse;
+  let data = ilo_data::<Chassis>(&untrusted, RedfishEndpoint::Thermal)
+    .await
+    .unwrap_or_else(|e| panic!("the pin alone should be enough to trust it: {e}"));
+  assert_eq!(data.temperatures.len(), 3);
+
+  let _ = fs::remove_file(tls::pin_store_path());
+}
+
+/// Needs `ipmi_sim` running with the config in `tests/fixtures/ipmi`, see `lan.conf` there
+async fn events_listener() -> (String, mpsc::Receiver<(&'static str, EventPayload)>) {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let base = format!("http://{}/redfish/events", listener.local_addr().unwrap());
+  let (tx, rx) = mpsc::channel(4);
+  tokio::spawn(serve(listener, None, tx));
+
+  (base, rx)
+}
+
+#[tokio::test]
+async fn mock_bmc_events_reach_the_listener() {
+  let bmc = MockBmc::start(&[]).await;
+  let host = bmc.host("events-push");
+  let (base, mut rx) = events_listener().await;
+
+  subscribe(&host, &base).await.unwrap_or_else(|e| panic!("{e}"));
+  let subs = bmc.subscriptions();
+  assert_eq!(subs.len(), 1);
+  assert_eq!(subs[0].0, format!("{base}/events-push"));
+
+  assert_eq!(bmc.push_event("event-drive-failure.json").await, vec![StatusCode::OK]);
+  let (host_id, payload) = rx.recv().await.unwrap();
+  assert_eq!(host_id, "events-push");
+  assert_eq!(payload.events.len(), 2);
+
+  let lines: Vec<String> = payload.events.iter().map(fmt_event).collect();
+  assert_eq!(
+    lines,
+    vec![
+      "**[Critical:01/06/2024 14:22:05]:** Drive in Port 1I Box 1 Bay 3 has failed.\n-# iLOEvents.2.1.DriveStatusChanged • \
+       Systems/1/SmartStorage/ArrayControllers/0/DiskDrives/2",
+      "**[Warning:01/06/2024 14:22:06]:** Logical drive 1 is degraded.\n-# iLOEvents.2.1.LogicalDriveStatusChanged"
+    ]
+  );
+
+  // Subscribing again replaces the earlier subscription instead of piling up
+  subscribe(&host, &base).await.unwrap_or_else(|e| panic!("{e}"));
+  assert_eq!(bmc.subscriptions().len(), 1);
+
+  let path = SUBSCRIPTIONS.get("events-push").and_then(|s| s.path.clone()).unwrap();
+  redfish_send::<()>(&host, Method::DELETE, &path, None)
+    .await
+    .unwrap_or_else(|e| panic!("{e}"));
+  assert!(bmc.subscriptions().is_empty());
+}
+
+#[tokio::test]
+async fn listener_rejects_unverified_events() {
+  let (base, mut rx) = events_listener().await;
+  SUBSCRIPTIONS.insert(
+    "events-spoofed",
+    Subscription {
+      context: "secret".to_string(),
+      path:    None
+    }
+  );
+
+  let client = Client::new();
+  let post = |host: &str, context: &str| {
+    client
+      .post(format!("{base}/{host}"))
+      .body(format!(r#"{{"Context":"{context}","Events":[{{"Message":"Hi"}}]}}"#))
+      .send()
+  };
+
+  assert_eq!(post("events-spoofed", "guess").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+  assert_eq!(post("events-unknown", "secret").await.unwrap().status(), StatusCode::NOT_FOUND);
+  assert_eq!(
+    client.get(format!("{base}/events-spoofed")).send().await.unwrap().status(),
+    StatusCode::METHOD_NOT_ALLOWED
+  );
+  assert!(rx.try_recv().is_err());
+
+  assert_eq!(post("events-spoofed", "secret").await.unwrap().status(), StatusCode::OK);
+  assert_eq!(rx.recv().await.unwrap().0, "events-spoofed");
+}
+
+#[tokio::test]
+#[ignore]
+async fn ipmi_sim() {
+  let target = std::env::var("KON_IPMI_SIM").expect("KON_IPMI_SIM should be address;username;password");
+  let mut parts = target.splitn(3, ';');
+  let host = BmcHost {
+    id:       "ipmi-sim",
+    name:     "SIM",
+    address:  parts.next().unwrap_or_default().to_string(),
+    username: parts.next().unwrap_or_default().to_string(),
+    password: parts.next().unwrap_or_default().to_string(),
+    protocol: BmcProtocol::Ipmi,
+    tls:      &MOCK_TLS,
+    sensors:  &[]
+  };
+  let sim = backend(&host);
+
+  let data = sim.sensors().await.unwrap_or_else(|e| panic!("{e}"));
+  let (temps, fans) = sensor_lines(&data, &[], true);
+  assert_eq!(temps, lines(&["**CPU Temp:** `42°C` (crit `85°C`, fatal `95°C`) - OK"]));
+  assert_eq!(fans, lines(&["**Fan 1:** `3000 RPM`"]));
+
+  let log = sim.event_log().await.unwrap_or_else(|e| panic!("{e}"));
+  let last = log.entries().last().expect("SEL entry");
+  assert_eq!(
+    (last.message.as_str(), last.severity.as_str()),
+    ("CPU Temp: Upper critical going high", "Critical")
+  );
+
+  let state = sim.power_state().await.unwrap_or_else(|e| panic!("{e}"));
+  assert!(matches!(state.as_str(), "On" | "Off"), "{state}");
+}
+
+#[test]
+fn temperature_hysteresis() {
+  // critical 90, fatal 100, readings have to drop 3°C under a threshold before it clears
+  assert!(temp_level(99, 90, 100, Level::Fatal) == Level::Fatal, "just under fatal stays Fatal");
+  assert!(temp_level(97, 90, 100, Level::Fatal) == Level::Critical);
+  assert!(temp_level(99, 90, 100, Level::Critical) == Level::Critical);
+  assert!(temp_level(100, 90, 100, Level::Normal) == Level::Fatal);
+  assert!(temp_level(88, 90, 100, Level::Critical) == Level::Critical);
+  assert!(temp_level(87, 90, 100, Level::Fatal) == Level::Normal);
+  assert!(temp_level(99, 0, 100, Level::Fatal) == Level::Fatal, "missing critical threshold");
+}
+
+#[test]
+fn long_messages_are_truncated() {
+  let entry = ImlEntry {
+    created:  "2024-01-01T00:00:00Z".to_string(),
+    message:  "é".repeat(400),
+    severity: "OK".to_string()
+  };
+
+  let rendered = fmt_entry(&entry);
+  assert!(rendered.ends_with('…'));
+  assert_eq!(rendered.matches('é').count(), 300);
+}
+
+#[test]
+fn fmt_dt_formats() {
+  assert_eq!(fmt_dt("2024-05-01T12:34:56Z").as_deref(), Some("01/05/2024 12:34:56"));
+  assert_eq!(fmt_dt("2024-05-01T12:34:56+02:00").as_deref(), Some("01/05/2024 12:34:56+02:00"));
+  assert_eq!(fmt_dt("2024-05-01"), None);
+  assert_eq!(fmt_dt("2024-05T12:34:56Z"), None);
+  assert_eq!(fmt_dt(""), None);
+}
+
+#[test]
+fn fields_and_pages_stay_in_limits() {
+  let many: Vec<String> = (0..200).map(|i| format!("**Sensor {i:03}:** `40°C`")).collect();
+  let fields = pack_fields("Temperatures", &many);
+
+  assert!(fields.len() > 1);
+  assert!(fields.iter().all(|(_, value, _)| value.len() <= FIELD_LIMIT));
+  assert_eq!(fields[0].0, format!("Temperatures (1/{})", fields.len()));
+  assert_eq!(fields.iter().map(|(_, v, _)| v.lines().count()).sum::<usize>(), 200);
+
+  let small: Vec<(String, String, bool)> = (0..30).map(|i| (format!("Field {i}"), "value".to_string(), false)).collect();
+  let pages = pack_pages(small);
+  assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![MAX_FIELDS, 5]);
+}
@@ -0,0 +1,352 @@
+//! Redfish service that answers with the recorded fixtures over HTTPS,
+//! enough of one for the commands and the event listener to talk to
+
+use {
+  super::super::{
+    hosts::BmcHost,
+    session::AUTH_TOKEN_HEADER
+  },
+  http_body_util::{
+    BodyExt,
+    Full
+  },
+  hyper::{
+    Method,
+    Request,
+    Response,
+    StatusCode,
+    body::{
+      Bytes,
+      Incoming
+    },
+    header::{
+      AUTHORIZATION,
+      LOCATION
+    },
+    server::conn::http1,
+    service::service_fn
+  },
+  hyper_util::rt::TokioIo,
+  kon_libs::{
+    BmcProtocol,
+    IloTls
+  },
+  serde_json::{
+    Value,
+    json
+  },
+  std::{
+    collections::HashMap,
+    convert::Infallible,
+    fs,
+    sync::{
+      Arc,
+      Mutex
+    }
+  },
+  tokio::net::TcpListener,
+  tokio_native_tls::{
+    TlsAcceptor,
+    native_tls::{
+      self,
+      Identity
+    }
+  }
+};
+
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/redfish");
+
+/// Self-signed for `localhost` and `127.0.0.1`, trusted as the host's CA bundle
+pub static MOCK_TLS: IloTls = IloTls::CaBundle(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/redfish/mock-cert.pem"));
+
+/// `admin:password`
+const BASIC_AUTH: &str = "Basic YWRtaW46cGFzc3dvcmQ=";
+
+pub fn fixture(name: &str) -> String { fs::read_to_string(format!("{FIXTURES}/{name}")).unwrap_or_else(|e| panic!("fixture {name}: {e}")) }
+
+struct MockSubscription {
+  id:          u32,
+  destination: String,
+  context:     String
+}
+
+#[derive(Default)]
+struct State {
+  /// Path under `/redfish/v1/` to the body served for it
+  routes:        HashMap<String, String>,
+  subscriptions: Vec<MockSubscription>,
+  /// Bodies PATCHed to existing routes, in the order they came in
+  patches:       Vec<(String, Value)>,
+  /// Bodies POSTed to actions
+  posts:         Vec<(String, Value)>,
+  next_id:       u32,
+  /// Off by default so the other tests stay on basic auth, once on only tokens are accepted
+  sessions_on:   bool,
+  /// Live sessions as (ID, token)
+  sessions:      Vec<(u32, String)>,
+  logins:        u32
+}
+
+pub struct MockBmc {
+  pub address: String,
+  state:       Arc<Mutex<State>>
+}
+
+fn members(paths: &[&str]) -> String {
+  let members: Vec<Value> = paths.iter().map(|p| json!({ "@odata.id": format!("/redfish/v1/{p}") })).collect();
+  json!({ "Members": members }).to_string()
+}
+
+fn reply(
+  status: StatusCode,
+  body: String
+) -> Response<Full<Bytes>> {
+  let mut res = Response::new(Full::new(Bytes::from(body)));
+  *res.status_mut() = status;
+  res
+}
+
+async fn handle(
+  req: Request<Incoming>,
+  state: Arc<Mutex<State>>
+) -> Response<Full<Bytes>> {
+  let path = req.uri().path().trim_start_matches("/redfish/v1").trim_matches('/').to_string();
+
+  // The service root and logging in are the only things that go without credentials
+  if path.is_empty() {
+    return reply(StatusCode::OK, json!({ "RedfishVersion": "1.6.0" }).to_string());
+  }
+
+  let method = req.method().clone();
+  let token = req.headers().get(AUTH_TOKEN_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string);
+  let basic = req.headers().get(AUTHORIZATION).is_some_and(|v| *v == BASIC_AUTH);
+  let body: Value = match req.into_body().collect().await {
+    Ok(body) => serde_json::from_slice(&body.to_bytes()).unwrap_or(Value::Null),
+    Err(_) => return reply(StatusCode::BAD_REQUEST, String::new())
+  };
+
+  let mut state = state.lock().unwrap();
+  if method == Method::POST && path == "SessionService/Sessions" {
+    return login(&mut state, &body);
+  }
+
+  let authorized = if state.sessions_on {
+    token.is_some_and(|t| state.sessions.iter().any(|(_, live)| *live == t))
+  } else {
+    basic
+  };
+  if !authorized {
+    return reply(StatusCode::UNAUTHORIZED, String::new());
+  }
+
+  let sub_id = path.strip_prefix("EventService/Subscriptions/").and_then(|id| id.parse::<u32>().ok());
+  if method == Method::DELETE
+    && let Some(id) = path.strip_prefix("SessionService/Sessions/").and_then(|id| id.parse::<u32>().ok())
+  {
+    let before = state.sessions.len();
+    state.sessions.retain(|(live, _)| *live != id);
+    return if state.sessions.len() < before {
+      reply(StatusCode::NO_CONTENT, String::new())
+    } else {
+      reply(StatusCode::NOT_FOUND, String::new())
+    };
+  }
+
+  match (method, sub_id) {
+    (Method::GET, _) if path == "EventService/Subscriptions" => {
+      let paths: Vec<String> = state.subscriptions.iter().map(|s| format!("{path}/{}", s.id)).collect();
+      reply(StatusCode::OK, members(&paths.iter().map(String::as_str).collect::<Vec<_>>()))
+    },
+    (Method::POST, _) if path == "EventService/Subscriptions" => {
+      state.next_id += 1;
+      let id = state.next_id;
+      state.subscriptions.push(MockSubscription {
+        id,
+        destination: body["Destination"].as_str().unwrap_or_default().to_string(),
+        context: body["Context"].as_str().unwrap_or_default().to_string()
+      });
+
+      let mut res = reply(StatusCode::CREATED, String::new());
+      res
+        .headers_mut()
+        .insert(LOCATION, format!("/redfish/v1/EventService/Subscriptions/{id}/").parse().unwrap());
+      res
+    },
+    (Method::GET, Some(id)) => match state.subscriptions.iter().find(|s| s.id == id) {
+      Some(sub) => reply(
+        StatusCode::OK,
+        json!({ "Id": id.to_string(), "Destination": sub.destination }).to_string()
+      ),
+      None => reply(StatusCode::NOT_FOUND, String::new())
+    },
+    (Method::DELETE, Some(id)) => {
+      let before = state.subscriptions.len();
+      state.subscriptions.retain(|s| s.id != id);
+      if state.subscriptions.len() < before {
+        reply(StatusCode::OK, String::new())
+      } else {
+        reply(StatusCode::NOT_FOUND, String::new())
+      }
+    },
+    (Method::POST, _) if path.contains("/Actions/") => {
+      state.posts.push((path, body));
+      reply(StatusCode::OK, String::new())
+    },
+    (Method::PATCH, _) if state.routes.contains_key(&path) => {
+      state.patches.push((path, body));
+      reply(StatusCode::OK, String::new())
+    },
+    (Method::GET, _) => match state.routes.get(&path) {
+      Some(body) => reply(StatusCode::OK, body.clone()),
+      None => reply(StatusCode::NOT_FOUND, String::new())
+    },
+    _ => reply(StatusCode::METHOD_NOT_ALLOWED, String::new())
+  }
+}
+
+/// Hand out a token and the session's location like iLO does, or 405 while sessions are off
+fn login(
+  state: &mut State,
+  body: &Value
+) -> Response<Full<Bytes>> {
+  if !state.sessions_on {
+    return reply(StatusCode::METHOD_NOT_ALLOWED, String::new());
+  }
+  if body["UserName"] != "admin" || body["Password"] != "password" {
+    return reply(StatusCode::UNAUTHORIZED, String::new());
+  }
+
+  state.logins += 1;
+  state.next_id += 1;
+  let (id, token) = (state.next_id, format!("mock-token-{}", state.next_id));
+  state.sessions.push((id, token.clone()));
+
+  let mut res = reply(StatusCode::CREATED, String::new());
+  res.headers_mut().insert(AUTH_TOKEN_HEADER, token.parse().unwrap());
+  res
+    .headers_mut()
+    .insert(LOCATION, format!("/redfish/v1/SessionService/Sessions/{id}/").parse().unwrap());
+  res
+}
+
+impl MockBmc {
+  /// Serve the fixtures at the given paths, along with the collections discovery walks through
+  pub async fn start(routes: &[(&str, &str)]) -> Self { Self::start_presenting(routes, "mock-cert.pem", "mock-key.pem").await }
+
+  /// Same as [`MockBmc::start`] but with another certificate and key out of the fixtures
+  pub async fn start_presenting(
+    routes: &[(&str, &str)],
+    cert: &str,
+    key: &str
+  ) -> Self {
+    let mut state = State::default();
+    for (path, body) in [
+      ("Systems", members(&["Systems/1"])),
+      ("Chassis", members(&["Chassis/1"])),
+      ("Managers", members(&["Managers/1"])),
+      ("Systems/1/LogServices", members(&["Systems/1/LogServices/IML"])),
+      ("Managers/1/LogServices", members(&["Managers/1/LogServices/IEL"]))
+    ] {
+      state.routes.insert(path.to_string(), body);
+    }
+    for (path, name) in routes {
+      state.routes.insert(path.to_string(), fixture(name));
+    }
+
+    let identity = Identity::from_pkcs8(fixture(cert).as_bytes(), fixture(key).as_bytes()).unwrap();
+    let acceptor: TlsAcceptor = native_tls::TlsAcceptor::new(identity).unwrap().into();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let state = Arc::new(Mutex::new(state));
+
+    let shared = state.clone();
+    tokio::spawn(async move {
+      while let Ok((stream, _)) = listener.accept().await {
+        let (acceptor, state) = (acceptor.clone(), shared.clone());
+        tokio::spawn(async move {
+          let Ok(stream) = acceptor.accept(stream).await else { return };
+          let service = service_fn(move |req| {
+            let state = state.clone();
+            async move { Ok::<_, Infallible>(handle(req, state).await) }
+          });
+          let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+        });
+      }
+    });
+
+    Self { address, state }
+  }
+
+  /// Host pointed at this BMC, IDs have to be unique per test since clients and IDs are cached by them
+  pub fn host(
+    &self,
+    id: &'static str
+  ) -> BmcHost {
+    self.host_with(id, "password", &[])
+  }
+
+  pub fn host_with(
+    &self,
+    id: &'static str,
+    password: &str,
+    sensors: &'static [(&'static str, &'static str)]
+  ) -> BmcHost {
+    BmcHost {
+      id,
+      name: "MOCK",
+      address: self.address.clone(),
+      username: "admin".to_string(),
+      password: password.to_string(),
+      protocol: BmcProtocol::Redfish,
+      tls: &MOCK_TLS,
+      sensors
+    }
+  }
+
+  /// Registered subscriptions as (destination, context)
+  pub fn subscriptions(&self) -> Vec<(String, String)> {
+    let state = self.state.lock().unwrap();
+    state.subscriptions.iter().map(|s| (s.destination.clone(), s.context.clone())).collect()
+  }
+
+  /// PATCHes received so far as (path, body)
+  pub fn patches(&self) -> Vec<(String, Value)> { self.state.lock().unwrap().patches.clone() }
+
+  /// Actions POSTed so far as (path, body)
+  pub fn posts(&self) -> Vec<(String, Value)> { self.state.lock().unwrap().posts.clone() }
+
+  /// Start handing out session tokens, basic auth is turned away from then on
+  pub fn enable_sessions(&self) { self.state.lock().unwrap().sessions_on = true; }
+
+  /// Drop every live session like a BMC timing them out or resetting
+  pub fn expire_sessions(&self) { self.state.lock().unwrap().sessions.clear(); }
+
+  /// Logins so far and sessions still live
+  pub fn sessions(&self) -> (u32, usize) {
+    let state = self.state.lock().unwrap();
+    (state.logins, state.sessions.len())
+  }
+
+  /// POST the event fixture to every subscriber like a BMC would, `{context}` is filled in per subscription
+  pub async fn push_event(
+    &self,
+    name: &str
+  ) -> Vec<StatusCode> {
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+
+    for (destination, context) in self.subscriptions() {
+      let res = client
+        .post(&destination)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(fixture(name).replace("{context}", &context))
+        .send()
+        .await
+        .unwrap();
+      statuses.push(res.status());
+    }
+
+    statuses
+  }
+}
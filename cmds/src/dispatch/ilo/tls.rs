@@ -0,0 +1,244 @@
+use {
+  super::{
+    IloError,
+    hosts::BmcHost
+  },
+  dashmap::DashMap,
+  kon_libs::IloTls,
+  reqwest::{
+    Certificate,
+    Client,
+    ClientBuilder,
+    tls::TlsInfo
+  },
+  rustls::{
+    CertificateError,
+    ClientConfig,
+    DigitallySignedStruct,
+    SignatureScheme,
+    client::danger::{
+      HandshakeSignatureValid,
+      ServerCertVerified,
+      ServerCertVerifier
+    },
+    crypto::{
+      CryptoProvider,
+      ring,
+      verify_tls12_signature,
+      verify_tls13_signature
+    },
+    pki_types::{
+      CertificateDer,
+      ServerName,
+      UnixTime
+    }
+  },
+  sha2::{
+    Digest,
+    Sha256
+  },
+  std::{
+    collections::HashMap,
+    fs,
+    sync::{
+      Arc,
+      LazyLock,
+      Mutex
+    }
+  },
+  tokio::time::Duration
+};
+
+/// Pinned clients, one per host since each trusts its own certificate
+static CLIENTS: LazyLock<DashMap<&'static str, Client>> = LazyLock::new(DashMap::new);
+static PIN_STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where the trust-on-first-use fingerprints are kept, override with `KON_ILO_PINS`
+pub fn pin_store_path() -> String { std::env::var("KON_ILO_PINS").unwrap_or_else(|_| default_pin_store()) }
+
+#[cfg(not(test))]
+fn default_pin_store() -> String { "ilo_pins.json".to_string() }
+
+/// Tests pin the mock BMCs outside the working directory
+#[cfg(test)]
+fn default_pin_store() -> String {
+  std::env::temp_dir()
+    .join(format!("kon_ilo_pins.{}.json", std::process::id()))
+    .to_string_lossy()
+    .to_string()
+}
+
+fn base_client() -> ClientBuilder {
+  ClientBuilder::new()
+    .timeout(Duration::from_secs(15))
+    .pool_max_idle_per_host(6)
+    .pool_idle_timeout(Some(Duration::from_secs(30)))
+    .tcp_keepalive(Duration::from_secs(600))
+}
+
+fn fingerprint(der: &[u8]) -> String { hex::encode_upper(Sha256::digest(der)) }
+
+/// Accept fingerprints with or without colons and in either case
+fn normalize(fp: &str) -> String { fp.chars().filter(char::is_ascii_hexdigit).collect::<String>().to_uppercase() }
+
+fn load_pins() -> HashMap<String, String> {
+  fs::read_to_string(pin_store_path())
+    .ok()
+    .and_then(|s| serde_json::from_str(&s).ok())
+    .unwrap_or_default()
+}
+
+fn save_pin(
+  host_id: &str,
+  fp: &str
+) {
+  let _guard = PIN_STORE_LOCK.lock().unwrap();
+  let mut pins = load_pins();
+  pins.insert(host_id.to_string(), fp.to_string());
+
+  let result = serde_json::to_string_pretty(&pins)
+    .map_err(|e| e.to_string())
+    .and_then(|json| fs::write(pin_store_path(), json).map_err(|e| e.to_string()));
+
+  if let Err(e) = result {
+    eprintln!("iLO[TLS] Couldn't save the pin for \"{host_id}\": {e}");
+  }
+}
+
+/// Grab the certificate the BMC presents, no credentials are sent along
+async fn probe_certificate(host: &BmcHost) -> Result<Vec<u8>, IloError> {
+  let probe = base_client().danger_accept_invalid_certs(true).tls_info(true).build()?;
+  let res = probe.get(format!("https://{}/redfish/v1/", host.address)).send().await?;
+
+  res
+    .extensions()
+    .get::<TlsInfo>()
+    .and_then(|info| info.peer_certificate())
+    .map(|der| der.to_vec())
+    .ok_or_else(|| IloError::Tls("BMC didn't present a certificate".to_string()))
+}
+
+/// Check the presented certificate against the host's pin, pins it if nothing's known yet. Returns the pinned fingerprint
+async fn verified_fingerprint(host: &BmcHost) -> Result<String, IloError> {
+  let der = probe_certificate(host).await?;
+  let presented = fingerprint(&der);
+
+  let expected = match host.tls {
+    IloTls::Fingerprint(fp) => Some(normalize(fp)),
+    _ => load_pins().get(host.id).map(|fp| normalize(fp))
+  };
+
+  match expected {
+    Some(expected) if expected != presented => return Err(IloError::PinMismatch { expected, presented }),
+    Some(_) => (),
+    None => {
+      println!("iLO[TLS] Pinned {}'s certificate on first use ({presented})", host.name);
+      save_pin(host.id, &presented);
+    }
+  }
+
+  Ok(presented)
+}
+
+/// Trusts the one certificate with the pinned fingerprint and nothing else. BMCs tend to present self-signed or
+/// "Default Issuer (Do not trust)" certificates that are long expired and made out to another name, so the chain,
+/// dates and hostname aren't looked at, only the handshake signature is still checked against the certificate
+#[derive(Debug)]
+struct PinVerifier {
+  fingerprint: String,
+  provider:    Arc<CryptoProvider>
+}
+
+impl ServerCertVerifier for PinVerifier {
+  fn verify_server_cert(
+    &self,
+    end_entity: &CertificateDer<'_>,
+    _intermediates: &[CertificateDer<'_>],
+    _server_name: &ServerName<'_>,
+    _ocsp_response: &[u8],
+    _now: UnixTime
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    if fingerprint(end_entity) == self.fingerprint {
+      Ok(ServerCertVerified::assertion())
+    } else {
+      Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure))
+    }
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> { self.provider.signature_verification_algorithms.supported_schemes() }
+}
+
+fn pinned_config(fingerprint: String) -> Result<ClientConfig, IloError> {
+  let provider = Arc::new(ring::default_provider());
+  let verifier = PinVerifier {
+    fingerprint,
+    provider: provider.clone()
+  };
+
+  Ok(
+    ClientConfig::builder_with_provider(provider)
+      .with_safe_default_protocol_versions()
+      .map_err(|e| IloError::Tls(e.to_string()))?
+      .dangerous()
+      .with_custom_certificate_verifier(Arc::new(verifier))
+      .with_no_client_auth()
+  )
+}
+
+/// Client that only trusts the host's pinned certificate or configured CA bundle
+pub async fn client_for(host: &BmcHost) -> Result<Client, IloError> {
+  if let Some(client) = CLIENTS.get(host.id) {
+    return Ok(client.clone());
+  }
+
+  let client = match host.tls {
+    IloTls::CaBundle(path) => {
+      let pem = fs::read(path).map_err(|e| IloError::Tls(format!("Couldn't read the CA bundle: {e}")))?;
+
+      let mut builder = base_client().tls_built_in_root_certs(false);
+      for cert in Certificate::from_pem_bundle(&pem)? {
+        builder = builder.add_root_certificate(cert);
+      }
+      builder.build()?
+    },
+    _ => base_client()
+      .use_preconfigured_tls(pinned_config(verified_fingerprint(host).await?)?)
+      .build()?
+  };
+
+  CLIENTS.insert(host.id, client.clone());
+  Ok(client)
+}
+
+/// After a failed connection, check whether the BMC started presenting a different certificate
+pub async fn recheck_pin(host: &BmcHost) -> Option<IloError> {
+  if matches!(host.tls, IloTls::CaBundle(_)) {
+    return None;
+  }
+
+  match verified_fingerprint(host).await {
+    Err(e @ IloError::PinMismatch { .. }) => {
+      CLIENTS.remove(host.id);
+      Some(e)
+    },
+    _ => None
+  }
+}
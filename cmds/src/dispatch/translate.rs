@@ -1,12 +1,52 @@
+mod budget;
+mod cache;
+mod prefs;
+mod provider;
+mod segments;
+#[cfg(test)]
+mod tests;
+
 use {
-  kon_libs::KonResult,
+  budget::{
+    Scope,
+    warn_usage,
+    within_reserve
+  },
+  cache::{
+    cached,
+    hit_rate,
+    translate_cached
+  },
+  kon_libs::{
+    BINARY_PROPERTIES,
+    KonResult,
+    PoiseCtx
+  },
   poise::{
     CreateReply,
-    serenity_prelude::Message
+    serenity_prelude::{
+      AutocompleteChoice,
+      CreateAttachment,
+      CreateAutocompleteResponse,
+      Http,
+      Message
+    }
   },
-  serde::{
-    Deserialize,
-    Serialize
+  prefs::{
+    default_target,
+    remember_target
+  },
+  provider::{
+    Provider,
+    Translator,
+    providers
+  },
+  segments::{
+    Part,
+    Segment,
+    Segments,
+    message_segments,
+    render
   },
   std::{
     collections::HashMap,
@@ -20,34 +60,11 @@ use {
 static REQWEST_: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
 static LOCALE_CACHE: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
 
-#[derive(Serialize)]
-struct DeepLRequest {
-  text:        Vec<String>,
-  target_lang: &'static str
-}
-
-#[derive(Deserialize)]
-struct DeepLResponse {
-  translations: Vec<Translation>
-}
+/// Discord's limit for a message's content
+const MESSAGE_LIMIT: usize = 2000;
 
-#[derive(Deserialize)]
-struct DeepLLanguage {
-  language: String,
-  name:     String
-}
-
-#[derive(Deserialize)]
-struct DeepLUsage {
-  character_count: u64,
-  character_limit: u64
-}
-
-#[derive(Deserialize)]
-struct Translation {
-  text:                     String,
-  detected_source_language: String
-}
+/// Used when the user hasn't remembered a target of their own
+const DEFAULT_TARGET: &str = "EN";
 
 fn prettify_nums(num: u64) -> String {
   let mut s = String::new();
@@ -64,173 +81,309 @@ fn prettify_nums(num: u64) -> String {
   s
 }
 
-/// Translate a given message using DeepL
-#[poise::command(
-  context_menu_command = "Translate via DeepL",
-  install_context = "Guild|User",
-  interaction_context = "Guild|BotDm|PrivateChannel"
-)]
-pub async fn translate(
-  ctx: super::PoiseCtx<'_>,
-  message: Message
-) -> KonResult<()> {
-  let content = message.content.trim();
-  if content.is_empty() {
-    ctx.send(CreateReply::new().content("Nothing to translate!").ephemeral(true)).await?;
-    return Ok(());
+/// Match a language by its code or name, sources only take the base codes
+fn resolve_lang(
+  input: &str,
+  source: bool
+) -> Option<String> {
+  let input = input.trim();
+  let matches = |code: &str, name: &str| code.eq_ignore_ascii_case(input) || name.eq_ignore_ascii_case(input);
+
+  if !source
+    && let Ok(cache) = LOCALE_CACHE.read()
+    && let Some((code, _)) = cache.iter().find(|(code, name)| matches(code, name))
+  {
+    return Some(code.clone());
   }
 
-  let deepl_key = std::env::var("KON_DEEPL").expect("No 'KON_DEEPL' key found!");
-  if deepl_key.is_empty() {
-    ctx
-      .send(
-        CreateReply::new()
-          .content("Can't translate this message, see console for more info!")
-          .ephemeral(true)
-      )
-      .await?;
-    return Ok(());
+  LOCALE_LOOKUP
+    .iter()
+    .find(|(code, name)| matches(code, name))
+    .map(|(code, _)| code.to_string())
+}
+
+/// Languages whose code or name contains what's been typed so far, sorted by name
+fn lang_choices(
+  partial: &str,
+  source: bool
+) -> CreateAutocompleteResponse<'static> {
+  let partial = partial.to_lowercase();
+  let mut langs: Vec<(String, String)> = match LOCALE_CACHE.read() {
+    Ok(cache) if !source && !cache.is_empty() => cache.iter().map(|(c, n)| (c.clone(), n.clone())).collect(),
+    _ => LOCALE_LOOKUP.iter().map(|(c, n)| (c.to_string(), n.to_string())).collect()
+  };
+  langs.retain(|(code, name)| code.to_lowercase().contains(&partial) || name.to_lowercase().contains(&partial));
+  langs.sort_by(|a, b| a.1.cmp(&b.1));
+
+  let choices: Vec<AutocompleteChoice> = langs
+    .into_iter()
+    .take(25)
+    .map(|(code, name)| AutocompleteChoice::new(format!("{name} ({code})"), code))
+    .collect();
+
+  CreateAutocompleteResponse::new().set_choices(choices)
+}
+
+async fn autocomplete_source<'a>(
+  _: PoiseCtx<'_>,
+  partial: &'a str
+) -> CreateAutocompleteResponse<'a> {
+  lang_choices(partial, true)
+}
+
+async fn autocomplete_target<'a>(
+  _: PoiseCtx<'_>,
+  partial: &'a str
+) -> CreateAutocompleteResponse<'a> {
+  warm_locale_cache().await;
+  lang_choices(partial, false)
+}
+
+/// Fill the cache so regional targets like `EN-GB` are known, falls back to [`LOCALE_LOOKUP`] if no provider can be asked
+async fn warm_locale_cache() {
+  if !LOCALE_CACHE.read().unwrap().is_empty() {
+    return;
   }
 
-  if LOCALE_CACHE.read().unwrap().is_empty() {
-    update_locale_cache(&deepl_key).await.expect("Failed to update locale cache...");
+  if let Err(e) = update_locale_cache().await {
+    eprintln!("Translate[Locales] Couldn't fetch the languages: {e}");
   }
+}
 
+/// Translate a given message, DeepL first with LibreTranslate to fall back on
+#[poise::command(
+  context_menu_command = "Translate",
+  install_context = "Guild|User",
+  interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn translate_message(
+  ctx: PoiseCtx<'_>,
+  message: Message
+) -> KonResult<()> {
+  // Text attachments are downloaded one by one, that alone can outlast Discord's 3 seconds to answer
   ctx.defer().await?;
+  let target = default_target(ctx.author().id).await.unwrap_or_else(|| DEFAULT_TARGET.to_string());
+  let segments = message_segments(&message).await;
+  run_translation(ctx, segments.list, None, &target).await
+}
 
-  let api_url = if deepl_key.ends_with(":fx") {
-    "https://api-free.deepl.com"
-  } else {
-    "https://api.deepl.com"
-  };
-
-  let resp = match REQWEST_
-    .post(format!("{api_url}/v2/translate"))
-    .header("User-Agent", "kon/reqwest")
-    .header("Authorization", format!("DeepL-Auth-Key {deepl_key}"))
-    .header("Content-Type", "application/json")
-    .json(&DeepLRequest {
-      text:        vec![content.to_owned()],
-      target_lang: "EN"
-    })
-    .send()
-    .await
-  {
-    Ok(r) => r,
-    Err(e) => {
-      ctx
-        .send(CreateReply::new().content(format!("**(DeepL) Service error:** {e}")).ephemeral(true))
-        .await?;
-      return Ok(());
-    }
-  };
-
-  match resp.status().as_u16() {
-    200 => (),
-    403 => {
-      ctx
-        .send(CreateReply::new().content("Not authenticated to DeepL API").ephemeral(true))
-        .await?;
-      return Ok(());
-    },
-    429 => {
-      ctx
-        .send(CreateReply::new().content("DeepL requests ratelimited, slow down!").ephemeral(true))
-        .await?;
-      return Ok(());
-    },
-    456 => {
-      ctx
-        .send(
-          CreateReply::new()
-            .content("Quota exceeded, used up all of 500k characters for this month!\nConsider upgrading to **Pro** if still relying on DeepL!")
-            .ephemeral(true)
-        )
-        .await?;
-      return Ok(());
-    },
-    500 => {
+/// Translate some text
+#[poise::command(slash_command, install_context = "Guild|User", interaction_context = "Guild|BotDm|PrivateChannel")]
+pub async fn translate(
+  ctx: PoiseCtx<'_>,
+  #[description = "Text to translate"] text: String,
+  #[description = "Language it's written in, detected if left out"]
+  #[autocomplete = "autocomplete_source"]
+  source: Option<String>,
+  #[description = "Language to translate to, defaults to your remembered one"]
+  #[autocomplete = "autocomplete_target"]
+  target: Option<String>,
+  #[description = "Remember the target for the context menu and later translations"] remember: Option<bool>
+) -> KonResult<()> {
+  let source = match source.as_deref().map(|s| (s, resolve_lang(s, true))) {
+    None => None,
+    Some((_, Some(code))) => Some(code),
+    Some((input, None)) => {
       ctx
         .send(
           CreateReply::new()
-            .content("DeepL service gave an internal server error, try again later!")
+            .content(format!("`{input}` isn't a language that can be translated from!"))
             .ephemeral(true)
         )
         .await?;
       return Ok(());
-    },
-    _ => {
+    }
+  };
+
+  warm_locale_cache().await;
+  let target = match target.as_deref().map(|t| (t, resolve_lang(t, false))) {
+    None => default_target(ctx.author().id).await.unwrap_or_else(|| DEFAULT_TARGET.to_string()),
+    Some((_, Some(code))) => code,
+    Some((input, None)) => {
       ctx
         .send(
           CreateReply::new()
-            .content(format!("Unknown status code, DeepL returned with HTTP {}", resp.status().as_u16()))
+            .content(format!("`{input}` isn't a language that can be translated to!"))
             .ephemeral(true)
         )
         .await?;
       return Ok(());
     }
+  };
+
+  if remember.unwrap_or(false) {
+    remember_target(ctx.author().id, &target).await;
   }
 
-  let translation: DeepLResponse = match resp.json().await {
-    Ok(d) => d,
-    Err(e) => {
-      ctx
-        .send(CreateReply::new().content(format!("**(Kon) Parsing error:** {e}")).ephemeral(true))
-        .await?;
+  ctx.defer().await?;
+  let mut segments = Segments::default();
+  segments.push(Part::Content, false, &text);
+  run_translation(ctx, segments.list, source, &target).await
+}
+
+/// Translate every segment and reply with them laid out like the original, errors are only shown to the invoking user.
+/// The interaction has to be deferred already
+async fn run_translation(
+  ctx: PoiseCtx<'_>,
+  segments: Vec<Segment>,
+  source: Option<String>,
+  target: &str
+) -> KonResult<()> {
+  if segments.is_empty() {
+    ctx.send(CreateReply::new().content("Nothing to translate!").ephemeral(true)).await?;
+    return Ok(());
+  }
+
+  let providers = providers();
+  if providers.is_empty() {
+    eprintln!("Translate: no providers configured, set 'KON_DEEPL' or 'KON_LIBRETRANSLATE'");
+    ctx
+      .send(
+        CreateReply::new()
+          .content("Can't translate this message, see console for more info!")
+          .ephemeral(true)
+      )
+      .await?;
+    return Ok(());
+  }
+
+  let text: Vec<String> = segments.iter().map(|s| s.text.clone()).collect();
+  let cached = cached(&text, source.as_deref(), target).await;
+  let chars = text
+    .iter()
+    .zip(&cached)
+    .filter(|(_, c)| c.is_none())
+    .map(|(t, _)| t.chars().count() as u64)
+    .sum();
+  let mut scopes = vec![Scope::User(ctx.author().id)];
+  if let Some(guild) = ctx.guild_id() {
+    scopes.push(Scope::Guild(guild));
+  }
+  // Handed back when dropped, unless the characters made it to a provider
+  let mut reservation = match budget::reserve(&BINARY_PROPERTIES.translate_budget, &scopes, chars) {
+    Ok(r) => r,
+    Err(refusal) => {
+      ctx.send(CreateReply::new().content(refusal).ephemeral(true)).await?;
       return Ok(());
     }
   };
 
-  if let Some(translation) = translation.translations.first() {
-    let quota_info = match get_quota(&deepl_key).await {
-      Ok(u) => &format!("-# **Quota: {}/{}**", prettify_nums(u.character_count), prettify_nums(u.character_limit)),
-      Err(_) => "-# *Failed to check the quota!*"
-    };
-
+  warm_locale_cache().await;
+  let providers = within_reserve(providers, chars).await;
+  if providers.is_empty() {
     ctx
       .send(
-        CreateReply::new().content(
-          [
-            format!("**Translated from {}**", prettify_lang(translation.detected_source_language.as_str())),
-            quota_info.to_string(),
-            format!("```\n{}\n```", translation.text)
-          ]
-          .join("\n")
-        )
+        CreateReply::new()
+          .content("The translation budget is spent for now, try again later!")
+          .ephemeral(true)
       )
       .await?;
-  } else {
+    return Ok(());
+  }
+
+  let (provider, translations) = match translate_cached(&providers, &text, cached, source.as_deref(), target).await {
+    Ok(r) => r,
+    Err((provider, e, sent)) => {
+      reservation.keep(sent);
+      eprintln!("Translate[{}] {e}", provider.name());
+      ctx.send(CreateReply::new().content(e.describe(provider.name())).ephemeral(true)).await?;
+      return Ok(());
+    }
+  };
+  reservation.keep(chars);
+
+  let Some((first, _)) = translations.first() else {
     ctx
-      .send(CreateReply::new().content("DeepL didn't send translated text back!").ephemeral(true))
+      .send(CreateReply::new().content("No translated text came back!").ephemeral(true))
       .await?;
+    return Ok(());
+  };
+
+  let cached = match translations.iter().filter(|(_, cached)| *cached).count() {
+    0 => "",
+    n if n == translations.len() => " *(cached)*",
+    _ => " *(partly cached)*"
+  };
+  let header = format!(
+    "**Translated from {} to {}**{cached}",
+    prettify_lang(first.detected_source.as_str()),
+    prettify_lang(target)
+  );
+  let quota = quota_line(ctx.http(), provider.unwrap_or(&providers[0])).await;
+
+  let translated: Vec<String> = translations.into_iter().map(|(t, _)| t.text).collect();
+  let mut rendered = render(&segments, &translated, target);
+  let omitted = match rendered.omitted {
+    0 => String::new(),
+    1 => "-# 1 embed left out, it didn't fit in one message".to_string(),
+    n => format!("-# {n} embeds left out, they didn't fit in one message")
+  };
+  let mut content = [header.clone(), quota.clone(), rendered.body.clone(), omitted.clone()]
+    .into_iter()
+    .filter(|l| !l.is_empty())
+    .collect::<Vec<_>>()
+    .join("\n");
+  if content.chars().count() > MESSAGE_LIMIT {
+    content = [header, quota, "-# Too long for a message, attached instead".to_string(), omitted].join("\n");
+    rendered
+      .files
+      .insert(0, CreateAttachment::bytes(rendered.body.into_bytes(), "translation.txt"));
+  }
+
+  let mut reply = CreateReply::new().content(content.trim_end().to_string());
+  for embed in rendered.embeds {
+    reply = reply.embed(embed);
+  }
+  for file in rendered.files {
+    reply = reply.attachment(file);
   }
+  ctx.send(reply).await?;
 
   Ok(())
 }
 
-async fn update_locale_cache(api_key: &str) -> Result<(), reqwest::Error> {
-  let api_url = if api_key.ends_with(":fx") {
-    "https://api-free.deepl.com"
-  } else {
-    "https://api.deepl.com"
+/// Quota of the provider that translated, or the first one when the cache answered, followed by the cache's hit rate.
+/// Crossing into a higher share of the quota gets posted to the logs channel
+async fn quota_line(
+  http: &Http,
+  provider: &Provider
+) -> String {
+  let quota = match provider.usage().await {
+    Ok(Some(u)) => {
+      warn_usage(http, provider.name(), &u).await;
+      format!("**Quota: {}/{}**", prettify_nums(u.character_count), prettify_nums(u.character_limit))
+    },
+    Ok(None) => format!("*Via {}*", provider.name()),
+    Err(_) => "*Failed to check the quota!*".to_string()
   };
 
-  let languages: Vec<DeepLLanguage> = REQWEST_
-    .get(format!("{api_url}/v2/languages"))
-    .header("User-Agent", "kon/reqwest")
-    .header("Authorization", format!("DeepL-Auth-Key {api_key}"))
-    .query(&[("type", "target")])
-    .send()
-    .await?
-    .json()
-    .await?;
-
-  let mut c = LOCALE_CACHE.write().unwrap();
-  for language in languages {
-    c.insert(language.language, language.name);
+  match hit_rate() {
+    (_, 0) => format!("-# {quota}"),
+    (hits, lookups) => format!("-# {quota} • Cache hits: {}% ({hits}/{lookups})", hits * 100 / lookups)
   }
+}
 
-  Ok(())
+/// Fill the cache from the first provider that lists its languages
+async fn update_locale_cache() -> Result<(), String> {
+  let mut last_err = None;
+
+  for provider in providers() {
+    match provider.languages().await {
+      Ok(languages) => {
+        let mut c = LOCALE_CACHE.write().unwrap();
+        for language in languages {
+          c.insert(language.code, language.name);
+        }
+        return Ok(());
+      },
+      Err(e) => last_err = Some(format!("{}: {e}", provider.name()))
+    }
+  }
+
+  match last_err {
+    Some(e) => Err(e),
+    None => Ok(())
+  }
 }
 
 /// List of languages that DeepL supports for translation
@@ -280,22 +433,3 @@ fn prettify_lang(code: &str) -> String {
 
   LOCALE_LOOKUP.get(code).map(|&s| s.to_string()).unwrap_or_else(|| code.to_string())
 }
-
-async fn get_quota(api_key: &str) -> Result<DeepLUsage, reqwest::Error> {
-  let api_url = if api_key.ends_with(":fx") {
-    "https://api-free.deepl.com"
-  } else {
-    "https://api.deepl.com"
-  };
-
-  let usage: DeepLUsage = REQWEST_
-    .get(format!("{api_url}/v2/usage"))
-    .header("User-Agent", "kon/reqwest")
-    .header("Authorization", format!("DeepL-Auth-Key {api_key}"))
-    .send()
-    .await?
-    .json()
-    .await?;
-
-  Ok(usage)
-}
@@ -0,0 +1,221 @@
+use {
+  super::{
+    super::post_kon_logs,
+    prettify_nums,
+    provider::{
+      Provider,
+      Translator,
+      Usage
+    }
+  },
+  dashmap::DashMap,
+  kon_libs::{
+    BINARY_PROPERTIES,
+    TranslateBudget
+  },
+  poise::serenity_prelude::{
+    CreateEmbed,
+    GuildId,
+    Http,
+    Timestamp,
+    UserId
+  },
+  std::{
+    sync::{
+      LazyLock,
+      atomic::{
+        AtomicU8,
+        Ordering
+      }
+    },
+    time::{
+      SystemTime,
+      UNIX_EPOCH
+    }
+  }
+};
+
+/// Share of the provider's limit that gets a warning posted once crossed
+const WARNINGS: [u8; 2] = [75, 90];
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Scope {
+  User(UserId),
+  Guild(GuildId)
+}
+
+/// Characters spent per scope as (day, characters), the count starts over with the day
+static SPENT: LazyLock<DashMap<Scope, (u64, u64)>> = LazyLock::new(DashMap::new);
+/// Highest warning posted so far, falls back once usage drops in a new billing period
+static WARNED: AtomicU8 = AtomicU8::new(0);
+
+fn today() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() / 86400)
+    .unwrap_or_default()
+}
+
+pub fn spent_today(scope: Scope) -> u64 {
+  match SPENT.get(&scope) {
+    Some(entry) if entry.0 == today() => entry.1,
+    _ => 0
+  }
+}
+
+/// Characters held against the daily budgets while a translation is underway, whatever isn't kept goes back once dropped
+pub struct Reservation {
+  scopes: Vec<Scope>,
+  day:    u64,
+  chars:  u64
+}
+
+impl Reservation {
+  /// Keep this many of the held characters, they were sent off to a provider
+  pub fn keep(
+    &mut self,
+    chars: u64
+  ) {
+    self.chars = self.chars.saturating_sub(chars);
+  }
+}
+
+impl Drop for Reservation {
+  fn drop(&mut self) {
+    for scope in &self.scopes {
+      if let Some(mut entry) = SPENT.get_mut(scope)
+        && entry.0 == self.day
+      {
+        entry.1 = entry.1.saturating_sub(self.chars);
+      }
+    }
+  }
+}
+
+/// Hold the characters against the user's and the guild's daily budgets, each one is checked and added under the same lock
+/// so translations running side by side can't both slip under the limit. Cached translations are free, leave them out
+pub fn reserve(
+  budget: &TranslateBudget,
+  scopes: &[Scope],
+  chars: u64
+) -> Result<Reservation, String> {
+  let day = today();
+  let mut reservation = Reservation {
+    scopes: Vec::with_capacity(scopes.len()),
+    day,
+    chars
+  };
+
+  for scope in scopes {
+    let mut entry = SPENT.entry(*scope).or_insert((day, 0));
+    if entry.0 != day {
+      *entry = (day, 0);
+    }
+    if let Some(refusal) = refusal(budget, *scope, entry.1, chars) {
+      // Let go of the entry before the reservation hands back what the earlier scopes took
+      drop(entry);
+      return Err(refusal);
+    }
+    entry.1 += chars;
+    reservation.scopes.push(*scope);
+  }
+
+  Ok(reservation)
+}
+
+/// Why a translation was turned down, as told to the user
+pub fn refusal(
+  budget: &TranslateBudget,
+  scope: Scope,
+  spent: u64,
+  chars: u64
+) -> Option<String> {
+  let limit = match scope {
+    Scope::User(_) => budget.user_daily,
+    Scope::Guild(_) => budget.guild_daily
+  };
+  if limit == 0 || spent + chars <= limit {
+    return None;
+  }
+
+  let left = limit.saturating_sub(spent);
+  Some(match scope {
+    Scope::User(_) => format!(
+      "You've used {} of your {} characters for today, this needs {} and only {} are left. Try again tomorrow!",
+      prettify_nums(spent),
+      prettify_nums(limit),
+      prettify_nums(chars),
+      prettify_nums(left)
+    ),
+    Scope::Guild(_) => format!(
+      "This server has used {} of its {} characters for today, this needs {} and only {} are left. Try again tomorrow!",
+      prettify_nums(spent),
+      prettify_nums(limit),
+      prettify_nums(chars),
+      prettify_nums(left)
+    )
+  })
+}
+
+/// Whether spending this much would eat into the reserve
+pub fn breaches_reserve(
+  usage: &Usage,
+  reserve: u64,
+  chars: u64
+) -> bool {
+  usage.character_count + chars + reserve > usage.character_limit
+}
+
+/// Providers that can take this many characters without eating into the reserve,
+/// ones that don't count characters or can't tell right now are kept
+pub async fn within_reserve(
+  providers: Vec<Provider>,
+  chars: u64
+) -> Vec<Provider> {
+  let reserve = BINARY_PROPERTIES.translate_budget.reserve;
+  let mut kept = Vec::with_capacity(providers.len());
+
+  for provider in providers {
+    match provider.usage().await {
+      Ok(Some(usage)) if breaches_reserve(&usage, reserve, chars) => {
+        eprintln!("Translate[Budget] Skipping {}, it's down to its reserve", provider.name())
+      },
+      _ => kept.push(provider)
+    }
+  }
+
+  kept
+}
+
+/// Highest warning at or under the usage, 0 if none
+pub fn warning_level(usage: &Usage) -> u8 {
+  if usage.character_limit == 0 {
+    return 0;
+  }
+
+  let percent = usage.character_count.saturating_mul(100) / usage.character_limit;
+  WARNINGS.iter().rev().find(|w| percent >= **w as u64).copied().unwrap_or(0)
+}
+
+/// Post to the logs channel when usage crosses into a higher warning
+pub async fn warn_usage(
+  http: &Http,
+  provider: &str,
+  usage: &Usage
+) {
+  let level = warning_level(usage);
+  if level <= WARNED.swap(level, Ordering::Relaxed) {
+    return;
+  }
+
+  let embed = CreateEmbed::new()
+    .color(BINARY_PROPERTIES.embed_color)
+    .timestamp(Timestamp::now())
+    .title(format!("{provider} - Quota at {level}%"))
+    .description(format!(
+      "**{}** of **{}** characters used this billing period",
+      prettify_nums(usage.character_count),
+      prettify_nums(usage.character_limit)
+    ));
+  post_kon_logs(http, embed).await;
+}
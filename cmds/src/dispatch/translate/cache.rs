@@ -0,0 +1,313 @@
+use {
+  super::provider::{
+    Provider,
+    ProviderError,
+    Translation,
+    Translator,
+    translate_with_fallback
+  },
+  sha2::{
+    Digest,
+    Sha256
+  },
+  sqlx::{
+    SqlitePool,
+    sqlite::{
+      SqliteConnectOptions,
+      SqlitePoolOptions
+    }
+  },
+  std::{
+    collections::HashMap,
+    sync::{
+      LazyLock,
+      Mutex,
+      atomic::{
+        AtomicU64,
+        Ordering
+      }
+    },
+    time::{
+      SystemTime,
+      UNIX_EPOCH
+    }
+  },
+  tokio::sync::OnceCell
+};
+
+/// Translations older than this get translated again
+const TTL_SECS: i64 = 7 * 86400;
+/// Least recently used entries go first past this
+const CAPACITY: usize = 5000;
+/// Most texts DeepL takes in one request
+const MAX_BATCH: usize = 50;
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(|| Mutex::new(Cache::new(TTL_SECS, CAPACITY)));
+/// `None` if persistence isn't configured or the database couldn't be opened
+static POOL: OnceCell<Option<SqlitePool>> = OnceCell::const_new();
+static HITS: AtomicU64 = AtomicU64::new(0);
+static LOOKUPS: AtomicU64 = AtomicU64::new(0);
+
+/// Hash of the text with surrounding and repeated whitespace squashed, then the source (`AUTO` if detected) and target
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct CacheKey {
+  text:   String,
+  source: String,
+  target: String
+}
+
+impl CacheKey {
+  pub fn new(
+    text: &str,
+    source: Option<&str>,
+    target: &str
+  ) -> Self {
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    Self {
+      text:   hex::encode(Sha256::digest(normalized.as_bytes())),
+      source: source.unwrap_or("AUTO").to_uppercase(),
+      target: target.to_uppercase()
+    }
+  }
+}
+
+struct Entry {
+  translation: Translation,
+  created:     i64,
+  used:        i64
+}
+
+pub struct Cache {
+  entries:  HashMap<CacheKey, Entry>,
+  ttl:      i64,
+  capacity: usize
+}
+
+impl Cache {
+  pub fn new(
+    ttl: i64,
+    capacity: usize
+  ) -> Self {
+    Self {
+      entries: HashMap::new(),
+      ttl,
+      capacity
+    }
+  }
+
+  pub fn get(
+    &mut self,
+    key: &CacheKey,
+    now: i64
+  ) -> Option<Translation> {
+    match self.entries.get_mut(key) {
+      Some(entry) if now - entry.created < self.ttl => {
+        entry.used = now;
+        Some(entry.translation.clone())
+      },
+      Some(_) => {
+        self.entries.remove(key);
+        None
+      },
+      None => None
+    }
+  }
+
+  pub fn insert(
+    &mut self,
+    key: CacheKey,
+    translation: Translation,
+    created: i64,
+    now: i64
+  ) {
+    if now - created >= self.ttl {
+      return;
+    }
+
+    self.entries.insert(
+      key,
+      Entry {
+        translation,
+        created,
+        used: now
+      }
+    );
+
+    if self.entries.len() > self.capacity {
+      self.entries.retain(|_, e| now - e.created < self.ttl);
+    }
+    while self.entries.len() > self.capacity {
+      let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.used).map(|(k, _)| k.clone()) else {
+        break
+      };
+      self.entries.remove(&oldest);
+    }
+  }
+}
+
+fn now() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default()
+}
+
+/// Set `KON_TRANSLATE_CACHE` to a database path to keep the cache across restarts
+async fn pool() -> Option<&'static SqlitePool> {
+  POOL
+    .get_or_init(|| async {
+      let path = std::env::var("KON_TRANSLATE_CACHE").ok().filter(|p| !p.is_empty())?;
+      match open(&path).await {
+        Ok(pool) => Some(pool),
+        Err(e) => {
+          eprintln!("Translate[Cache] Couldn't open {path}, only caching in memory: {e}");
+          None
+        }
+      }
+    })
+    .await
+    .as_ref()
+}
+
+/// Open the database and load what hasn't expired yet into memory
+async fn open(path: &str) -> Result<SqlitePool, sqlx::Error> {
+  let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+  let pool = SqlitePoolOptions::new().max_connections(2).connect_with(options).await?;
+
+  sqlx::query(
+    "CREATE TABLE IF NOT EXISTS translations (text TEXT NOT NULL, source TEXT NOT NULL, target TEXT NOT NULL, translated TEXT NOT NULL, detected \
+     TEXT NOT NULL, created INTEGER NOT NULL, PRIMARY KEY (text, source, target))"
+  )
+  .execute(&pool)
+  .await?;
+
+  let now = now();
+  let rows: Vec<(String, String, String, String, String, i64)> =
+    sqlx::query_as("SELECT text, source, target, translated, detected, created FROM translations WHERE created >= ? ORDER BY created DESC LIMIT ?")
+      .bind(now - TTL_SECS)
+      .bind(CAPACITY as i64)
+      .fetch_all(&pool)
+      .await?;
+
+  let mut cache = CACHE.lock().unwrap();
+  for (text, source, target, translated, detected, created) in rows.into_iter().rev() {
+    let translation = Translation {
+      text:            translated,
+      detected_source: detected
+    };
+    cache.insert(CacheKey { text, source, target }, translation, created, now);
+  }
+
+  Ok(pool)
+}
+
+async fn persist(
+  pool: &SqlitePool,
+  key: &CacheKey,
+  translation: &Translation,
+  created: i64
+) -> Result<(), sqlx::Error> {
+  sqlx::query("INSERT OR REPLACE INTO translations (text, source, target, translated, detected, created) VALUES (?, ?, ?, ?, ?, ?)")
+    .bind(&key.text)
+    .bind(&key.source)
+    .bind(&key.target)
+    .bind(&translation.text)
+    .bind(&translation.detected_source)
+    .bind(created)
+    .execute(pool)
+    .await?;
+
+  sqlx::query("DELETE FROM translations WHERE created < ? OR rowid NOT IN (SELECT rowid FROM translations ORDER BY created DESC LIMIT ?)")
+    .bind(created - TTL_SECS)
+    .bind(CAPACITY as i64)
+    .execute(pool)
+    .await?;
+
+  Ok(())
+}
+
+pub async fn lookup(key: &CacheKey) -> Option<Translation> {
+  // Loads the persisted entries on first use
+  pool().await;
+
+  LOOKUPS.fetch_add(1, Ordering::Relaxed);
+  let hit = CACHE.lock().unwrap().get(key, now());
+  if hit.is_some() {
+    HITS.fetch_add(1, Ordering::Relaxed);
+  }
+
+  hit
+}
+
+pub async fn store(
+  key: CacheKey,
+  translation: &Translation
+) {
+  let now = now();
+  if let Some(pool) = pool().await
+    && let Err(e) = persist(pool, &key, translation, now).await
+  {
+    eprintln!("Translate[Cache] Couldn't persist a translation: {e}");
+  }
+
+  CACHE.lock().unwrap().insert(key, translation.clone(), now, now);
+}
+
+/// Hits and lookups since startup
+pub fn hit_rate() -> (u64, u64) { (HITS.load(Ordering::Relaxed), LOOKUPS.load(Ordering::Relaxed)) }
+
+/// What the cache already has for each text, `None` where it still has to be translated
+pub async fn cached(
+  text: &[String],
+  source: Option<&str>,
+  target: &str
+) -> Vec<Option<Translation>> {
+  let mut found = Vec::with_capacity(text.len());
+  for t in text {
+    found.push(lookup(&CacheKey::new(t, source, target)).await);
+  }
+
+  found
+}
+
+/// Translate what the cache didn't have in batches the providers take, the provider is the last one that translated
+/// or `None` if nothing had to be. Each translation comes back in the text's place, flagged if it came from the cache.
+/// A failure says how many characters already went through, those batches are cached all the same
+pub async fn translate_cached<'a>(
+  providers: &'a [Provider],
+  text: &[String],
+  cached: Vec<Option<Translation>>,
+  source: Option<&str>,
+  target: &str
+) -> Result<(Option<&'a Provider>, Vec<(Translation, bool)>), (&'a Provider, ProviderError, u64)> {
+  let mut results: Vec<Option<(Translation, bool)>> = cached.into_iter().map(|c| c.map(|t| (t, true))).collect();
+  let missing: Vec<usize> = (0..results.len()).filter(|&i| results[i].is_none()).collect();
+
+  let mut last = None;
+  let mut sent = 0;
+  for chunk in missing.chunks(MAX_BATCH) {
+    let batch: Vec<String> = chunk.iter().map(|&i| text[i].clone()).collect();
+    let (provider, translations) = translate_with_fallback(providers, &batch, source, target)
+      .await
+      .map_err(|(provider, e)| (provider, e, sent))?;
+    sent += batch.iter().map(|t| t.chars().count() as u64).sum::<u64>();
+    if translations.len() != batch.len() {
+      let e = format!(
+        "{} sent {} translations back for {} texts",
+        provider.name(),
+        translations.len(),
+        batch.len()
+      );
+      return Err((provider, ProviderError::Parse(e), sent));
+    }
+
+    for (&i, translation) in chunk.iter().zip(translations) {
+      store(CacheKey::new(&text[i], source, target), &translation).await;
+      results[i] = Some((translation, false));
+    }
+    last = Some(provider);
+  }
+
+  Ok((last, results.into_iter().flatten().collect()))
+}
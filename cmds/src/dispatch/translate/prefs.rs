@@ -0,0 +1,81 @@
+use {
+  dashmap::DashMap,
+  poise::serenity_prelude::UserId,
+  sqlx::{
+    SqlitePool,
+    sqlite::{
+      SqliteConnectOptions,
+      SqlitePoolOptions
+    }
+  },
+  tokio::sync::OnceCell
+};
+
+static POOL: OnceCell<SqlitePool> = OnceCell::const_new();
+/// Remembered targets by user, read from the database once and written through after that
+static TARGETS: OnceCell<DashMap<UserId, String>> = OnceCell::const_new();
+
+/// Where the remembered target languages are kept, override with `KON_TRANSLATE_PREFS`
+fn db_path() -> String { std::env::var("KON_TRANSLATE_PREFS").unwrap_or_else(|_| "translate_prefs.db".to_string()) }
+
+async fn pool() -> Result<&'static SqlitePool, sqlx::Error> {
+  POOL
+    .get_or_try_init(|| async {
+      let options = SqliteConnectOptions::new().filename(db_path()).create_if_missing(true);
+      let pool = SqlitePoolOptions::new().max_connections(2).connect_with(options).await?;
+
+      sqlx::query("CREATE TABLE IF NOT EXISTS targets (user INTEGER PRIMARY KEY, target TEXT NOT NULL)")
+        .execute(&pool)
+        .await?;
+
+      Ok(pool)
+    })
+    .await
+}
+
+async fn targets() -> &'static DashMap<UserId, String> {
+  TARGETS
+    .get_or_init(|| async {
+      let targets = DashMap::new();
+      let rows: Result<Vec<(i64, String)>, sqlx::Error> = match pool().await {
+        Ok(pool) => sqlx::query_as("SELECT user, target FROM targets").fetch_all(pool).await,
+        Err(e) => Err(e)
+      };
+
+      match rows {
+        Ok(rows) => {
+          for (user, target) in rows {
+            targets.insert(UserId::new(user as u64), target);
+          }
+        },
+        Err(e) => eprintln!("Translate[Prefs] Couldn't load the remembered targets: {e}")
+      }
+
+      targets
+    })
+    .await
+}
+
+/// Target language the user asked to be remembered, if any
+pub async fn default_target(user: UserId) -> Option<String> { targets().await.get(&user).map(|t| t.clone()) }
+
+pub async fn remember_target(
+  user: UserId,
+  code: &str
+) {
+  targets().await.insert(user, code.to_string());
+
+  let result = match pool().await {
+    Ok(pool) => sqlx::query("INSERT OR REPLACE INTO targets (user, target) VALUES (?, ?)")
+      .bind(user.get() as i64)
+      .bind(code)
+      .execute(pool)
+      .await
+      .map(|_| ()),
+    Err(e) => Err(e)
+  };
+
+  if let Err(e) = result {
+    eprintln!("Translate[Prefs] Couldn't save the target for {user}: {e}");
+  }
+}
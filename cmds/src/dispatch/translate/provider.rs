@@ -0,0 +1,446 @@
+use {
+  super::REQWEST_,
+  reqwest::{
+    Method,
+    RequestBuilder,
+    StatusCode
+  },
+  serde::{
+    Deserialize,
+    Serialize,
+    de::DeserializeOwned
+  },
+  std::fmt
+};
+
+/// What Kon needs from a translation service, codes are DeepL-style (`EN`, `EN-GB`, `PT-BR`) on both ends
+pub trait Translator {
+  fn name(&self) -> &'static str;
+
+  /// Translate every text in one request, the results come back in the same order
+  async fn translate(
+    &self,
+    text: &[String],
+    source: Option<&str>,
+    target: &str
+  ) -> Result<Vec<Translation>, ProviderError>;
+
+  /// Languages that can be translated to
+  async fn languages(&self) -> Result<Vec<Language>, ProviderError>;
+
+  /// Characters used and allowed this billing period, `None` if the service doesn't count them
+  async fn usage(&self) -> Result<Option<Usage>, ProviderError>;
+}
+
+#[derive(Clone)]
+pub struct Translation {
+  pub text:            String,
+  pub detected_source: String
+}
+
+pub struct Language {
+  pub code: String,
+  pub name: String
+}
+
+pub struct Usage {
+  pub character_count: u64,
+  pub character_limit: u64
+}
+
+pub enum ProviderError {
+  /// Request never got an answer
+  Unreachable(String),
+  /// Service failed on its end
+  ServerError(u16),
+  QuotaExceeded,
+  Unauthorized,
+  RateLimited,
+  Status(u16),
+  Parse(String)
+}
+
+impl ProviderError {
+  /// Whether the next provider should be given a go, only for outages and spent quotas
+  pub fn falls_back(&self) -> bool {
+    matches!(
+      self,
+      ProviderError::Unreachable(_) | ProviderError::ServerError(_) | ProviderError::QuotaExceeded
+    )
+  }
+
+  /// What the user gets told, the details (which can name a self-hosted instance's address) stay in the console
+  pub fn describe(
+    &self,
+    provider: &str
+  ) -> String {
+    match self {
+      ProviderError::Unreachable(_) => format!("Couldn't reach {provider}, try again later!"),
+      ProviderError::ServerError(_) => format!("{provider} service gave an internal server error, try again later!"),
+      ProviderError::QuotaExceeded => format!("{provider} has used up its quota for this billing period, try again later!"),
+      ProviderError::Unauthorized => format!("Not authenticated to {provider} API"),
+      ProviderError::RateLimited => format!("{provider} requests ratelimited, slow down!"),
+      ProviderError::Status(code) => format!("Unknown status code, {provider} returned with HTTP {code}"),
+      ProviderError::Parse(_) => format!("**(Kon) Parsing error:** {provider} answered with something unexpected")
+    }
+  }
+}
+
+impl fmt::Display for ProviderError {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    match self {
+      ProviderError::Unreachable(e) => write!(f, "Request failed: {e}"),
+      ProviderError::ServerError(code) => write!(f, "Server error (HTTP {code})"),
+      ProviderError::QuotaExceeded => write!(f, "Quota exceeded"),
+      ProviderError::Unauthorized => write!(f, "API key was rejected"),
+      ProviderError::RateLimited => write!(f, "Ratelimited"),
+      ProviderError::Status(code) => write!(f, "Unexpected status (HTTP {code})"),
+      ProviderError::Parse(e) => write!(f, "Couldn't parse the response: {e}")
+    }
+  }
+}
+
+fn check_status(status: StatusCode) -> Result<(), ProviderError> {
+  match status.as_u16() {
+    200..=299 => Ok(()),
+    401 | 403 => Err(ProviderError::Unauthorized),
+    429 => Err(ProviderError::RateLimited),
+    456 => Err(ProviderError::QuotaExceeded),
+    code @ 500..=599 => Err(ProviderError::ServerError(code)),
+    code => Err(ProviderError::Status(code))
+  }
+}
+
+async fn send<T: DeserializeOwned>(req: RequestBuilder) -> Result<T, ProviderError> {
+  let resp = req
+    .header("User-Agent", "kon/reqwest")
+    .send()
+    .await
+    .map_err(|e| ProviderError::Unreachable(e.to_string()))?;
+  check_status(resp.status())?;
+
+  resp.json().await.map_err(|e| ProviderError::Parse(e.to_string()))
+}
+
+#[derive(Serialize)]
+struct DeepLRequest<'a> {
+  text:        &'a [String],
+  #[serde(skip_serializing_if = "Option::is_none")]
+  source_lang: Option<&'a str>,
+  target_lang: &'a str
+}
+
+#[derive(Deserialize)]
+struct DeepLResponse {
+  translations: Vec<DeepLTranslation>
+}
+
+#[derive(Deserialize)]
+struct DeepLTranslation {
+  text:                     String,
+  detected_source_language: String
+}
+
+#[derive(Deserialize)]
+struct DeepLLanguage {
+  language: String,
+  name:     String
+}
+
+#[derive(Deserialize)]
+struct DeepLUsage {
+  character_count: u64,
+  character_limit: u64
+}
+
+pub struct DeepL {
+  key:     String,
+  api_url: String
+}
+
+impl DeepL {
+  pub fn new(key: String) -> Self {
+    let api_url = if key.ends_with(":fx") {
+      "https://api-free.deepl.com"
+    } else {
+      "https://api.deepl.com"
+    };
+
+    Self::with_url(key, api_url.to_string())
+  }
+
+  pub fn with_url(
+    key: String,
+    api_url: String
+  ) -> Self {
+    Self { key, api_url }
+  }
+
+  fn request(
+    &self,
+    method: Method,
+    path: &str
+  ) -> RequestBuilder {
+    REQWEST_
+      .request(method, format!("{}/v2/{path}", self.api_url))
+      .header("Authorization", format!("DeepL-Auth-Key {}", self.key))
+  }
+}
+
+impl Translator for DeepL {
+  fn name(&self) -> &'static str { "DeepL" }
+
+  async fn translate(
+    &self,
+    text: &[String],
+    source: Option<&str>,
+    target: &str
+  ) -> Result<Vec<Translation>, ProviderError> {
+    let resp: DeepLResponse = send(self.request(Method::POST, "translate").json(&DeepLRequest {
+      text,
+      source_lang: source,
+      target_lang: target
+    }))
+    .await?;
+
+    Ok(
+      resp
+        .translations
+        .into_iter()
+        .map(|t| Translation {
+          text:            t.text,
+          detected_source: t.detected_source_language
+        })
+        .collect()
+    )
+  }
+
+  async fn languages(&self) -> Result<Vec<Language>, ProviderError> {
+    let languages: Vec<DeepLLanguage> = send(self.request(Method::GET, "languages").query(&[("type", "target")])).await?;
+
+    Ok(
+      languages
+        .into_iter()
+        .map(|l| Language {
+          code: l.language,
+          name: l.name
+        })
+        .collect()
+    )
+  }
+
+  async fn usage(&self) -> Result<Option<Usage>, ProviderError> {
+    let usage: DeepLUsage = send(self.request(Method::GET, "usage")).await?;
+
+    Ok(Some(Usage {
+      character_count: usage.character_count,
+      character_limit: usage.character_limit
+    }))
+  }
+}
+
+#[derive(Serialize)]
+struct LibreRequest<'a> {
+  q:       &'a [String],
+  source:  String,
+  target:  String,
+  format:  &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  api_key: Option<&'a str>
+}
+
+/// Single texts get single answers, the batches we send get arrays back
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+  One(T),
+  Many(Vec<T>)
+}
+
+impl<T> OneOrMany<T> {
+  fn into_vec(self) -> Vec<T> {
+    match self {
+      OneOrMany::One(t) => vec![t],
+      OneOrMany::Many(v) => v
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LibreResponse {
+  translated_text:   OneOrMany<String>,
+  detected_language: Option<OneOrMany<LibreDetection>>
+}
+
+#[derive(Deserialize)]
+struct LibreDetection {
+  language: String
+}
+
+#[derive(Deserialize)]
+struct LibreLanguage {
+  code: String,
+  name: String
+}
+
+/// LibreTranslate only knows the base languages, and in lowercase
+fn libre_code(code: &str) -> String { code.split('-').next().unwrap_or(code).to_lowercase() }
+
+/// Self-hosted LibreTranslate, the key is only needed if the instance asks for one
+pub struct LibreTranslate {
+  url: String,
+  key: Option<String>
+}
+
+impl LibreTranslate {
+  pub fn new(
+    url: String,
+    key: Option<String>
+  ) -> Self {
+    Self {
+      url: url.trim_end_matches('/').to_string(),
+      key
+    }
+  }
+}
+
+impl Translator for LibreTranslate {
+  fn name(&self) -> &'static str { "LibreTranslate" }
+
+  async fn translate(
+    &self,
+    text: &[String],
+    source: Option<&str>,
+    target: &str
+  ) -> Result<Vec<Translation>, ProviderError> {
+    let req = REQWEST_.post(format!("{}/translate", self.url)).json(&LibreRequest {
+      q:       text,
+      source:  source.map(libre_code).unwrap_or_else(|| "auto".to_string()),
+      target:  libre_code(target),
+      format:  "text",
+      api_key: self.key.as_deref()
+    });
+    let resp: LibreResponse = send(req).await?;
+
+    let detected: Vec<String> = match resp.detected_language {
+      Some(d) => d.into_vec().into_iter().map(|d| d.language.to_uppercase()).collect(),
+      None => Vec::new()
+    };
+    let fallback = source.unwrap_or_default().to_uppercase();
+
+    Ok(
+      resp
+        .translated_text
+        .into_vec()
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| Translation {
+          text,
+          detected_source: detected.get(i).cloned().unwrap_or_else(|| fallback.clone())
+        })
+        .collect()
+    )
+  }
+
+  async fn languages(&self) -> Result<Vec<Language>, ProviderError> {
+    let languages: Vec<LibreLanguage> = send(REQWEST_.get(format!("{}/languages", self.url))).await?;
+
+    Ok(
+      languages
+        .into_iter()
+        .map(|l| Language {
+          code: l.code.to_uppercase(),
+          name: l.name
+        })
+        .collect()
+    )
+  }
+
+  async fn usage(&self) -> Result<Option<Usage>, ProviderError> { Ok(None) }
+}
+
+pub enum Provider {
+  DeepL(DeepL),
+  LibreTranslate(LibreTranslate)
+}
+
+/// Configured providers in the order they're tried, DeepL first
+pub fn providers() -> Vec<Provider> {
+  let mut providers = Vec::new();
+
+  if let Ok(key) = std::env::var("KON_DEEPL")
+    && !key.is_empty()
+  {
+    providers.push(Provider::DeepL(DeepL::new(key)));
+  }
+  if let Ok(url) = std::env::var("KON_LIBRETRANSLATE")
+    && !url.is_empty()
+  {
+    let key = std::env::var("KON_LIBRETRANSLATE_KEY").ok().filter(|k| !k.is_empty());
+    providers.push(Provider::LibreTranslate(LibreTranslate::new(url, key)));
+  }
+
+  providers
+}
+
+/// Try each provider in turn, moving on only when one is down or out of quota
+pub async fn translate_with_fallback<'a>(
+  providers: &'a [Provider],
+  text: &[String],
+  source: Option<&str>,
+  target: &str
+) -> Result<(&'a Provider, Vec<Translation>), (&'a Provider, ProviderError)> {
+  let mut last_err = None;
+
+  for provider in providers {
+    match provider.translate(text, source, target).await {
+      Ok(translations) => return Ok((provider, translations)),
+      Err(e) if e.falls_back() => {
+        eprintln!("Translate[{}] {e}, trying the next provider", provider.name());
+        last_err = Some((provider, e));
+      },
+      Err(e) => return Err((provider, e))
+    }
+  }
+
+  Err(last_err.expect("translate_with_fallback needs at least one provider"))
+}
+
+impl Translator for Provider {
+  fn name(&self) -> &'static str {
+    match self {
+      Provider::DeepL(p) => p.name(),
+      Provider::LibreTranslate(p) => p.name()
+    }
+  }
+
+  async fn translate(
+    &self,
+    text: &[String],
+    source: Option<&str>,
+    target: &str
+  ) -> Result<Vec<Translation>, ProviderError> {
+    match self {
+      Provider::DeepL(p) => p.translate(text, source, target).await,
+      Provider::LibreTranslate(p) => p.translate(text, source, target).await
+    }
+  }
+
+  async fn languages(&self) -> Result<Vec<Language>, ProviderError> {
+    match self {
+      Provider::DeepL(p) => p.languages().await,
+      Provider::LibreTranslate(p) => p.languages().await
+    }
+  }
+
+  async fn usage(&self) -> Result<Option<Usage>, ProviderError> {
+    match self {
+      Provider::DeepL(p) => p.usage().await,
+      Provider::LibreTranslate(p) => p.usage().await
+    }
+  }
+}
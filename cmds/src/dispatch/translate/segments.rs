@@ -0,0 +1,274 @@
+use {
+  super::REQWEST_,
+  kon_libs::{
+    BINARY_PROPERTIES,
+    TranslateBudget
+  },
+  poise::serenity_prelude::{
+    Attachment,
+    CreateAttachment,
+    CreateEmbed,
+    Embed,
+    Message
+  },
+  std::collections::BTreeMap
+};
+
+/// Text attachments past this are left alone, they'd eat the quota
+const MAX_ATTACHMENT_BYTES: u32 = 8 * 1024;
+/// Discord's limits for embeds
+const TITLE_LIMIT: usize = 256;
+const DESCRIPTION_LIMIT: usize = 4096;
+const FIELD_NAME_LIMIT: usize = 256;
+const FIELD_VALUE_LIMIT: usize = 1024;
+const EMBEDS_LIMIT: usize = 6000;
+const MAX_EMBEDS: usize = 10;
+
+/// Where a piece of text came from, so its translation can be put back in the same place.
+/// Embeds are numbered across the message and its forwards
+pub enum Part {
+  Content,
+  EmbedTitle(usize),
+  EmbedDescription(usize),
+  FieldName(usize, usize),
+  FieldValue(usize, usize, bool),
+  PollQuestion,
+  PollAnswer,
+  Attachment(String)
+}
+
+pub struct Segment {
+  pub part:      Part,
+  /// Came from a forwarded message's snapshot
+  pub forwarded: bool,
+  pub text:      String
+}
+
+/// Everything translatable in a message, in the order it shows up
+#[derive(Default)]
+pub struct Segments {
+  pub list: Vec<Segment>,
+  embeds:   usize
+}
+
+impl Segments {
+  pub fn push(
+    &mut self,
+    part: Part,
+    forwarded: bool,
+    text: &str
+  ) {
+    if !text.trim().is_empty() {
+      self.list.push(Segment {
+        part,
+        forwarded,
+        text: text.trim().to_string()
+      });
+    }
+  }
+
+  fn push_embeds(
+    &mut self,
+    embeds: &[Embed],
+    forwarded: bool
+  ) {
+    for embed in embeds {
+      let i = self.embeds;
+      self.embeds += 1;
+
+      self.push(Part::EmbedTitle(i), forwarded, embed.title.as_deref().unwrap_or_default());
+      self.push(Part::EmbedDescription(i), forwarded, embed.description.as_deref().unwrap_or_default());
+      for (f, field) in embed.fields.iter().enumerate() {
+        self.push(Part::FieldName(i, f), forwarded, &field.name);
+        self.push(Part::FieldValue(i, f, field.inline), forwarded, &field.value);
+      }
+    }
+  }
+
+  async fn push_attachments(
+    &mut self,
+    attachments: &[Attachment],
+    forwarded: bool
+  ) {
+    for attachment in attachments {
+      if !is_text_attachment(&attachment.filename, attachment.content_type.as_deref(), attachment.size) {
+        continue;
+      }
+
+      match fetch_text(&attachment.url).await {
+        Some(text) => self.push(Part::Attachment(attachment.filename.to_string()), forwarded, &text),
+        None => eprintln!("Translate[Attachments] Skipped {}, it couldn't be read as UTF-8", attachment.filename)
+      }
+    }
+  }
+}
+
+/// Biggest text attachment taken, never more than a user may translate in a day or it'd always be refused
+pub fn attachment_limit(budget: &TranslateBudget) -> u32 {
+  match budget.user_daily {
+    0 => MAX_ATTACHMENT_BYTES,
+    daily => MAX_ATTACHMENT_BYTES.min(u32::try_from(daily).unwrap_or(u32::MAX))
+  }
+}
+
+/// Small plain text files, going by their type or extension
+pub fn is_text_attachment(
+  filename: &str,
+  content_type: Option<&str>,
+  size: u32
+) -> bool {
+  let texty = match content_type {
+    Some(t) => t.starts_with("text/plain") || t.starts_with("text/markdown"),
+    None => [".txt", ".md"].iter().any(|ext| filename.to_lowercase().ends_with(ext))
+  };
+
+  texty && size <= attachment_limit(&BINARY_PROPERTIES.translate_budget)
+}
+
+async fn fetch_text(url: &str) -> Option<String> {
+  let bytes = REQWEST_.get(url).send().await.ok()?.error_for_status().ok()?.bytes().await.ok()?;
+  String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Content, embeds, poll and text attachments of the message, then the same for every message it forwards
+pub async fn message_segments(message: &Message) -> Segments {
+  let mut segments = Segments::default();
+
+  segments.push(Part::Content, false, &message.content);
+  segments.push_embeds(&message.embeds, false);
+  if let Some(poll) = &message.poll {
+    segments.push(Part::PollQuestion, false, poll.question.text.as_deref().unwrap_or_default());
+    for answer in poll.answers.iter() {
+      segments.push(Part::PollAnswer, false, answer.poll_media.text.as_deref().unwrap_or_default());
+    }
+  }
+  segments.push_attachments(&message.attachments, false).await;
+
+  for snapshot in message.message_snapshots.iter() {
+    segments.push(Part::Content, true, &snapshot.content);
+    segments.push_embeds(&snapshot.embeds, true);
+    segments.push_attachments(&snapshot.attachments, true).await;
+  }
+
+  segments
+}
+
+/// Cut to the limit on a character boundary, marking that it was cut
+fn clip(
+  text: &str,
+  limit: usize
+) -> String {
+  if text.chars().count() <= limit {
+    return text.to_string();
+  }
+
+  let mut clipped: String = text.chars().take(limit - 1).collect();
+  clipped.push('…');
+  clipped
+}
+
+#[derive(Default)]
+struct TranslatedEmbed {
+  title:       Option<String>,
+  description: Option<String>,
+  /// Field number to (name, value, inline)
+  fields:      BTreeMap<usize, (Option<String>, Option<String>, bool)>
+}
+
+impl TranslatedEmbed {
+  fn len(&self) -> usize {
+    let fields = self
+      .fields
+      .values()
+      .map(|(n, v, _)| n.as_deref().map_or(0, str::len) + v.as_deref().map_or(0, str::len))
+      .sum::<usize>();
+    self.title.as_deref().map_or(0, str::len) + self.description.as_deref().map_or(0, str::len) + fields
+  }
+
+  fn build(self) -> CreateEmbed<'static> {
+    let mut embed = CreateEmbed::new().color(BINARY_PROPERTIES.embed_color);
+    if let Some(title) = self.title {
+      embed = embed.title(title);
+    }
+    if let Some(description) = self.description {
+      embed = embed.description(description);
+    }
+    for (name, value, inline) in self.fields.into_values() {
+      embed = embed.field(
+        name.unwrap_or_else(|| "\u{200B}".to_string()),
+        value.unwrap_or_else(|| "\u{200B}".to_string()),
+        inline
+      );
+    }
+
+    embed
+  }
+}
+
+/// The translations laid out like the original message
+pub struct Rendered {
+  pub body:    String,
+  pub embeds:  Vec<CreateEmbed<'static>>,
+  pub files:   Vec<CreateAttachment<'static>>,
+  /// Embeds that didn't fit in Discord's limits for a single message
+  pub omitted: usize
+}
+
+pub fn render(
+  segments: &[Segment],
+  translations: &[String],
+  target: &str
+) -> Rendered {
+  let mut blocks: Vec<String> = Vec::new();
+  let mut embeds: BTreeMap<usize, TranslatedEmbed> = BTreeMap::new();
+  let mut files = Vec::new();
+  let mut forwarded = false;
+
+  for (segment, text) in segments.iter().zip(translations) {
+    if segment.forwarded && !forwarded {
+      forwarded = true;
+      blocks.push("**Forwarded message**".to_string());
+    }
+
+    match &segment.part {
+      Part::Content => blocks.push(format!("```\n{text}\n```")),
+      Part::PollQuestion => blocks.push(format!("**Poll:** {text}")),
+      Part::PollAnswer => blocks.push(format!("- {text}")),
+      Part::EmbedTitle(i) => embeds.entry(*i).or_default().title = Some(clip(text, TITLE_LIMIT)),
+      Part::EmbedDescription(i) => embeds.entry(*i).or_default().description = Some(clip(text, DESCRIPTION_LIMIT)),
+      Part::FieldName(i, f) => embeds.entry(*i).or_default().fields.entry(*f).or_default().0 = Some(clip(text, FIELD_NAME_LIMIT)),
+      Part::FieldValue(i, f, inline) => {
+        let field = embeds.entry(*i).or_default().fields.entry(*f).or_default();
+        field.1 = Some(clip(text, FIELD_VALUE_LIMIT));
+        field.2 = *inline;
+      },
+      Part::Attachment(filename) => {
+        let stem = filename.rsplit_once('.').map_or(filename.as_str(), |(stem, _)| stem);
+        files.push(CreateAttachment::bytes(
+          text.clone().into_bytes(),
+          format!("{stem}.{}.txt", target.to_lowercase())
+        ));
+      }
+    }
+  }
+
+  // Whatever doesn't fit in Discord's limits for a single message is left out
+  let translated = embeds.len();
+  let mut total = 0;
+  let embeds: Vec<_> = embeds
+    .into_values()
+    .take(MAX_EMBEDS)
+    .take_while(|e| {
+      total += e.len();
+      total <= EMBEDS_LIMIT
+    })
+    .map(TranslatedEmbed::build)
+    .collect();
+
+  Rendered {
+    body: blocks.join("\n"),
+    omitted: translated - embeds.len(),
+    embeds,
+    files
+  }
+}
@@ -0,0 +1,382 @@
+use {
+  super::{
+    budget::{
+      Scope,
+      breaches_reserve,
+      refusal,
+      reserve,
+      spent_today,
+      warning_level
+    },
+    cache::{
+      Cache,
+      CacheKey,
+      cached,
+      translate_cached
+    },
+    provider::*,
+    segments::{
+      Part,
+      Segments,
+      attachment_limit,
+      is_text_attachment,
+      render
+    }
+  },
+  http_body_util::{
+    BodyExt,
+    Full
+  },
+  hyper::{
+    Request,
+    Response,
+    StatusCode,
+    body::{
+      Bytes,
+      Incoming
+    },
+    server::conn::http1,
+    service::service_fn
+  },
+  hyper_util::rt::TokioIo,
+  kon_libs::{
+    BINARY_PROPERTIES,
+    TranslateBudget
+  },
+  poise::serenity_prelude::{
+    GuildId,
+    UserId
+  },
+  std::convert::Infallible,
+  tokio::net::TcpListener
+};
+
+/// Plain HTTP stand-in for a provider, every path gets the same status and body
+async fn serve(
+  status: u16,
+  body: &'static str
+) -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let url = format!("http://{}", listener.local_addr().unwrap());
+
+  tokio::spawn(async move {
+    while let Ok((stream, _)) = listener.accept().await {
+      tokio::spawn(async move {
+        let service = service_fn(move |_| async move {
+          let mut res = Response::new(Full::new(Bytes::from(body)));
+          *res.status_mut() = StatusCode::from_u16(status).unwrap();
+          Ok::<_, Infallible>(res)
+        });
+        let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+      });
+    }
+  });
+
+  url
+}
+
+const DEEPL_OK: &str = r#"{ "translations": [{ "detected_source_language": "DE", "text": "Hello" }] }"#;
+const LIBRE_OK: &str = r#"{ "translatedText": ["Hallo"], "detectedLanguage": [{ "confidence": 90, "language": "en" }] }"#;
+
+async fn chain(
+  deepl: (u16, &'static str),
+  libre: (u16, &'static str)
+) -> Vec<Provider> {
+  vec![
+    Provider::DeepL(DeepL::with_url("key".to_string(), serve(deepl.0, deepl.1).await)),
+    Provider::LibreTranslate(LibreTranslate::new(serve(libre.0, libre.1).await, None)),
+  ]
+}
+
+fn text() -> Vec<String> { vec!["Hallo".to_string()] }
+
+#[tokio::test]
+async fn deepl_answers_first() {
+  let providers = chain((200, DEEPL_OK), (200, LIBRE_OK)).await;
+
+  let Ok((provider, translations)) = translate_with_fallback(&providers, &text(), None, "EN").await else {
+    panic!("DeepL should have answered");
+  };
+  assert_eq!(provider.name(), "DeepL");
+  assert_eq!(translations[0].text, "Hello");
+  assert_eq!(translations[0].detected_source, "DE");
+}
+
+#[tokio::test]
+async fn falls_back_on_quota_and_outages() {
+  for status in [456, 500, 503] {
+    let providers = chain((status, ""), (200, LIBRE_OK)).await;
+
+    let Ok((provider, translations)) = translate_with_fallback(&providers, &text(), None, "DE").await else {
+      panic!("LibreTranslate should have answered after HTTP {status}");
+    };
+    assert_eq!(provider.name(), "LibreTranslate");
+    assert_eq!(translations[0].text, "Hallo");
+    assert_eq!(translations[0].detected_source, "EN");
+  }
+}
+
+#[tokio::test]
+async fn stays_on_client_errors() {
+  let providers = chain((403, ""), (200, LIBRE_OK)).await;
+
+  match translate_with_fallback(&providers, &text(), None, "DE").await {
+    Err((provider, ProviderError::Unauthorized)) => assert_eq!(provider.name(), "DeepL"),
+    _ => panic!("a rejected key shouldn't fall back")
+  }
+}
+
+#[tokio::test]
+async fn last_error_when_everything_fails() {
+  let providers = chain((456, ""), (502, "")).await;
+
+  match translate_with_fallback(&providers, &text(), None, "DE").await {
+    Err((provider, ProviderError::ServerError(502))) => assert_eq!(provider.name(), "LibreTranslate"),
+    _ => panic!("the last provider's error should come back")
+  }
+}
+
+#[tokio::test]
+async fn libre_single_answers() {
+  let libre = LibreTranslate::new(serve(200, r#"{ "translatedText": "Hallo" }"#).await, None);
+
+  let Ok(translations) = libre.translate(&text(), Some("EN-GB"), "DE").await else {
+    panic!("a single answer should parse");
+  };
+  assert_eq!(translations[0].text, "Hallo");
+  assert_eq!(translations[0].detected_source, "EN-GB");
+}
+
+fn translation(text: &str) -> Translation {
+  Translation {
+    text:            text.to_string(),
+    detected_source: "DE".to_string()
+  }
+}
+
+#[test]
+fn cache_keys_ignore_whitespace() {
+  assert!(CacheKey::new("  Guten   Tag\n", None, "en") == CacheKey::new("Guten Tag", None, "EN"));
+  assert!(CacheKey::new("Guten Tag", None, "EN") != CacheKey::new("Guten Tag", Some("DE"), "EN"));
+  assert!(CacheKey::new("Guten Tag", None, "EN") != CacheKey::new("Guten Tag", None, "EN-GB"));
+  assert!(CacheKey::new("Guten Tag", None, "EN") != CacheKey::new("guten tag", None, "EN"));
+}
+
+#[test]
+fn cache_expires_and_evicts() {
+  let mut cache = Cache::new(100, 2);
+  let key = |t: &str| CacheKey::new(t, None, "EN");
+
+  cache.insert(key("a"), translation("A"), 0, 0);
+  assert_eq!(cache.get(&key("a"), 99).map(|t| t.text).as_deref(), Some("A"));
+  assert!(cache.get(&key("a"), 100).is_none(), "expired entries shouldn't be served");
+
+  // Reading "b" makes "c" the least recently used once "d" pushes past the bound
+  cache.insert(key("b"), translation("B"), 200, 200);
+  cache.insert(key("c"), translation("C"), 201, 201);
+  cache.get(&key("b"), 202);
+  cache.insert(key("d"), translation("D"), 203, 203);
+  assert!(cache.get(&key("c"), 204).is_none());
+  assert!(cache.get(&key("b"), 204).is_some());
+  assert!(cache.get(&key("d"), 204).is_some());
+
+  // Rows loaded from disk that already expired aren't kept
+  cache.insert(key("e"), translation("E"), 0, 300);
+  assert!(cache.get(&key("e"), 300).is_none());
+}
+
+#[tokio::test]
+async fn cached_translations_skip_the_provider() {
+  let providers = chain((200, DEEPL_OK), (200, LIBRE_OK)).await;
+  let text = vec!["Hallo, cache!".to_string()];
+
+  let Ok((Some(provider), first)) = translate_cached(&providers, &text, cached(&text, None, "EN").await, None, "EN").await else {
+    panic!("the first translation should go to DeepL");
+  };
+  assert_eq!(provider.name(), "DeepL");
+  assert!(!first[0].1);
+
+  let Ok((None, second)) = translate_cached(&providers, &text, cached(&text, None, "EN").await, None, "EN").await else {
+    panic!("the second translation should come from the cache");
+  };
+  assert!(second[0].1);
+  assert_eq!(second[0].0.text, "Hello");
+}
+
+/// DeepL stand-in that hands every text back as is, and turns down batches over its limit of 50 like DeepL does
+async fn echo_deepl() -> String {
+  let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+  let url = format!("http://{}", listener.local_addr().unwrap());
+
+  tokio::spawn(async move {
+    while let Ok((stream, _)) = listener.accept().await {
+      tokio::spawn(async move {
+        let service = service_fn(|req: Request<Incoming>| async move {
+          let body = req.into_body().collect().await.unwrap().to_bytes();
+          let texts: Vec<String> = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["text"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t.as_str().unwrap().to_string())
+            .collect();
+
+          let mut res = Response::new(Full::new(Bytes::new()));
+          if texts.len() > 50 {
+            *res.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+            return Ok::<_, Infallible>(res);
+          }
+          let translations: Vec<_> = texts
+            .into_iter()
+            .map(|t| serde_json::json!({ "detected_source_language": "DE", "text": t }))
+            .collect();
+          *res.body_mut() = Full::new(Bytes::from(serde_json::json!({ "translations": translations }).to_string()));
+          Ok(res)
+        });
+        let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+      });
+    }
+  });
+
+  url
+}
+
+#[tokio::test]
+async fn long_messages_go_out_in_batches() {
+  let providers = vec![Provider::DeepL(DeepL::with_url("key".to_string(), echo_deepl().await))];
+  let text: Vec<String> = (0..120).map(|i| format!("Feld {i} im Stapel")).collect();
+
+  let Ok((Some(_), translations)) = translate_cached(&providers, &text, cached(&text, None, "EN").await, None, "EN").await else {
+    panic!("every batch should make it through");
+  };
+  assert_eq!(translations.len(), 120);
+  assert!(
+    translations
+      .iter()
+      .zip(&text)
+      .all(|((t, cached), original)| !cached && t.text == *original)
+  );
+}
+
+fn usage(
+  count: u64,
+  limit: u64
+) -> Usage {
+  Usage {
+    character_count: count,
+    character_limit: limit
+  }
+}
+
+#[test]
+fn daily_budgets() {
+  let budget = TranslateBudget {
+    reserve:     0,
+    user_daily:  100,
+    guild_daily: 0
+  };
+  let user = Scope::User(UserId::new(1));
+
+  assert!(refusal(&budget, user, 60, 40).is_none());
+  let refused = refusal(&budget, user, 60, 41).expect("going over should be refused");
+  assert!(refused.contains("only 40 are left"), "{refused}");
+  assert!(
+    refusal(&budget, Scope::Guild(GuildId::new(1)), 1000000, 1).is_none(),
+    "0 turns the limit off"
+  );
+
+  let scopes = [Scope::User(UserId::new(2)), Scope::Guild(GuildId::new(2))];
+  let mut first = reserve(&budget, &scopes, 30).expect("within the budget");
+  first.keep(30);
+  let mut second = reserve(&budget, &scopes[..1], 50).expect("within the budget");
+  assert_eq!(spent_today(scopes[0]), 80);
+  assert_eq!(spent_today(scopes[1]), 30);
+
+  // Held characters count against the budget until they're handed back
+  assert!(reserve(&budget, &scopes[..1], 21).is_err());
+  second.keep(12);
+  drop(second);
+  drop(first);
+  assert_eq!(spent_today(scopes[0]), 42);
+  assert_eq!(spent_today(scopes[1]), 30);
+  assert!(reserve(&budget, &scopes[..1], 58).is_ok());
+  assert_eq!(spent_today(scopes[0]), 42, "a dropped reservation hands everything back");
+}
+
+#[test]
+fn quota_thresholds() {
+  assert!(!breaches_reserve(&usage(400, 1000), 500, 100));
+  assert!(breaches_reserve(&usage(400, 1000), 500, 101));
+
+  assert_eq!(warning_level(&usage(749, 1000)), 0);
+  assert_eq!(warning_level(&usage(750, 1000)), 75);
+  assert_eq!(warning_level(&usage(950, 1000)), 90);
+  assert_eq!(warning_level(&usage(10, 0)), 0);
+}
+
+#[test]
+fn text_attachments() {
+  assert!(is_text_attachment("notes.txt", Some("text/plain; charset=utf-8"), 512));
+  assert!(is_text_attachment("README.MD", None, 512));
+  assert!(!is_text_attachment("notes.txt", Some("text/plain"), 64 * 1024), "big files eat the quota");
+  assert!(!is_text_attachment("photo.png", Some("image/png"), 512));
+}
+
+#[test]
+fn attachments_fit_the_daily_budget() {
+  // The biggest attachment taken has to get past a fresh user's budget, or it'd be fetched only to be refused
+  let budget = &BINARY_PROPERTIES.translate_budget;
+  let limit = attachment_limit(budget);
+  assert!(is_text_attachment("notes.txt", Some("text/plain"), limit));
+  assert!(refusal(budget, Scope::User(UserId::new(3)), 0, limit as u64).is_none());
+
+  let tight = TranslateBudget {
+    reserve:     0,
+    user_daily:  5000,
+    guild_daily: 0
+  };
+  assert_eq!(attachment_limit(&tight), 5000);
+  assert!(refusal(&tight, Scope::User(UserId::new(3)), 0, attachment_limit(&tight) as u64).is_none());
+}
+
+#[test]
+fn rendering_keeps_the_layout() {
+  let mut segments = Segments::default();
+  segments.push(Part::Content, false, "  Hallo  ");
+  segments.push(Part::Content, false, "   ");
+  segments.push(Part::PollQuestion, false, "Frage");
+  segments.push(Part::PollAnswer, false, "Ja");
+  segments.push(Part::EmbedTitle(0), false, "Titel");
+  segments.push(Part::FieldValue(0, 0, true), false, "Wert");
+  segments.push(Part::Attachment("notiz.txt".to_string()), false, "Notiz");
+  segments.push(Part::Content, true, "Weitergeleitet");
+  assert_eq!(segments.list.len(), 7, "blank text shouldn't be sent off");
+  assert_eq!(segments.list[0].text, "Hallo");
+
+  let translations: Vec<String> = ["Hello", "Question", "Yes", "Title", "Value", "Note", "Forwarded"]
+    .iter()
+    .map(|t| t.to_string())
+    .collect();
+  let rendered = render(&segments.list, &translations, "EN-GB");
+
+  assert_eq!(
+    rendered.body,
+    "```\nHello\n```\n**Poll:** Question\n- Yes\n**Forwarded message**\n```\nForwarded\n```"
+  );
+  assert_eq!(rendered.embeds.len(), 1);
+  assert_eq!(rendered.files.len(), 1);
+  assert_eq!(rendered.files[0].filename, "notiz.en-gb.txt");
+  assert_eq!(rendered.omitted, 0);
+}
+
+#[test]
+fn rendering_counts_left_out_embeds() {
+  let mut segments = Segments::default();
+  for i in 0..12 {
+    segments.push(Part::EmbedTitle(i), false, "Titel");
+  }
+  let translations = vec!["Title".to_string(); 12];
+  let rendered = render(&segments.list, &translations, "EN");
+
+  assert_eq!(rendered.embeds.len(), 10);
+  assert_eq!(rendered.omitted, 2);
+}
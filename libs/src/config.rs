@@ -1,12 +1,67 @@
 use std::sync::LazyLock;
 
+/// A BMC that `/ilo` can talk to
+pub struct IloHost {
+  pub id:              &'static str,
+  pub name:            &'static str,
+  /// Env var holding `address;username;password`,
+  /// falls back to TokenService's `ilo_*` set when left empty
+  pub credentials_env: Option<&'static str>,
+  pub protocol:        BmcProtocol,
+  pub tls:             IloTls,
+  /// Sensors that `/ilo temperature` shows as (Redfish or SDR name, display name),
+  /// every sensor is shown when left empty
+  pub sensors:         Vec<(&'static str, &'static str)>
+}
+
+/// What the bot speaks to the BMC with
+#[derive(Clone, Copy, PartialEq)]
+pub enum BmcProtocol {
+  /// Redfish over HTTPS, everything `/ilo` has to offer
+  Redfish,
+  /// IPMI 2.0 over LAN (RMCP+) for BMCs without Redfish, limited to the sensors,
+  /// the SEL and chassis power, `address` may carry a port other than 623
+  Ipmi
+}
+
+/// How the BMC's certificate gets verified, unused over IPMI
+pub enum IloTls {
+  /// Pin whatever certificate the BMC presents on first contact
+  TrustOnFirstUse,
+  /// SHA-256 fingerprint of the BMC's certificate
+  Fingerprint(&'static str),
+  /// Path to a PEM bundle with the CA(s) that signed the BMC's certificate
+  CaBundle(&'static str)
+}
+
+/// How much of the translation providers' quota Kon may spend, limits of 0 are off
+pub struct TranslateBudget {
+  /// Characters of the provider's limit that are left alone, providers past it are skipped
+  pub reserve:     u64,
+  /// Characters a user can have translated per day (UTC)
+  pub user_daily:  u64,
+  /// Characters a guild can have translated per day (UTC)
+  pub guild_daily: u64
+}
+
 pub struct ConfigMeta {
-  pub env:          String,
-  pub embed_color:  u32,
-  pub ready_notify: u64,
-  pub rss_channel:  u64,
-  pub kon_logs:     u64,
-  pub developers:   Vec<u64>
+  pub env:                String,
+  pub embed_color:        u32,
+  pub ready_notify:       u64,
+  pub rss_channel:        u64,
+  pub kon_logs:           u64,
+  pub developers:         Vec<u64>,
+  /// How often (in seconds) the iLO monitor polls Redfish
+  pub ilo_poll_secs:      u64,
+  /// First entry is used when `/ilo` is ran without a host
+  pub ilo_hosts:          Vec<IloHost>,
+  /// Where the Redfish event listener binds, the URL the BMCs
+  /// should push to is read from `KON_ILO_EVENTS_URL`.
+  /// Anything but loopback needs `KON_ILO_EVENTS_CERT`/`KEY` set
+  pub ilo_events_bind:    &'static str,
+  /// Channel that pushed Redfish events are posted to
+  pub ilo_events_channel: u64,
+  pub translate_budget:   TranslateBudget
 }
 
 #[cfg(feature = "production")]
@@ -19,19 +74,40 @@ pub static BINARY_PROPERTIES: LazyLock<ConfigMeta> = LazyLock::new(|| {
     .embed_color(0xF1D63C)
     .ready_notify(1311282815601741844)
     .rss_channel(1311282815601741844)
+    .ilo_events_channel(1311282815601741844)
 });
 
 impl ConfigMeta {
   fn new() -> Self {
     Self {
-      env:          "prod".to_string(),
-      embed_color:  0x5A99C7,
-      ready_notify: 1268493237912604672,
-      rss_channel:  865673694184996888,
-      kon_logs:     1268493237912604672,
-      developers:   vec![
+      env:                "prod".to_string(),
+      embed_color:        0x5A99C7,
+      ready_notify:       1268493237912604672,
+      rss_channel:        865673694184996888,
+      kon_logs:           1268493237912604672,
+      developers:         vec![
         190407856527376384, // nwero.sama
-      ]
+      ],
+      ilo_poll_secs:      120,
+      ilo_hosts:          vec![IloHost {
+        id:              "pomni",
+        name:            "POMNI",
+        credentials_env: None,
+        protocol:        BmcProtocol::Redfish,
+        tls:             IloTls::TrustOnFirstUse,
+        sensors:         vec![
+          ("01-Inlet Ambient", "Inlet Ambient"),
+          ("04-P1 DIMM 1-6", "P1 DIMM 1-6"),
+          ("14-Chipset Zone", "Chipset Zone"),
+        ]
+      }],
+      ilo_events_bind:    "127.0.0.1:8470",
+      ilo_events_channel: 1268493237912604672,
+      translate_budget:   TranslateBudget {
+        reserve:     25000,
+        user_daily:  10000,
+        guild_daily: 25000
+      }
     }
   }
 
@@ -71,4 +147,13 @@ impl ConfigMeta {
     self.rss_channel = channel_id;
     self
   }
+
+  #[cfg(not(feature = "production"))]
+  fn ilo_events_channel(
+    mut self,
+    channel_id: u64
+  ) -> Self {
+    self.ilo_events_channel = channel_id;
+    self
+  }
 }
@@ -1,5 +1,11 @@
 mod config;
-pub use config::BINARY_PROPERTIES;
+pub use config::{
+  BINARY_PROPERTIES,
+  BmcProtocol,
+  IloHost,
+  IloTls,
+  TranslateBudget
+};
 
 mod types;
 pub use types::*;
@@ -43,3 +49,9 @@ pub fn mention_dev(ctx: PoiseCtx<'_>) -> Option<String> {
 
   if mentions.is_empty() { None } else { Some(mentions.join(", ")) }
 }
+
+/// Whether the user is listed in [`BINARY_PROPERTIES`]'s developers or is one of the bot's owners
+pub fn is_developer(ctx: PoiseCtx<'_>) -> bool {
+  let author = ctx.author().id;
+  BINARY_PROPERTIES.developers.contains(&author.get()) || ctx.framework().options().owners.contains(&author)
+}
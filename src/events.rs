@@ -1,4 +1,8 @@
 use {
+  kon_cmds::{
+    spawn_ilo_listener,
+    spawn_ilo_monitor
+  },
   kon_libs::{
     BINARY_PROPERTIES,
     BOT_VERSION,
@@ -52,6 +56,9 @@ impl EventHandler for DiscordEvents {
         .send_message(&ctx.http, message.add_embed(ready_embed))
         .await
         .unwrap();
+
+      spawn_ilo_monitor(ctx.http.clone());
+      spawn_ilo_listener(ctx.http.clone());
     }
   }
 }
@@ -1,8 +1,14 @@
-use tokio::{
-  select,
-  signal::unix::{
-    SignalKind,
-    signal
+use {
+  kon_cmds::{
+    close_ilo_sessions,
+    close_ilo_subscriptions
+  },
+  tokio::{
+    select,
+    signal::unix::{
+      SignalKind,
+      signal
+    }
   }
 };
 
@@ -19,5 +25,7 @@ pub async fn gracefully_shutdown() {
     v = s3.recv() => v.unwrap()
   );
 
+  close_ilo_subscriptions().await;
+  close_ilo_sessions().await;
   println!("\nKon says goodbye! 👋");
 }
@@ -36,8 +36,11 @@ impl TSClient {
   }
 }
 
+/// Same as [`token_path`] but leaves the failure to the caller, for background tasks that have to outlive an outage
+pub async fn try_token_path() -> Result<TokenServiceApi, Box<dyn Error + Send + Sync>> { TSCLIENT.lock().await.get().await }
+
 pub async fn token_path() -> TokenServiceApi {
-  match TSCLIENT.lock().await.get().await {
+  match try_token_path().await {
     Ok(a) => a,
     Err(e) => panic!("TSClient[Error] {e}")
   }